@@ -0,0 +1,402 @@
+use proc_macro2::{Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::spanned::Spanned;
+use syn::visit::{self, Visit};
+
+/// Lines `lo..=hi` (0-based) covered by one attribute or by a run of doc comments, and the column it starts at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Extent {
+    /// First line.
+    pub lo: usize,
+    /// Last line (inclusive).
+    pub hi: usize,
+    /// Column (in chars) of the first token.
+    pub col: usize,
+}
+
+/// An item found by parsing a source file, with the line extents of its docs, attributes and own tokens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedItem {
+    /// `fn`, `struct` or `field`.
+    pub kind: &'static str,
+    /// The item's name.
+    pub name: String,
+    /// The enclosing `impl` type, trait, struct or inline `mod`; `None` at the top of the file.
+    pub parent: Option<String>,
+    /// Where the item's own tokens (visibility, `fn`, `struct`, field name, …) start, after its attributes.
+    pub item: Extent,
+    /// Doc comments and `#[doc = ...]` attributes, adjacent ones merged, in source order.
+    pub docs: Vec<Extent>,
+    /// Every other attribute, in source order.
+    pub attrs: Vec<Extent>,
+}
+
+/// Where a doc block goes, in lines of the current file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocSlot {
+    /// Lines `lo..hi` are replaced by the new doc (`lo == hi` inserts before `lo`).
+    pub lo: usize,
+    /// See `lo`.
+    pub hi: usize,
+    /// The line whose indentation the doc takes.
+    pub item_line0: usize,
+    /// Other doc runs of the item (`lo..hi`, exclusive), removed when the doc is replaced.
+    pub extra_docs: Vec<(usize, usize)>,
+}
+
+/// Returns the 0-based line and the column where a span starts.
+fn start_of(span: Span) -> (usize, usize) {
+    let s = span.start();
+    (s.line.saturating_sub(1), s.column)
+}
+
+/// Returns the extent of any spanned syntax node.
+fn extent_of<T: Spanned>(node: &T) -> Extent {
+    let span = node.span();
+    let (lo, col) = start_of(span);
+    Extent {
+        lo,
+        hi: span.end().line.saturating_sub(1).max(lo),
+        col,
+    }
+}
+
+/// Returns where a node's first token after its outer attributes starts.
+fn after_attrs(tokens: TokenStream) -> Extent {
+    let mut iter = tokens.into_iter().peekable();
+    while let Some(TokenTree::Punct(p)) = iter.peek() {
+        if p.as_char() != '#' {
+            break;
+        }
+        iter.next();
+        iter.next();
+    }
+    let (lo, col) = iter.next().map(|t| start_of(t.span())).unwrap_or((0, 0));
+    Extent { lo, hi: lo, col }
+}
+
+impl ParsedItem {
+    /// Builds an item from its attributes and tokens, splitting doc attributes from the others.
+    fn new(
+        kind: &'static str,
+        name: String,
+        parent: Option<String>,
+        attrs: &[syn::Attribute],
+        tokens: TokenStream,
+    ) -> Self {
+        let mut docs: Vec<Extent> = Vec::new();
+        let mut other = Vec::new();
+        for attr in attrs {
+            let e = extent_of(attr);
+            let is_doc =
+                attr.path().is_ident("doc") && matches!(attr.meta, syn::Meta::NameValue(_));
+            if !is_doc {
+                other.push(e);
+                continue;
+            }
+            match docs.last_mut() {
+                Some(prev) if prev.hi + 1 == e.lo => prev.hi = e.hi,
+                _ => docs.push(e),
+            }
+        }
+        Self {
+            kind,
+            name,
+            parent,
+            item: after_attrs(tokens),
+            docs,
+            attrs: other,
+        }
+    }
+
+    /// Computes the slot for the item's doc: the doc run closest to the item is replaced and the others removed;
+    /// an undocumented item gets its doc above its attributes.
+    ///
+    /// Returns:
+    /// - `None` when the docs, attributes or item share a line with each other, or the first of them shares its
+    ///   line with preceding code (`fn a() {} fn b() {}`); whole-line edits would then cut code, so the caller
+    ///   falls back to the line heuristics.
+    pub fn doc_slot(&self, lines: &[&str]) -> Option<DocSlot> {
+        let mut all: Vec<Extent> = self.docs.iter().chain(&self.attrs).copied().collect();
+        all.sort_by_key(|e| e.lo);
+        all.push(self.item);
+        if all.windows(2).any(|w| w[1].lo <= w[0].hi) {
+            return None;
+        }
+        let first = all[0];
+        let before = lines.get(first.lo)?.chars().take(first.col);
+        if !before.collect::<String>().trim().is_empty() {
+            return None;
+        }
+
+        let item_line0 = self.item.lo;
+        Some(match self.docs.split_last() {
+            Some((last, rest)) => DocSlot {
+                lo: last.lo,
+                hi: last.hi + 1,
+                item_line0,
+                extra_docs: rest.iter().map(|e| (e.lo, e.hi + 1)).collect(),
+            },
+            None => {
+                let at = self.attrs.first().map_or(item_line0, |a| a.lo);
+                DocSlot {
+                    lo: at,
+                    hi: at,
+                    item_line0,
+                    extra_docs: Vec::new(),
+                }
+            }
+        })
+    }
+}
+
+/// Collects fns, structs and named fields while walking a file, tracking the enclosing parent.
+#[derive(Default)]
+struct Collector {
+    items: Vec<ParsedItem>,
+    parents: Vec<String>,
+}
+
+impl Collector {
+    fn push(
+        &mut self,
+        kind: &'static str,
+        name: String,
+        attrs: &[syn::Attribute],
+        tokens: TokenStream,
+    ) {
+        let parent = self.parents.last().cloned();
+        self.items
+            .push(ParsedItem::new(kind, name, parent, attrs, tokens));
+    }
+
+    fn nested(&mut self, parent: String, walk: impl FnOnce(&mut Self)) {
+        self.parents.push(parent);
+        walk(self);
+        self.parents.pop();
+    }
+}
+
+impl<'ast> Visit<'ast> for Collector {
+    fn visit_item_fn(&mut self, i: &'ast syn::ItemFn) {
+        self.push("fn", i.sig.ident.to_string(), &i.attrs, i.to_token_stream());
+    }
+
+    fn visit_impl_item_fn(&mut self, i: &'ast syn::ImplItemFn) {
+        self.push("fn", i.sig.ident.to_string(), &i.attrs, i.to_token_stream());
+    }
+
+    fn visit_trait_item_fn(&mut self, i: &'ast syn::TraitItemFn) {
+        self.push("fn", i.sig.ident.to_string(), &i.attrs, i.to_token_stream());
+    }
+
+    fn visit_item_struct(&mut self, i: &'ast syn::ItemStruct) {
+        let name = i.ident.to_string();
+        self.push("struct", name.clone(), &i.attrs, i.to_token_stream());
+        self.nested(name, |c| {
+            for field in &i.fields {
+                if let Some(ident) = &field.ident {
+                    c.push(
+                        "field",
+                        ident.to_string(),
+                        &field.attrs,
+                        field.to_token_stream(),
+                    );
+                }
+            }
+        });
+    }
+
+    fn visit_item_impl(&mut self, i: &'ast syn::ItemImpl) {
+        let name = match &*i.self_ty {
+            syn::Type::Path(p) => p.path.segments.last().map(|s| s.ident.to_string()),
+            _ => None,
+        };
+        self.nested(name.unwrap_or_default(), |c| visit::visit_item_impl(c, i));
+    }
+
+    fn visit_item_trait(&mut self, i: &'ast syn::ItemTrait) {
+        self.nested(i.ident.to_string(), |c| visit::visit_item_trait(c, i));
+    }
+
+    fn visit_item_mod(&mut self, i: &'ast syn::ItemMod) {
+        self.nested(i.ident.to_string(), |c| visit::visit_item_mod(c, i));
+    }
+
+    fn visit_item_macro(&mut self, i: &'ast syn::ItemMacro) {
+        // Items written out inside a macro call keep their source spans, so they can be patched like any other.
+        if let Ok(file) = syn::parse2::<syn::File>(i.mac.tokens.clone()) {
+            self.visit_file(&file);
+        }
+    }
+}
+
+/// Parses a source file and lists its fns (free, inherent, trait), structs and named fields.
+///
+/// Returns:
+/// - `None` if the file does not parse.
+pub fn parse_items(src: &str) -> Option<Vec<ParsedItem>> {
+    let file = syn::parse_file(src).ok()?;
+    let mut collector = Collector::default();
+    collector.visit_file(&file);
+    Some(collector.items)
+}
+
+/// Identifies an item for [`verify_items`]: kind, parent and name.
+fn item_key(item: &ParsedItem) -> String {
+    match &item.parent {
+        Some(parent) => format!("{} {parent}::{}", item.kind, item.name),
+        None => format!("{} {}", item.kind, item.name),
+    }
+}
+
+/// Checks that patched text still parses and declares the same fns, structs and fields as before.
+///
+/// Parameters:
+/// - `before`: The items of the original file, from [`parse_items`].
+/// - `after`: The patched file contents.
+///
+/// Errors:
+/// - A description of the problem: the file no longer parses, or which items disappeared or appeared.
+pub fn verify_items(before: &[ParsedItem], after: &str) -> std::result::Result<(), String> {
+    let after = parse_items(after).ok_or_else(|| "the file no longer parses".to_string())?;
+    let mut old: Vec<String> = before.iter().map(item_key).collect();
+    let mut new: Vec<String> = after.iter().map(item_key).collect();
+    old.sort();
+    new.sort();
+    if old == new {
+        return Ok(());
+    }
+    let lost: Vec<&str> = old
+        .iter()
+        .filter(|k| !new.contains(k))
+        .map(String::as_str)
+        .collect();
+    let gained: Vec<&str> = new
+        .iter()
+        .filter(|k| !old.contains(k))
+        .map(String::as_str)
+        .collect();
+    Err(format!(
+        "the set of items changed (lost: [{}], gained: [{}])",
+        lost.join(", "),
+        gained.join(", ")
+    ))
+}
+
+/// Picks the parsed item a generated doc is for.
+///
+/// Parameters:
+/// - `items`: From [`parse_items`].
+/// - `kind`, `fqpath`: The result's kind and path; the last segment is the name and the one before it the parent.
+/// - `near_line0`: The item's recorded (or re-located) line; among equal candidates the closest one wins.
+///
+/// Returns:
+/// - The item, preferring ones whose parent matches, then top-level ones; `None` if no item has that kind and name.
+pub fn find_item<'a>(
+    items: &'a [ParsedItem],
+    kind: &str,
+    fqpath: &str,
+    near_line0: usize,
+) -> Option<&'a ParsedItem> {
+    let mut segments = fqpath.rsplit("::");
+    let name = segments.next()?;
+    let parent = segments.next();
+    let same: Vec<&ParsedItem> = items
+        .iter()
+        .filter(|i| i.kind == kind && i.name == name)
+        .collect();
+    let in_parent: Vec<&ParsedItem> = same
+        .iter()
+        .copied()
+        .filter(|i| i.parent.is_some() && i.parent.as_deref() == parent)
+        .collect();
+    let pool = if in_parent.is_empty() {
+        same.into_iter().filter(|i| i.parent.is_none()).collect()
+    } else {
+        in_parent
+    };
+    let top = |i: &ParsedItem| {
+        i.docs
+            .iter()
+            .chain(&i.attrs)
+            .map(|e| e.lo)
+            .min()
+            .unwrap_or(i.item.lo)
+    };
+    pool.into_iter().min_by_key(|i| {
+        near_line0
+            .abs_diff(i.item.lo)
+            .min(near_line0.abs_diff(top(i)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---------- parse_items / find_item ----------
+
+    #[test]
+    fn test_parse_items_finds_items_the_regexes_miss() {
+        let src = "pub(in crate::a) fn one<T>(\n    x: T,\n) -> T {\n    x\n}\nfn two() {}\nimpl S {\n    /// Old.\n    #[inline]\n    default fn three(&self) {}\n}\nmake! {\n    pub fn four() {}\n}\n";
+        let items = parse_items(src).expect("parses");
+
+        let one = find_item(&items, "fn", "crate::one", 0).expect("multi-line fn");
+        assert_eq!(one.item.lo, 0);
+        let two = find_item(&items, "fn", "crate::two", 5).expect("adjacent fn");
+        assert_eq!(
+            two.doc_slot(&src.lines().collect::<Vec<_>>()),
+            Some(DocSlot {
+                lo: 5,
+                hi: 5,
+                item_line0: 5,
+                extra_docs: vec![]
+            }),
+            "the doc goes right above `two`, not above the `one` it touches"
+        );
+
+        let three = find_item(&items, "fn", "crate::S::three", 9).expect("`default fn` in an impl");
+        assert_eq!(three.parent.as_deref(), Some("S"));
+        assert_eq!(
+            three.doc_slot(&src.lines().collect::<Vec<_>>()),
+            Some(DocSlot {
+                lo: 7,
+                hi: 8,
+                item_line0: 9,
+                extra_docs: vec![]
+            })
+        );
+
+        let four = find_item(&items, "fn", "crate::four", 12).expect("fn inside a macro call");
+        assert_eq!(four.item.lo, 12);
+        assert!(parse_items("fn broken( {").is_none());
+    }
+
+    #[test]
+    fn test_doc_slot_refuses_shared_lines() {
+        let src = "fn a() {} fn b() {}\n";
+        let items = parse_items(src).expect("parses");
+        let b = find_item(&items, "fn", "crate::b", 0).expect("found");
+        assert_eq!(b.doc_slot(&src.lines().collect::<Vec<_>>()), None);
+    }
+
+    // ---------- verify_items ----------
+
+    #[test]
+    fn test_verify_items_reports_lost_and_gained_items() {
+        let before = parse_items("fn one() {}\nfn two() {}\n").expect("parses");
+        assert_eq!(
+            verify_items(&before, "/// One.\nfn one() {}\nfn two() {}\n"),
+            Ok(())
+        );
+        assert_eq!(
+            verify_items(&before, "fn one() {}\n// fn two() {}\nfn three() {}\n"),
+            Err("the set of items changed (lost: [fn two], gained: [fn three])".to_string())
+        );
+        assert_eq!(
+            verify_items(&before, "fn one() {\n"),
+            Err("the file no longer parses".to_string())
+        );
+    }
+}
@@ -0,0 +1,593 @@
+use crate::doccomment::to_line_comments;
+use crate::lock::{DocState, Lockfile};
+use crate::model::Row;
+use crate::sidecar::row_doc;
+use crate::style::{parse_doc, strip_doc_prefix};
+use crate::validate::is_unsafe_fn;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Output format of `awful_rustdocs audit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum AuditFormat {
+    /// Aligned plain-text table followed by per-visibility and per-module totals.
+    Table,
+    /// The full [`AuditReport`] as pretty-printed JSON.
+    Json,
+}
+
+/// One documentation problem found on a harvested item.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Finding {
+    /// Issue code: `undocumented`, `stale-param`, `missing-errors`, `missing-safety`, `trivial-doc` or `stale-doc`.
+    pub issue: &'static str,
+    /// Fully-qualified path of the item.
+    pub fqpath: String,
+    /// The item kind (`fn`, `struct`, …).
+    pub kind: String,
+    /// The item visibility as harvested (`pub`, `pub(crate)`, `private`, …).
+    pub visibility: String,
+    /// Source file of the item.
+    pub file: String,
+    /// 1-based line of the item, if known.
+    pub line: Option<u32>,
+    /// Human-readable explanation.
+    pub detail: String,
+}
+
+/// Undocumented / total item counts for one group (a visibility or a module).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct DocCount {
+    /// Items without any doc.
+    pub undocumented: usize,
+    /// All audited items.
+    pub total: usize,
+}
+
+impl DocCount {
+    /// Counts one item.
+    pub fn add(&mut self, documented: bool) {
+        self.total += 1;
+        self.undocumented += usize::from(!documented);
+    }
+
+    /// Percentage of documented items; an empty group counts as fully documented.
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            return 100.0;
+        }
+        100.0 * (self.total - self.undocumented) as f64 / self.total as f64
+    }
+}
+
+/// The result of auditing a harvest.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AuditReport {
+    /// Every finding, grouped by file and ordered by line.
+    pub findings: Vec<Finding>,
+    /// Undocumented items per visibility.
+    pub by_visibility: BTreeMap<String, DocCount>,
+    /// Undocumented items per module (`crate::a::b`).
+    pub by_module: BTreeMap<String, DocCount>,
+}
+
+/// Returns the module of an item: the harvested `module_path` when present, otherwise the fqpath minus its
+/// last segment.
+pub fn module_of(row: &Row) -> String {
+    match &row.module_path {
+        Some(m) if !m.is_empty() => {
+            let joined = m.join("::");
+            if joined.starts_with("crate") {
+                joined
+            } else {
+                format!("crate::{joined}")
+            }
+        }
+        _ => row
+            .fqpath
+            .rsplit_once("::")
+            .map(|(m, _)| m.to_string())
+            .unwrap_or_else(|| "crate".into()),
+    }
+}
+
+/// Returns the byte range of a function signature's parameter list, without its parentheses.
+///
+/// The list is the first `(` after the `fn` keyword that is not inside the generic parameters, so neither
+/// `pub(crate)` nor an `F: Fn(u8) -> u8` bound is taken for it. Brackets are tracked through `()`, `<>` and `[]`;
+/// the `>` of a `->` closes nothing.
+fn param_list(signature: &str) -> Option<(usize, usize)> {
+    let fn_kw = signature.match_indices("fn").find(|&(i, _)| {
+        let before = signature[..i].chars().next_back();
+        let after = signature[i + 2..].chars().next();
+        before.is_none_or(char::is_whitespace) && after.is_some_and(char::is_whitespace)
+    })?;
+    let mut depth = 0i32;
+    let mut open = None;
+    let mut prev = ' ';
+    for (i, c) in signature
+        .char_indices()
+        .skip_while(|&(i, _)| i < fn_kw.0 + 2)
+    {
+        let arrow = prev == '-' && c == '>';
+        prev = c;
+        match c {
+            '(' if depth == 0 && open.is_none() => open = Some(i + 1),
+            ')' if depth == 0 => return open.map(|o| (o, i)),
+            '(' | '<' | '[' => depth += 1,
+            '>' if arrow => {}
+            ')' | '>' | ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Returns the parameter names declared in a function signature, excluding `self` receivers.
+///
+/// Top-level commas are found by tracking `()`, `<>` and `[]` nesting, so `f(a: Vec<(u8, u8)>, b: u8)`
+/// yields `a` and `b`; the `>` of a `->` in `impl Fn(u8) -> u8` does not close anything, and the parameter
+/// list is found past generics (see [`param_list`]). Patterns such as `mut x` or `&x` yield `x`.
+pub fn signature_params(signature: &str) -> Vec<String> {
+    let Some((open, close)) = param_list(signature) else {
+        return vec![];
+    };
+    let mut depth = 0i32;
+    let mut params = Vec::new();
+    let mut current = String::new();
+    let mut prev = ' ';
+    for c in signature[open..close].chars() {
+        let arrow = prev == '-' && c == '>';
+        prev = c;
+        match c {
+            '(' | '<' | '[' => depth += 1,
+            '>' if arrow => {}
+            ')' | '>' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                params.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    params.push(current);
+
+    params
+        .iter()
+        .filter_map(|p| p.split_once(':').map(|(pat, _)| pat))
+        .map(|pat| {
+            pat.trim()
+                .trim_start_matches('&')
+                .trim_start_matches("mut ")
+                .trim()
+                .to_string()
+        })
+        .filter(|name| !name.is_empty() && name != "self" && !name.ends_with("self"))
+        .collect()
+}
+
+/// Returns the parameter names listed in a doc's `Parameters` section (lines like ``- `name`: …``).
+fn documented_params(doc: &str) -> Vec<String> {
+    let parsed = parse_doc(doc);
+    let Some(section) = parsed.sections.iter().find(|s| s.title == "Parameters") else {
+        return vec![];
+    };
+    section
+        .lines
+        .iter()
+        .filter_map(|l| {
+            let item = l.trim_start().strip_prefix(['-', '*'])?.trim_start();
+            let rest = item.strip_prefix('`')?;
+            let (name, _) = rest.split_once('`')?;
+            Some(name.trim().to_string())
+        })
+        .collect()
+}
+
+/// Returns `true` if a signature's return type is a `Result` (plain, qualified or aliased as `…Result<…>`).
+///
+/// The return type is what follows the `->` right after the parameter list, so a `Result` inside it (such as
+/// `Box<dyn Fn() -> Result<u8, E>>`) does not count.
+fn returns_result(signature: &str) -> bool {
+    param_list(signature)
+        .and_then(|(_, close)| signature[close + 1..].trim_start().strip_prefix("->"))
+        .map(|ret| {
+            let ret = ret.split(" where ").next().unwrap_or(ret).trim();
+            let head = ret.split('<').next().unwrap_or(ret);
+            head.rsplit("::").next().unwrap_or(head).ends_with("Result")
+        })
+        .unwrap_or(false)
+}
+
+/// Audits harvested rows for documentation debt without contacting a model.
+///
+/// Parameters:
+/// - `rows`: The harvested items; struct fields and `impl` blocks are not audited.
+/// - `lock`: The lockfile of generated docs; pass an empty one when there is none.
+///
+/// Returns:
+/// - An [`AuditReport`] with these findings per item:
+///   - `undocumented`: the item has no doc.
+///   - `stale-param`: the `Parameters` section names a parameter the signature no longer has.
+///   - `missing-errors`: a `pub` function returns `Result` but has no `Errors` section.
+///   - `missing-safety`: an `unsafe fn` has no `Safety` section.
+///   - `trivial-doc`: the doc is empty or a single word.
+///   - `stale-doc`: the doc was generated, but the signature or body changed since.
+pub fn audit_rows(rows: &[Row], lock: &Lockfile) -> AuditReport {
+    let mut report = AuditReport::default();
+
+    let mut audited: Vec<&Row> = rows
+        .iter()
+        .filter(|r| r.kind != "field" && r.kind != "impl")
+        .collect();
+    audited.sort_by(|a, b| {
+        (a.file.as_str(), a.span.start_line).cmp(&(b.file.as_str(), b.span.start_line))
+    });
+
+    for row in audited {
+        let documented = row.had_doc();
+        for count in [
+            report
+                .by_visibility
+                .entry(row.visibility.clone())
+                .or_default(),
+            report.by_module.entry(module_of(row)).or_default(),
+        ] {
+            count.add(documented);
+        }
+
+        let mut finding = |issue: &'static str, detail: String| {
+            report.findings.push(Finding {
+                issue,
+                fqpath: row.fqpath.clone(),
+                kind: row.kind.clone(),
+                visibility: row.visibility.clone(),
+                file: row.file.clone(),
+                line: row.span.start_line,
+                detail,
+            });
+        };
+
+        if !documented {
+            finding("undocumented", format!("{} has no doc", row.kind));
+            continue;
+        }
+        let doc = to_line_comments(&row_doc(row));
+
+        let words: usize = doc
+            .lines()
+            .map(|l| strip_doc_prefix(l).split_whitespace().count())
+            .sum();
+        if words <= 1 {
+            finding("trivial-doc", format!("doc has {words} word(s)"));
+        }
+        if let Some(changed) = lock.state(row).as_ref().and_then(DocState::changed) {
+            finding(
+                "stale-doc",
+                format!("{changed} changed since the doc was generated"),
+            );
+        }
+
+        if row.kind != "fn" {
+            continue;
+        }
+        let titles: BTreeSet<String> = parse_doc(&doc)
+            .sections
+            .into_iter()
+            .map(|s| s.title)
+            .collect();
+
+        let params = signature_params(&row.signature);
+        let stale: Vec<String> = documented_params(&doc)
+            .into_iter()
+            .filter(|p| !params.contains(p))
+            .collect();
+        if !stale.is_empty() {
+            finding(
+                "stale-param",
+                format!(
+                    "documents {} but the signature has ({})",
+                    stale
+                        .iter()
+                        .map(|p| format!("`{p}`"))
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                    params.join(", ")
+                ),
+            );
+        }
+        if row.visibility.starts_with("pub")
+            && returns_result(&row.signature)
+            && !titles.contains("Errors")
+        {
+            finding(
+                "missing-errors",
+                "returns `Result` without an Errors section".into(),
+            );
+        }
+        if is_unsafe_fn(&row.signature) && !titles.contains("Safety") {
+            finding(
+                "missing-safety",
+                "`unsafe fn` without a Safety section".into(),
+            );
+        }
+    }
+    report
+}
+
+/// Formats a report as an aligned plain-text table followed by totals.
+pub fn render_table(report: &AuditReport) -> String {
+    use std::fmt::Write;
+    let mut s = String::new();
+
+    let location = |f: &Finding| match f.line {
+        Some(l) => format!("{}:{l}", f.file),
+        None => f.file.clone(),
+    };
+    let header = ["ISSUE", "VISIBILITY", "ITEM", "LOCATION", "DETAIL"];
+    let rows: Vec<[String; 5]> = report
+        .findings
+        .iter()
+        .map(|f| {
+            [
+                f.issue.to_string(),
+                f.visibility.clone(),
+                f.fqpath.clone(),
+                location(f),
+                f.detail.clone(),
+            ]
+        })
+        .collect();
+    let mut widths = header.map(str::len);
+    for r in &rows {
+        for (w, cell) in widths.iter_mut().zip(r) {
+            *w = (*w).max(cell.chars().count());
+        }
+    }
+    let line = |cells: &[&str]| {
+        cells
+            .iter()
+            .zip(widths)
+            .map(|(c, w)| format!("{c:<w$}"))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    writeln!(s, "{}", line(&header)).ok();
+    for r in &rows {
+        writeln!(s, "{}", line(&r.each_ref().map(String::as_str))).ok();
+    }
+
+    writeln!(s, "\n{} finding(s)", report.findings.len()).ok();
+    for (title, groups) in [
+        ("visibility", &report.by_visibility),
+        ("module", &report.by_module),
+    ] {
+        writeln!(s, "\nUndocumented by {title}:").ok();
+        for (name, c) in groups {
+            writeln!(s, "  {name:<30} {:>4} / {:<4}", c.undocumented, c.total).ok();
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Span;
+
+    // ---------- helpers ----------
+
+    fn mk_row(fqpath: &str, visibility: &str, signature: &str, doc: Option<&str>) -> Row {
+        Row {
+            kind: if signature.contains("fn ") {
+                "fn"
+            } else {
+                "struct"
+            }
+            .into(),
+            name: fqpath.rsplit("::").next().unwrap_or("").into(),
+            crate_name: Some("demo".into()),
+            module_path: None,
+            fqpath: fqpath.into(),
+            visibility: visibility.into(),
+            file: "src/lib.rs".into(),
+            span: Span {
+                start_line: Some(1),
+                end_line: None,
+                start_byte: None,
+                end_byte: None,
+            },
+            signature: signature.into(),
+            has_body: true,
+            doc: doc.map(String::from),
+            body_text: None,
+            callers: None,
+            file_hash: None,
+        }
+    }
+
+    // ---------- signature_params ----------
+
+    #[test]
+    fn test_signature_params_skips_receivers_and_nested_commas() {
+        assert_eq!(
+            signature_params("pub fn f(&mut self, mut a: Vec<(u8, u8)>, b: [u8; 2]) -> u8"),
+            vec!["a", "b"]
+        );
+        assert!(signature_params("fn g()").is_empty());
+    }
+
+    #[test]
+    fn test_signature_params_ignores_return_arrows_in_parameter_types() {
+        assert_eq!(
+            signature_params("fn f(g: impl Fn(u8) -> u8, h: fn(&str) -> Vec<u8>, x: u32) -> u8"),
+            vec!["g", "h", "x"],
+            "the `>` of `->` must not close a bracket"
+        );
+    }
+
+    #[test]
+    fn test_signature_params_skips_generics_and_where_clauses() {
+        assert_eq!(
+            signature_params("pub fn apply<F: Fn(u8) -> u8>(f: F, x: u8) -> u8"),
+            vec!["f", "x"],
+            "the `(` of an `Fn(..)` bound is not the parameter list"
+        );
+        assert_eq!(
+            signature_params(
+                "pub(crate) fn run<T, G>(items: &[T], g: G) -> Vec<T> where G: FnMut(&T) -> bool"
+            ),
+            vec!["items", "g"]
+        );
+    }
+
+    // ---------- returns_result ----------
+
+    #[test]
+    fn test_returns_result_reads_only_the_outer_return_type() {
+        assert!(returns_result(
+            "pub fn f<F: Fn() -> u8>(f: F) -> io::Result<()>"
+        ));
+        assert!(returns_result(
+            "fn g(x: u8) -> Result<u8, E> where E: From<std::io::Error>"
+        ));
+        assert!(!returns_result("fn h() -> Box<dyn Fn() -> Result<u8, E>>"));
+        assert!(!returns_result("fn k(f: impl Fn() -> Result<u8, E>)"));
+    }
+
+    // ---------- audit_rows ----------
+
+    #[test]
+    fn test_audit_rows_reports_each_issue_kind() {
+        let rows = vec![
+            mk_row("crate::a::undoc", "pub", "pub fn undoc()", None),
+            mk_row(
+                "crate::a::stale",
+                "pub",
+                "pub fn stale(input: &str) -> Result<(), Error>",
+                Some("/// Parses it.\n///\n/// ## Parameters\n/// - `text`: The input."),
+            ),
+            mk_row(
+                "crate::b::raw",
+                "pub(crate)",
+                "pub(crate) unsafe fn raw(p: *const u8) -> u8",
+                Some("/// Reads."),
+            ),
+            mk_row(
+                "crate::b::Fine",
+                "pub",
+                "pub struct Fine",
+                Some("/// A well documented struct."),
+            ),
+        ];
+        let report = audit_rows(&rows, &Lockfile::default());
+        let issues: Vec<(&str, &str)> = report
+            .findings
+            .iter()
+            .map(|f| (f.issue, f.fqpath.as_str()))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                ("undocumented", "crate::a::undoc"),
+                ("stale-param", "crate::a::stale"),
+                ("missing-errors", "crate::a::stale"),
+                ("trivial-doc", "crate::b::raw"),
+                ("missing-safety", "crate::b::raw"),
+            ],
+            "{report:#?}"
+        );
+        assert_eq!(
+            report.by_module["crate::a"],
+            DocCount {
+                undocumented: 1,
+                total: 2
+            }
+        );
+        assert_eq!(report.by_visibility["pub"].total, 3);
+    }
+
+    #[test]
+    fn test_audit_rows_reports_docs_generated_before_a_code_change() {
+        let doc = "/// Returns the answer.";
+        let before = mk_row(
+            "crate::a::answer",
+            "pub",
+            "pub fn answer() -> u8",
+            Some(doc),
+        );
+        let mut lock = Lockfile::default();
+        lock.record(&before, doc);
+
+        let after = mk_row(
+            "crate::a::answer",
+            "pub",
+            "pub fn answer() -> u16",
+            Some(doc),
+        );
+        let report = audit_rows(&[after], &lock);
+        assert_eq!(report.findings.len(), 1, "{report:#?}");
+        assert_eq!(report.findings[0].issue, "stale-doc");
+        assert_eq!(
+            report.findings[0].detail,
+            "signature changed since the doc was generated"
+        );
+
+        let edited = mk_row(
+            "crate::a::answer",
+            "pub",
+            "pub fn answer() -> u16",
+            Some("/// 42."),
+        );
+        assert!(
+            audit_rows(&[edited], &lock)
+                .findings
+                .iter()
+                .all(|f| f.issue != "stale-doc"),
+            "hand-edited docs are not reported as stale"
+        );
+    }
+
+    #[test]
+    fn test_audit_rows_reads_sidecar_docs() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("docs")).unwrap();
+        std::fs::write(
+            dir.path().join("docs").join("crate.a.parse.md"),
+            "Parses `input`.\n\n# Parameters\n- `input`: The text.\n\n# Errors\nIf it is not a number.\n",
+        )
+        .unwrap();
+        let row = Row {
+            file: dir.path().join("src/lib.rs").to_string_lossy().into_owned(),
+            ..mk_row(
+                "crate::a::parse",
+                "pub",
+                "pub fn parse(input: &str) -> Result<u8, Error>",
+                Some("#[doc = include_str!(\"../docs/crate.a.parse.md\")]"),
+            )
+        };
+        let report = audit_rows(&[row], &Lockfile::default());
+        assert!(
+            report.findings.is_empty(),
+            "the sidecar is audited, not the include line: {report:#?}"
+        );
+    }
+
+    #[test]
+    fn test_render_table_lists_findings_and_totals() {
+        let rows = vec![mk_row("crate::a::undoc", "pub", "pub fn undoc()", None)];
+        let table = render_table(&audit_rows(&rows, &Lockfile::default()));
+        assert!(
+            table.starts_with("ISSUE") && table.contains("undocumented  pub"),
+            "TABLE:\n{table}"
+        );
+        assert!(table.contains("1 finding(s)"), "TABLE:\n{table}");
+        assert!(table.contains("crate::a"), "TABLE:\n{table}");
+    }
+}
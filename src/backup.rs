@@ -0,0 +1,359 @@
+use crate::error::{Error, Result};
+use crate::util::stable_hash;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The directory per-run backups are kept in under the output directory, one subdirectory per run id.
+pub const BACKUP_DIR: &str = "backups";
+
+const MANIFEST: &str = "manifest.json";
+
+/// Maps an I/O error to `Error::Io` for `path`.
+fn io_err(path: &Path) -> impl FnOnce(std::io::Error) -> Error + '_ {
+    move |e| Error::Io {
+        path: Some(path.to_path_buf()),
+        source: e,
+    }
+}
+
+/// Replaces a file's contents atomically: the text is written and synced to a temporary file next to it, which is
+/// then renamed over the original. The original's permissions are kept.
+///
+/// Errors:
+/// - `Error::Io` if the temporary file cannot be written or renamed.
+pub fn atomic_write(path: &Path, contents: &str) -> Result<()> {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{name}.awful_rustdocs.tmp"));
+
+    let mut f = fs::File::create(&tmp).map_err(io_err(&tmp))?;
+    f.write_all(contents.as_bytes())
+        .and_then(|_| f.sync_all())
+        .map_err(io_err(&tmp))?;
+    if let Ok(meta) = fs::metadata(path) {
+        fs::set_permissions(&tmp, meta.permissions()).map_err(io_err(&tmp))?;
+    }
+    fs::rename(&tmp, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        Error::Io {
+            path: Some(path.to_path_buf()),
+            source: e,
+        }
+    })
+}
+
+/// One file saved by a run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BackedUpFile {
+    /// The patched file, as the run addressed it.
+    pub path: String,
+    /// The saved original, relative to the run directory.
+    pub backup: String,
+    /// [`stable_hash`] of the text the run wrote; `undo` refuses to restore a file that no longer has it.
+    pub patched_hash: String,
+    /// The run created the file (a new sidecar); `undo` deletes it instead of restoring it.
+    #[serde(default)]
+    pub created: bool,
+}
+
+/// The record of one patching run.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// The run id (also the directory name).
+    pub run_id: String,
+    /// Seconds since the Unix epoch when the run started.
+    pub created: u64,
+    /// Every file the run patched, in write order.
+    pub files: Vec<BackedUpFile>,
+}
+
+/// Saves the originals of the files a run patches, so the run can be undone.
+#[derive(Debug)]
+pub struct Backup {
+    dir: PathBuf,
+    manifest: Manifest,
+}
+
+impl Backup {
+    /// Starts a run under `root`; nothing is written until the first file is saved.
+    pub fn start(root: &Path) -> Self {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let run_id = format!("{created:010}-{}", std::process::id());
+        Self {
+            dir: root.join(&run_id),
+            manifest: Manifest {
+                run_id,
+                created,
+                files: Vec::new(),
+            },
+        }
+    }
+
+    /// The id `undo` accepts for this run.
+    pub fn run_id(&self) -> &str {
+        &self.manifest.run_id
+    }
+
+    /// Saves `original` as the pre-run contents of `path`, which is about to be replaced with `patched`.
+    ///
+    /// The manifest is rewritten after every file, so a run that stops half way can still be undone.
+    ///
+    /// Errors:
+    /// - `Error::Io` / `Error::Json` if the backup or the manifest cannot be written.
+    pub fn save(&mut self, path: &str, original: &str, patched: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir).map_err(io_err(&self.dir))?;
+        let name = Path::new(path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "file".into());
+        let backup = format!("{:04}-{name}", self.manifest.files.len());
+        let target = self.dir.join(&backup);
+        fs::write(&target, original).map_err(io_err(&target))?;
+
+        self.manifest.files.push(BackedUpFile {
+            path: path.to_string(),
+            backup,
+            patched_hash: stable_hash(patched),
+            created: !Path::new(path).exists(),
+        });
+        let bytes = serde_json::to_vec_pretty(&self.manifest).map_err(|e| Error::Json {
+            context: "serialize backup manifest",
+            source: e,
+        })?;
+        let manifest = self.dir.join(MANIFEST);
+        fs::write(&manifest, bytes).map_err(io_err(&manifest))?;
+        debug!(file = path, run = %self.manifest.run_id, "backed up original");
+        Ok(())
+    }
+}
+
+/// Lists the recorded runs under `root`, oldest first.
+///
+/// Errors:
+/// - `Error::Io` / `Error::Json` if a manifest cannot be read.
+pub fn list_runs(root: &Path) -> Result<Vec<Manifest>> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Ok(Vec::new());
+    };
+    let mut runs = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path().join(MANIFEST);
+        if !path.is_file() {
+            continue;
+        }
+        let text = fs::read_to_string(&path).map_err(io_err(&path))?;
+        runs.push(
+            serde_json::from_str::<Manifest>(&text).map_err(|e| Error::Json {
+                context: "parse backup manifest",
+                source: e,
+            })?,
+        );
+    }
+    runs.sort_by(|a, b| (a.created, &a.run_id).cmp(&(b.created, &b.run_id)));
+    Ok(runs)
+}
+
+/// Restores the files patched by a run and deletes its backup.
+///
+/// Parameters:
+/// - `root`: The backup root (`<out dir>/`[`BACKUP_DIR`]).
+/// - `run_id`: The run to undo; the latest one when `None`.
+///
+/// Returns:
+/// - The restored file paths, including those of files the run created, which are deleted.
+///
+/// Errors:
+/// - `Error::External` if there is no such run, or if any patched file changed since the run (nothing is restored
+///   then; the changed files are listed). A file that still matches its backup, because the run failed before
+///   writing it, counts as restored.
+/// - `Error::Io` if a backup cannot be read or a file cannot be written.
+pub fn undo(root: &Path, run_id: Option<&str>) -> Result<Vec<String>> {
+    let runs = list_runs(root)?;
+    let run = match run_id {
+        Some(id) => runs.iter().find(|r| r.run_id == id),
+        None => runs.last(),
+    }
+    .ok_or_else(|| Error::External {
+        context: "nothing to undo",
+        message: match run_id {
+            Some(id) => format!("no backup for run '{id}' under {}", root.display()),
+            None => format!("no backups under {}", root.display()),
+        },
+    })?;
+    let dir = root.join(&run.run_id);
+
+    // A file still in its pre-run state was never written (the run failed on it), so there is nothing to undo.
+    let unpatched = |f: &BackedUpFile| {
+        if f.created {
+            return !Path::new(&f.path).exists();
+        }
+        match (
+            fs::read_to_string(&f.path),
+            fs::read_to_string(dir.join(&f.backup)),
+        ) {
+            (Ok(now), Ok(original)) => now == original,
+            _ => false,
+        }
+    };
+    let pending: Vec<&BackedUpFile> = run.files.iter().filter(|f| !unpatched(f)).collect();
+
+    let changed: Vec<&str> = pending
+        .iter()
+        .filter(|f| {
+            fs::read_to_string(&f.path).map_or(true, |now| stable_hash(&now) != f.patched_hash)
+        })
+        .map(|f| f.path.as_str())
+        .collect();
+    if !changed.is_empty() {
+        return Err(Error::External {
+            context: "refusing to undo",
+            message: format!(
+                "run {}: these files changed since they were patched: {}",
+                run.run_id,
+                changed.join(", ")
+            ),
+        });
+    }
+
+    let mut restored = Vec::new();
+    for f in pending.into_iter().rev() {
+        if f.created {
+            fs::remove_file(&f.path).map_err(io_err(Path::new(&f.path)))?;
+            restored.push(f.path.clone());
+            continue;
+        }
+        let saved = dir.join(&f.backup);
+        let original = fs::read_to_string(&saved).map_err(io_err(&saved))?;
+        atomic_write(Path::new(&f.path), &original)?;
+        restored.push(f.path.clone());
+    }
+    fs::remove_dir_all(&dir).map_err(io_err(&dir))?;
+    info!(run = %run.run_id, files = restored.len(), "undo complete");
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---------- atomic_write ----------
+
+    #[test]
+    fn test_atomic_write_replaces_contents_and_leaves_no_temp_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("lib.rs");
+        fs::write(&file, "old").unwrap();
+        atomic_write(&file, "new").unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+        assert_eq!(
+            fs::read_dir(dir.path()).unwrap().count(),
+            1,
+            "the temporary file is renamed away"
+        );
+    }
+
+    // ---------- undo ----------
+
+    #[test]
+    fn test_undo_restores_latest_run_and_refuses_changed_files() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path().join("backups");
+        let file = dir.path().join("lib.rs");
+        let path = file.to_string_lossy().into_owned();
+
+        fs::write(&file, "fn a() {}\n").unwrap();
+        let mut first = Backup::start(&root);
+        first.manifest.run_id = "0001".into();
+        first.dir = root.join("0001");
+        first
+            .save(&path, "fn a() {}\n", "/// A.\nfn a() {}\n")
+            .unwrap();
+        atomic_write(&file, "/// A.\nfn a() {}\n").unwrap();
+
+        let mut second = Backup::start(&root);
+        second.manifest.run_id = "0002".into();
+        second.dir = root.join("0002");
+        second
+            .save(&path, "/// A.\nfn a() {}\n", "/// Does a.\nfn a() {}\n")
+            .unwrap();
+        fs::write(&file, "/// Does a.\nfn a() {} // edited\n").unwrap();
+
+        let err = undo(&root, None).expect_err("the file changed after the run");
+        assert!(err.to_string().contains("lib.rs"), "{err}");
+
+        fs::write(&file, "/// Does a.\nfn a() {}\n").unwrap();
+        assert_eq!(undo(&root, None).unwrap(), vec![path.clone()]);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "/// A.\nfn a() {}\n");
+        assert_eq!(
+            list_runs(&root)
+                .unwrap()
+                .iter()
+                .map(|r| r.run_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["0001"],
+            "an undone run is removed, so the next undo goes one run further back"
+        );
+
+        assert_eq!(undo(&root, Some("0001")).unwrap(), vec![path]);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "fn a() {}\n");
+        assert!(undo(&root, Some("0001")).is_err());
+    }
+
+    #[test]
+    fn test_undo_skips_files_the_run_failed_to_write() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path().join("backups");
+        let (a, b) = (dir.path().join("a.rs"), dir.path().join("b.rs"));
+        let (pa, pb) = (
+            a.to_string_lossy().into_owned(),
+            b.to_string_lossy().into_owned(),
+        );
+        fs::write(&a, "fn a() {}\n").unwrap();
+        fs::write(&b, "fn b() {}\n").unwrap();
+
+        let mut run = Backup::start(&root);
+        run.save(&pa, "fn a() {}\n", "/// A.\nfn a() {}\n").unwrap();
+        atomic_write(&a, "/// A.\nfn a() {}\n").unwrap();
+        // saved, then the write of b failed: b is still the original
+        run.save(&pb, "fn b() {}\n", "/// B.\nfn b() {}\n").unwrap();
+
+        assert_eq!(
+            undo(&root, None).unwrap(),
+            vec![pa],
+            "only the written file is restored"
+        );
+        assert_eq!(fs::read_to_string(&a).unwrap(), "fn a() {}\n");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "fn b() {}\n");
+    }
+
+    #[test]
+    fn test_undo_deletes_files_the_run_created() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let root = dir.path().join("backups");
+        let md = dir.path().join("docs").join("crate.f.md");
+        let path = md.to_string_lossy().into_owned();
+
+        let mut run = Backup::start(&root);
+        run.save(&path, "", "Does f.\n").unwrap();
+        fs::create_dir_all(md.parent().unwrap()).unwrap();
+        atomic_write(&md, "Does f.\n").unwrap();
+        assert!(run.manifest.files[0].created);
+
+        assert_eq!(undo(&root, None).unwrap(), vec![path]);
+        assert!(
+            !md.exists(),
+            "a sidecar the run created is removed, not emptied"
+        );
+    }
+}
@@ -1,8 +1,16 @@
-use crate::defaults::{DEFAULT_CONFIG_YAML, DEFAULT_RUSTDOC_FN_YAML, DEFAULT_RUSTDOC_STRUCT_YAML};
+use crate::audit::AuditFormat;
+use crate::coverage::CoverageFormat;
+use crate::defaults::{
+    DEFAULT_CONFIG_YAML, DEFAULT_FN_PROMPT_MD, DEFAULT_RUSTDOC_CRITIQUE_YAML,
+    DEFAULT_RUSTDOC_FN_YAML, DEFAULT_RUSTDOC_STRUCT_YAML, DEFAULT_STRUCT_PROMPT_MD,
+};
 use crate::error::{Error, Result};
+use crate::lock::LOCK_FILE;
+use crate::report::{OUT_DIR, ReportFormat};
 use clap::{ArgAction, Parser, Subcommand};
 use directories::ProjectDirs;
 use std::{
+    collections::BTreeSet,
     fs,
     path::{Path, PathBuf},
 };
@@ -33,7 +41,103 @@ pub enum Command {
         dry_run: bool,
     },
     // Run the application.
-    Run(GenerateOpts),
+    Run(Box<GenerateOpts>),
+    /// Report undocumented items and doc problems without contacting a model.
+    Audit(AuditOpts),
+    /// Report the percentage of documented items per crate, module and kind.
+    Coverage(CoverageOpts),
+    /// Patch source files from a (possibly hand-edited) `docs.json` without contacting a model.
+    Apply(ApplyOpts),
+    /// Restore the files patched by the last run (or a named one) from its backup.
+    Undo {
+        /// The run to undo, as printed by `--list`; defaults to the latest run.
+        run_id: Option<String>,
+        /// List the runs that can be undone instead.
+        #[arg(long, action=ArgAction::SetTrue)]
+        list: bool,
+        /// The output directory the run wrote to; its backups are kept under `<DIR>/backups`.
+        #[arg(long, value_name = "DIR", default_value = OUT_DIR)]
+        out_dir: PathBuf,
+    },
+}
+
+/// Options for `awful_rustdocs audit`.
+#[derive(Debug, clap::Args, Clone)]
+pub struct AuditOpts {
+    /// Script file used to harvest items, default is "rust_ast.nu".
+    #[arg(long, default_value = "rust_ast.nu")]
+    pub script: PathBuf,
+    /// List of target paths to audit.
+    #[arg()]
+    pub targets: Vec<PathBuf>,
+    /// Output format.
+    #[arg(long, value_enum, default_value = "table")]
+    pub format: AuditFormat,
+    /// Lockfile used to report docs whose code changed since they were generated.
+    #[arg(long, value_name = "FILE", default_value = LOCK_FILE)]
+    pub lockfile: PathBuf,
+}
+
+/// Options for `awful_rustdocs coverage`.
+#[derive(Debug, clap::Args, Clone)]
+pub struct CoverageOpts {
+    /// Script file used to harvest items, default is "rust_ast.nu".
+    #[arg(long, default_value = "rust_ast.nu")]
+    pub script: PathBuf,
+    /// List of target paths to measure.
+    #[arg()]
+    pub targets: Vec<PathBuf>,
+    /// Output format.
+    #[arg(long, value_enum, default_value = "markdown")]
+    pub format: CoverageFormat,
+    /// A report saved earlier with `--format json`; each group's change against it is included.
+    #[arg(long, value_name = "FILE")]
+    pub previous: Option<PathBuf>,
+    /// Exit with an error when coverage of `pub` items is below this percentage.
+    #[arg(long, value_name = "PCT")]
+    pub fail_under: Option<f64>,
+}
+
+/// Options for `awful_rustdocs apply`.
+#[derive(Debug, clap::Args, Clone)]
+pub struct ApplyOpts {
+    /// The generated docs to apply, as written by `run` (`docs.json` or `docs.jsonl`); defaults to
+    /// `<out-dir>/docs.json`.
+    #[arg()]
+    pub docs: Option<PathBuf>,
+    /// The output directory of `run`: where the docs are read from by default and backups are kept.
+    #[arg(long, value_name = "DIR", default_value = OUT_DIR)]
+    pub out_dir: PathBuf,
+    /// Run each doc through the LLM-output sanitizer again (strips wrappers, fixes fences, adds `///`).
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub sanitize: bool,
+    /// Replace existing doc blocks instead of skipping documented items.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub overwrite: bool,
+    /// Replace the existing doc blocks of items that were generated with `--merge`.
+    #[arg(long, action=ArgAction::SetTrue, conflicts_with = "overwrite")]
+    pub merge: bool,
+    /// Print the edits as a unified diff instead of writing them.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub diff: bool,
+    /// Write the edits to this file as a `git apply`-compatible patch instead of writing them.
+    #[arg(long, value_name = "FILE")]
+    pub patch_out: Option<PathBuf>,
+    /// Reflow doc prose so lines, including indentation, fit in this many columns.
+    #[arg(long, value_name = "COLS")]
+    pub wrap: Option<usize>,
+    /// Write each doc as `///`, `/** */` or `#[doc = "..."]`, whichever most docs in its file already use.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub match_doc_style: bool,
+    /// Write each doc to `<DIR>/<fqpath>.md` (default `docs`) and include it with `#[doc = include_str!(..)]`.
+    #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = "docs")]
+    pub sidecar: Option<PathBuf>,
+    /// Script file used to harvest the patched files for the lockfile, default is "rust_ast.nu".
+    #[arg(long, default_value = "rust_ast.nu")]
+    pub script: PathBuf,
+    /// Lockfile recording the fingerprints of every item written.
+    #[arg(long, value_name = "FILE", default_value = LOCK_FILE)]
+    pub lockfile: PathBuf,
 }
 
 /// Configuration options for generating documentation from a script.
@@ -48,9 +152,30 @@ pub struct GenerateOpts {
     /// If set, write generated output to files.
     #[arg(long, action=ArgAction::SetTrue)]
     pub write: bool,
+    /// Print the doc edits as a unified diff instead of (or, with `--write`, in addition to) writing them.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub diff: bool,
+    /// Write the doc edits to this file as a `git apply`-compatible patch.
+    #[arg(long, value_name = "FILE")]
+    pub patch_out: Option<PathBuf>,
+    /// Directory the generated docs are written to.
+    #[arg(long, value_name = "DIR", default_value = OUT_DIR)]
+    pub out_dir: PathBuf,
+    /// Formats of the generated docs, comma-separated: `json` (read by `apply`), `jsonl`, or a `markdown` / `html`
+    /// report showing each item's signature, current doc and generated doc side by side.
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "json")]
+    pub format: Vec<ReportFormat>,
+    /// Show each pending edit (signature, current doc, proposed doc) and ask to accept, reject, edit it in
+    /// `$EDITOR` or skip the file; only accepted edits are applied. Decisions are remembered per proposed doc.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub review: bool,
     /// If set, overwrite existing files without prompting.
     #[arg(long, action=ArgAction::SetTrue)]
     pub overwrite: bool,
+    /// If set, keep the hand-written summary and sections of documented items and only add the missing
+    /// sections, written back in canonical order.
+    #[arg(long, action=ArgAction::SetTrue, conflicts_with = "overwrite")]
+    pub merge: bool,
     /// Session identifier to use for state persistence.
     #[arg(long)]
     pub session: Option<String>,
@@ -63,6 +188,9 @@ pub struct GenerateOpts {
     /// If set, skip path generation.
     #[arg(long, action=ArgAction::SetTrue)]
     pub no_paths: bool,
+    /// If set, skip the per-function scan for panicking operations and `unsafe` blocks.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub no_hazards: bool,
     /// Template to use for function definitions, default is "rustdoc_fn".
     #[arg(long, default_value = "rustdoc_fn")]
     pub fn_template: String,
@@ -75,6 +203,76 @@ pub struct GenerateOpts {
     /// Comma-separated list of symbols to generate documentation for only.
     #[arg(long = "only", value_delimiter = ',', value_name = "SYMBOL", num_args=1..)]
     pub only: Vec<String>,
+    /// If set, re-prompt once when a generated doc references unknown identifiers, then reject the item.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub strict_refs: bool,
+    /// If set, re-prompt once when a function doc lacks a `Panics` / `Safety` section the body calls for,
+    /// then reject the item.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub strict_sections: bool,
+    /// If set, turn backticked names of harvested items into intra-doc links (e.g. ``[`Pipeline`]``),
+    /// using the shortest path that resolves from the documented item's module.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub intra_links: bool,
+    /// If set, send each draft doc back through a critique pass that approves or corrects it.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub critique: bool,
+    /// Template to use for the critique pass, default is "rustdoc_critique".
+    #[arg(long, default_value = "rustdoc_critique")]
+    pub critique_template: String,
+    /// Answer LLM requests from canned files in this directory instead of calling Awful Jade.
+    /// Files are looked up as `<fqpath>.<template>.txt`, `<fqpath>.txt`, then `<prompt-hash>.txt`.
+    #[arg(long, value_name = "DIR")]
+    pub fixtures: Option<PathBuf>,
+    /// Save each item's prompts, template names, raw responses, sanitized docs and timings to this directory.
+    #[arg(long, value_name = "DIR", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+    /// Answer LLM requests from transcripts saved with `--record` instead of calling Awful Jade.
+    #[arg(long, value_name = "DIR", conflicts_with = "fixtures")]
+    pub replay: Option<PathBuf>,
+    /// Write the exact prompt for each item to this directory (one `<fqpath>.md` per item) and exit
+    /// without contacting the model.
+    #[arg(long, value_name = "DIR")]
+    pub emit_prompts: Option<PathBuf>,
+    /// Prompt body template for functions (markdown with `{{placeholders}}`).
+    /// Defaults to `<config_root>/prompts/rustdoc_fn.md` if present, else the built-in template.
+    #[arg(long, value_name = "FILE")]
+    pub fn_prompt: Option<PathBuf>,
+    /// Prompt body template for structs (markdown with `{{placeholders}}`).
+    /// Defaults to `<config_root>/prompts/rustdoc_struct.md` if present, else the built-in template.
+    #[arg(long, value_name = "FILE")]
+    pub struct_prompt: Option<PathBuf>,
+    /// House style for section headings (`rustdoc-std` or `legacy`).
+    /// Overrides `style:` in `.awful_rustdocs.yaml`; without either, docs are left as sanitized.
+    #[arg(long, value_name = "PROFILE")]
+    pub style: Option<String>,
+    /// Reflow doc prose written with `--write` so lines, including indentation, fit in this many columns.
+    /// Overrides `wrap:` in `.awful_rustdocs.yaml`; code fences, tables and long links are never split.
+    #[arg(long, value_name = "COLS")]
+    pub wrap: Option<usize>,
+    /// Write each doc as `///`, `/** */` or `#[doc = "..."]`, whichever most docs in its file already use.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub match_doc_style: bool,
+    /// Write each doc to `<DIR>/<fqpath>.md` (default `docs`) and include it with `#[doc = include_str!(..)]`.
+    #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = "docs")]
+    pub sidecar: Option<PathBuf>,
+    /// Lockfile recording the signature, body and doc fingerprints of every item documented with `--write`.
+    #[arg(long, value_name = "FILE", default_value = LOCK_FILE)]
+    pub lockfile: PathBuf,
+    /// Only regenerate items whose signature or body changed since their doc was written (per the lockfile).
+    /// Implies `--overwrite` for them; docs edited by hand since are left alone.
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub stale: bool,
+    /// Only document items whose lines changed since this git revision, including uncommitted changes.
+    #[arg(long, value_name = "REV", conflicts_with = "staged")]
+    pub since: Option<String>,
+    /// Only document items whose lines are staged for commit (for pre-commit hooks).
+    #[arg(long, action=ArgAction::SetTrue)]
+    pub staged: bool,
+    /// Fqpaths picked by `--stale`, `--since` or `--staged`; when set, only these items are generated.
+    /// Not a command-line flag.
+    #[arg(skip)]
+    pub selection: Option<BTreeSet<String>>,
 }
 
 /// Returns the path to the root configuration directory for the AwfulJade application.
@@ -160,7 +358,9 @@ fn write_if_needed(path: &Path, contents: &str, force: bool) -> Result<bool> {
 /// - Errors during file system path resolution.
 ///
 /// Notes:
-/// - Creates or updates three files: `rustdoc_config.yaml`, `rustdoc_fn.yaml`, and `rustdoc_struct.yaml` in the config directory.
+/// - Creates or updates four files: `rustdoc_config.yaml`, `rustdoc_fn.yaml`, `rustdoc_struct.yaml`, and
+///   `rustdoc_critique.yaml` in the config directory.
+/// - Also writes the editable prompt bodies `prompts/rustdoc_fn.md` and `prompts/rustdoc_struct.md`.
 /// - The configuration directory is determined by `config_root()`, which resolves to a user-specific or default location.
 /// - If `force` is false and files already exist, they are not overwritten.
 pub fn run_init(force: bool, dry_run: bool) -> Result<()> {
@@ -169,18 +369,28 @@ pub fn run_init(force: bool, dry_run: bool) -> Result<()> {
     let tpl_dir = root.join("templates");
     let fn_tpl = tpl_dir.join("rustdoc_fn.yaml");
     let struct_tpl = tpl_dir.join("rustdoc_struct.yaml");
+    let critique_tpl = tpl_dir.join("rustdoc_critique.yaml");
+    let prompt_dir = root.join("prompts");
+    let fn_prompt = prompt_dir.join("rustdoc_fn.md");
+    let struct_prompt = prompt_dir.join("rustdoc_struct.md");
 
     if dry_run {
         eprintln!("Would create:");
         eprintln!("  {}", cfg.display());
         eprintln!("  {}", fn_tpl.display());
         eprintln!("  {}", struct_tpl.display());
+        eprintln!("  {}", critique_tpl.display());
+        eprintln!("  {}", fn_prompt.display());
+        eprintln!("  {}", struct_prompt.display());
         return Ok(());
     }
 
     let w1 = write_if_needed(&cfg, DEFAULT_CONFIG_YAML, force)?;
     let w2 = write_if_needed(&fn_tpl, DEFAULT_RUSTDOC_FN_YAML, force)?;
     let w3 = write_if_needed(&struct_tpl, DEFAULT_RUSTDOC_STRUCT_YAML, force)?;
+    let w4 = write_if_needed(&critique_tpl, DEFAULT_RUSTDOC_CRITIQUE_YAML, force)?;
+    let w5 = write_if_needed(&fn_prompt, DEFAULT_FN_PROMPT_MD, force)?;
+    let w6 = write_if_needed(&struct_prompt, DEFAULT_STRUCT_PROMPT_MD, force)?;
 
     eprintln!("Config directory: {}", root.display());
     eprintln!("{} {}", if w1 { "Wrote" } else { "Kept" }, cfg.display());
@@ -190,5 +400,20 @@ pub fn run_init(force: bool, dry_run: bool) -> Result<()> {
         if w3 { "Wrote" } else { "Kept" },
         struct_tpl.display()
     );
+    eprintln!(
+        "{} {}",
+        if w4 { "Wrote" } else { "Kept" },
+        critique_tpl.display()
+    );
+    eprintln!(
+        "{} {}",
+        if w5 { "Wrote" } else { "Kept" },
+        fn_prompt.display()
+    );
+    eprintln!(
+        "{} {}",
+        if w6 { "Wrote" } else { "Kept" },
+        struct_prompt.display()
+    );
     Ok(())
 }
@@ -0,0 +1,287 @@
+use crate::audit::{DocCount, module_of};
+use crate::error::{Error, Result};
+use crate::model::Row;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Output format of `awful_rustdocs coverage`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CoverageFormat {
+    /// A markdown table, with a change column when `--previous` is given.
+    Markdown,
+    /// The full [`CoverageReport`] as pretty-printed JSON; save it to diff against later.
+    Json,
+    /// A shields.io endpoint badge for public-item coverage.
+    Badge,
+}
+
+/// Documented / total counts of one group, split into `pub` items and everything else.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Split {
+    /// Items declared plain `pub`.
+    pub public: DocCount,
+    /// Items with any other visibility (`pub(crate)`, private, …).
+    pub private: DocCount,
+}
+
+impl Split {
+    /// Counts one item in the half its visibility belongs to.
+    fn add(&mut self, row: &Row) {
+        let half = if row.visibility == "pub" {
+            &mut self.public
+        } else {
+            &mut self.private
+        };
+        half.add(row.had_doc());
+    }
+
+    /// Both halves combined.
+    pub fn all(&self) -> DocCount {
+        DocCount {
+            undocumented: self.public.undocumented + self.private.undocumented,
+            total: self.public.total + self.private.total,
+        }
+    }
+}
+
+/// Documentation coverage of a harvest, per crate, module and item kind.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CoverageReport {
+    /// Every counted item.
+    pub total: Split,
+    /// Per crate name.
+    pub by_crate: BTreeMap<String, Split>,
+    /// Per module (`crate::a::b`).
+    pub by_module: BTreeMap<String, Split>,
+    /// Per item kind (`fn`, `struct`, `field`, …).
+    pub by_kind: BTreeMap<String, Split>,
+    /// Percentage-point change of each group's overall coverage against a previous report, keyed like
+    /// `total`, `public`, `crate:demo`, `module:crate::a` or `kind:fn`. Empty unless [`CoverageReport::diff`]
+    /// was called.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub change: BTreeMap<String, f64>,
+}
+
+impl CoverageReport {
+    /// Counts every harvested item except `impl` blocks.
+    pub fn from_rows(rows: &[Row]) -> Self {
+        let mut report = Self::default();
+        for row in rows.iter().filter(|r| r.kind != "impl") {
+            let krate = row.crate_name.clone().unwrap_or_else(|| "crate".into());
+            report.total.add(row);
+            report.by_crate.entry(krate).or_default().add(row);
+            report.by_module.entry(module_of(row)).or_default().add(row);
+            report.by_kind.entry(row.kind.clone()).or_default().add(row);
+        }
+        report
+    }
+
+    /// Every group with its change key, in display order.
+    fn groups(&self) -> Vec<(String, &Split)> {
+        let mut groups = vec![("total".to_string(), &self.total)];
+        for (prefix, map) in [
+            ("crate", &self.by_crate),
+            ("module", &self.by_module),
+            ("kind", &self.by_kind),
+        ] {
+            groups.extend(map.iter().map(|(k, v)| (format!("{prefix}:{k}"), v)));
+        }
+        groups
+    }
+
+    /// Fills [`CoverageReport::change`] with the difference to `previous` for every group present in both.
+    pub fn diff(&mut self, previous: &CoverageReport) {
+        let before: BTreeMap<String, f64> = previous
+            .groups()
+            .into_iter()
+            .map(|(k, s)| (k, s.all().percent()))
+            .collect();
+        let mut change: BTreeMap<String, f64> = self
+            .groups()
+            .into_iter()
+            .filter_map(|(k, s)| Some((k.clone(), s.all().percent() - before.get(&k)?)))
+            .collect();
+        change.insert(
+            "public".into(),
+            self.total.public.percent() - previous.total.public.percent(),
+        );
+        self.change = change;
+    }
+
+    /// Reads a report previously written with `--format json`.
+    ///
+    /// Errors:
+    /// - `Error::Io` if the file cannot be read.
+    /// - `Error::Json` if it is not a coverage report.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(|e| Error::Io {
+            path: Some(path.to_path_buf()),
+            source: e,
+        })?;
+        serde_json::from_str(&text).map_err(|e| Error::Json {
+            context: "parse previous coverage report",
+            source: e,
+        })
+    }
+
+    /// Formats the report as a markdown table: one row per group with public, private and overall coverage,
+    /// plus a change column when the report was diffed.
+    pub fn render_markdown(&self) -> String {
+        let cell = |c: &DocCount| {
+            if c.total == 0 {
+                "–".to_string()
+            } else {
+                format!(
+                    "{:.1}% ({}/{})",
+                    c.percent(),
+                    c.total - c.undocumented,
+                    c.total
+                )
+            }
+        };
+        let diffed = !self.change.is_empty();
+
+        let mut s = String::from("| Group | Name | Public | Private | All |");
+        s.push_str(if diffed { " Change |\n" } else { "\n" });
+        s.push_str("|---|---|---:|---:|---:|");
+        s.push_str(if diffed { "---:|\n" } else { "\n" });
+        for (key, split) in self.groups() {
+            let (group, name) = key.split_once(':').unwrap_or((&key, ""));
+            let _ = write!(
+                s,
+                "| {group} | {} | {} | {} | {} |",
+                if name.is_empty() {
+                    String::new()
+                } else {
+                    format!("`{name}`")
+                },
+                cell(&split.public),
+                cell(&split.private),
+                cell(&split.all())
+            );
+            if diffed {
+                let _ = match self.change.get(&key) {
+                    Some(d) => write!(s, " {d:+.1} |"),
+                    None => write!(s, " new |"),
+                };
+            }
+            s.push('\n');
+        }
+        s
+    }
+
+    /// Returns a shields.io endpoint badge (`{"schemaVersion":1,"label":"docs",…}`) for public-item coverage,
+    /// or overall coverage when the crate has no `pub` items.
+    pub fn badge(&self) -> serde_json::Value {
+        let count = if self.total.public.total > 0 {
+            self.total.public.clone()
+        } else {
+            self.total.all()
+        };
+        let pct = count.percent();
+        let color = match pct {
+            p if p >= 90.0 => "brightgreen",
+            p if p >= 75.0 => "green",
+            p if p >= 60.0 => "yellow",
+            p if p >= 40.0 => "orange",
+            _ => "red",
+        };
+        serde_json::json!({
+            "schemaVersion": 1,
+            "label": "docs",
+            "message": format!("{pct:.1}%"),
+            "color": color,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Span;
+
+    // ---------- helpers ----------
+
+    fn mk_row(kind: &str, fqpath: &str, visibility: &str, documented: bool) -> Row {
+        Row {
+            kind: kind.into(),
+            name: fqpath.rsplit("::").next().unwrap_or("").into(),
+            crate_name: Some("demo".into()),
+            module_path: None,
+            fqpath: fqpath.into(),
+            visibility: visibility.into(),
+            file: "src/lib.rs".into(),
+            span: Span {
+                start_line: None,
+                end_line: None,
+                start_byte: None,
+                end_byte: None,
+            },
+            signature: String::new(),
+            has_body: true,
+            doc: documented.then(|| "/// Documented.".to_string()),
+            body_text: None,
+            callers: None,
+            file_hash: None,
+        }
+    }
+
+    fn rows() -> Vec<Row> {
+        vec![
+            mk_row("fn", "crate::a::one", "pub", true),
+            mk_row("fn", "crate::a::two", "pub", false),
+            mk_row("fn", "crate::a::three", "private", false),
+            mk_row("struct", "crate::b::Four", "pub", true),
+            mk_row("impl", "crate::b::Four", "private", false),
+        ]
+    }
+
+    // ---------- CoverageReport ----------
+
+    #[test]
+    fn test_from_rows_splits_by_group_and_visibility() {
+        let report = CoverageReport::from_rows(&rows());
+        assert_eq!(report.total.all().total, 4, "impl blocks are not counted");
+        assert!((report.total.public.percent() - 200.0 / 3.0).abs() < 1e-9);
+        assert_eq!(report.by_module["crate::a"].private.undocumented, 1);
+        assert_eq!(report.by_kind["struct"].all().percent(), 100.0);
+        assert_eq!(report.by_crate["demo"].all().total, 4);
+    }
+
+    #[test]
+    fn test_diff_and_render_markdown_show_changes() {
+        let previous = CoverageReport::from_rows(&rows()[..3]);
+        let mut report = CoverageReport::from_rows(&rows());
+        report.diff(&previous);
+        assert!(
+            (report.change["total"] - (50.0 - 100.0 / 3.0)).abs() < 1e-9,
+            "{report:#?}"
+        );
+        assert!(!report.change.contains_key("module:crate::b"));
+
+        let md = report.render_markdown();
+        assert!(
+            md.starts_with("| Group | Name | Public | Private | All | Change |"),
+            "{md}"
+        );
+        assert!(
+            md.contains("| total |  | 66.7% (2/3) | 0.0% (0/1) | 50.0% (2/4) | +16.7 |"),
+            "{md}"
+        );
+        assert!(
+            md.contains("| module | `crate::b` | 100.0% (1/1) | – | 100.0% (1/1) | new |"),
+            "{md}"
+        );
+    }
+
+    #[test]
+    fn test_badge_colors_public_coverage() {
+        let badge = CoverageReport::from_rows(&rows()).badge();
+        assert_eq!(badge["message"], "66.7%");
+        assert_eq!(badge["color"], "yellow");
+    }
+}
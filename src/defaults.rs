@@ -115,4 +115,96 @@ response_format:
               description: Rustdoc for the field. Keep it short; each line must start with '///'.
               minLength: 1
               pattern: "^(///.*\\n?)+$"
-"#;
\ No newline at end of file
+"#;
+pub const DEFAULT_RUSTDOC_CRITIQUE_YAML: &str = r#"system_prompt: You are Awful Jade, created by Awful Security.
+messages: []
+pre_user_message_content: |
+  You are reviewing a draft Rustdoc comment written for a Rust item.
+  Check it against the signature and body you are given:
+  1. Every statement must be true for the code shown.
+  2. Every parameter in the signature must be described; no invented parameters.
+  3. Only mention items, fields and functions that appear in the code or the crate.
+  4. Keep the original structure: summary first, then the Parameters, Returns, Errors, Notes, and Examples sections.
+  5. Every line must start with ///
+post_user_message_content: "If the draft is correct, reply with the single word APPROVED. Otherwise return only the corrected Rustdoc comment block. /nothink"
+should_stream: false
+"#;
+
+pub const DEFAULT_FN_PROMPT_MD: &str = r##"# Rust Function Documentation Task
+You are given context about a single Rust function.
+
+## Function Identity
+- **Fully-qualified path**: `{{fqpath}}`
+- **Signature**: `{{signature}}`
+- **Visibility**: `{{visibility}}`
+
+## Existing Documentation
+{{#existing_doc}}The function already has Rustdoc. Improve and rewrite it if necessary:
+```rust
+{{existing_doc}}
+```
+{{/existing_doc}}{{^existing_doc}}_No existing rustdoc found._
+{{/existing_doc}}
+## Referenced Symbols (body-level)
+{{#referenced_symbols}}{{referenced_symbols}}
+{{/referenced_symbols}}{{^referenced_symbols}}_No symbol references detected._
+{{/referenced_symbols}}{{#calls}}
+## Function Calls Inside This Function
+{{calls}}
+{{/calls}}{{#hazards}}
+## Panics and Safety Facts
+The body contains these operations; describe when they panic in a `Panics:` section (and the invariants callers must uphold in `Safety:` if the function is unsafe):
+{{hazards}}
+{{/hazards}}{{#body}}
+## Function Body (Truncated)
+```rust
+{{body}}
+```
+{{/body}}
+---
+## Output Requirements
+Return **ONLY** a Rustdoc block composed of lines starting with `///`.
+- No JSON, no backticks, no XML, no surrounding prose.
+- Include a clear 1–2 sentence summary.
+- If relevant, add sections titled exactly: `Parameters:`, `Returns:`, `Errors:`, `Notes:`, `Examples:`.
+- Only include a `Safety:` section if the function is unsafe.
+- Use concise bullet points; examples should be doc-test friendly (no fenced code).
+- Every line MUST start with `///` (or be a blank `///`).
+"##;
+
+pub const DEFAULT_STRUCT_PROMPT_MD: &str = r##"# Rust Struct Documentation Task
+You are given the source of a single Rust struct and a list of functions that reference it.
+
+## Struct Identity
+- **Fully-qualified path**: `{{fqpath}}`
+- **Signature**: `{{signature}}`
+- **Visibility**: `{{visibility}}`
+
+## Existing Documentation
+{{#existing_doc}}The struct already has Rustdoc. If needed, rewrite it to be concise:
+```rust
+{{existing_doc}}
+```
+{{/existing_doc}}{{^existing_doc}}_No existing rustdoc found._
+{{/existing_doc}}
+## Struct Body (verbatim)
+```rust
+{{body}}
+```
+
+## Referencing Functions (FQ paths)
+{{#callers}}{{callers}}
+{{/callers}}{{^callers}}_No referencing functions detected in the crate._
+{{/callers}}
+---
+## Output Requirements
+Respond in **structured JSON** (no prose) with this shape:
+{
+  "struct_doc": "/// short summary...\n/// ...",
+  "fields": [
+    { "name": "field_name", "doc": "/// one-line or short doc...\n/// ..." }
+  ]
+}
+- `struct_doc`: A short 1–2 sentence rustdoc for the struct (above attributes).
+- `fields`: One entry **per named field** appearing in the struct body; the `doc` value must be a ready-to-insert `///` block for that field (keep it short, include units/invariants if relevant).
+"##;
@@ -0,0 +1,210 @@
+use std::fmt::Write;
+
+/// One step of a line diff, with indices into the old and new line lists.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+/// Computes a shortest edit script between two line lists with Myers' algorithm.
+///
+/// The common prefix and suffix are matched up front, so the quadratic part only sees the changed middle of the
+/// file; for doc patches that is small even in long files.
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (ma, mb) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+
+    let mut ops: Vec<Op> = (0..prefix).map(|i| Op::Equal(i, i)).collect();
+    ops.extend(myers(ma, mb).into_iter().map(|op| match op {
+        Op::Equal(i, j) => Op::Equal(i + prefix, j + prefix),
+        Op::Delete(i) => Op::Delete(i + prefix),
+        Op::Insert(j) => Op::Insert(j + prefix),
+    }));
+    ops.extend((0..suffix).map(|i| Op::Equal(a.len() - suffix + i, b.len() - suffix + i)));
+    ops
+}
+
+/// The core of [`diff_lines`]: forward search recording each round's furthest reaching paths, then a walk back.
+fn myers(a: &[&str], b: &[&str]) -> Vec<Op> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    let idx = |k: isize| (k + max + 1) as usize;
+    let mut v = vec![0isize; 2 * max as usize + 3];
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+                v[idx(k + 1)]
+            } else {
+                v[idx(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+
+    let mut ops = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[idx(k - 1)] < v[idx(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[idx(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            ops.push(Op::Equal((x - 1) as usize, (y - 1) as usize));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push(Op::Insert((y - 1) as usize));
+            } else {
+                ops.push(Op::Delete((x - 1) as usize));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    ops.reverse();
+    ops
+}
+
+/// Appends one diff line, marking a last line that has no newline the way `git` does.
+fn push_line(out: &mut String, marker: char, line: &str) {
+    out.push(marker);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// Renders a `git apply`-compatible unified diff between two versions of a file.
+///
+/// Parameters:
+/// - `path`: The file path as it should appear after `a/` and `b/`; a leading `./` is dropped.
+/// - `old`, `new`: The file contents before and after.
+/// - `context`: Unchanged lines shown around each change (`git` uses 3); nearby changes share a hunk.
+///
+/// Returns:
+/// - The diff with `diff --git`, `---` and `+++` headers, or an empty string when the contents are equal.
+pub fn unified_diff(path: &str, old: &str, new: &str, context: usize) -> String {
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = diff_lines(&a, &b);
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, Op::Equal(..)))
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    // Position in both files before each op, for hunk headers.
+    let mut pos = Vec::with_capacity(ops.len() + 1);
+    let (mut oa, mut ob) = (0usize, 0usize);
+    for op in &ops {
+        pos.push((oa, ob));
+        match op {
+            Op::Equal(..) => {
+                oa += 1;
+                ob += 1;
+            }
+            Op::Delete(_) => oa += 1,
+            Op::Insert(_) => ob += 1,
+        }
+    }
+    pos.push((oa, ob));
+
+    let path = path.trim_start_matches("./");
+    let mut out = format!("diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n");
+
+    let mut i = 0;
+    while i < changes.len() {
+        let mut j = i;
+        while j + 1 < changes.len() && changes[j + 1] - changes[j] <= 2 * context + 1 {
+            j += 1;
+        }
+        let lo = changes[i].saturating_sub(context);
+        let hi = (changes[j] + context + 1).min(ops.len());
+        let (old_count, new_count) = (pos[hi].0 - pos[lo].0, pos[hi].1 - pos[lo].1);
+        let start = |at: usize, count: usize| if count == 0 { at } else { at + 1 };
+        let _ = writeln!(
+            out,
+            "@@ -{},{old_count} +{},{new_count} @@",
+            start(pos[lo].0, old_count),
+            start(pos[lo].1, new_count)
+        );
+        for op in &ops[lo..hi] {
+            match *op {
+                Op::Equal(x, _) => push_line(&mut out, ' ', a[x]),
+                Op::Delete(x) => push_line(&mut out, '-', a[x]),
+                Op::Insert(y) => push_line(&mut out, '+', b[y]),
+            }
+        }
+        i = j + 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---------- unified_diff ----------
+
+    #[test]
+    fn test_unified_diff_inserts_doc_with_context() {
+        let old = "use x;\n\nfn a() {}\n\nfn b() {}\n";
+        let new = "use x;\n\n/// Does a.\nfn a() {}\n\nfn b() {}\n";
+        assert_eq!(
+            unified_diff("./src/lib.rs", old, new, 1),
+            "diff --git a/src/lib.rs b/src/lib.rs\n--- a/src/lib.rs\n+++ b/src/lib.rs\n\
+             @@ -2,2 +2,3 @@\n \n+/// Does a.\n fn a() {}\n"
+        );
+        assert_eq!(unified_diff("src/lib.rs", old, old, 3), "");
+    }
+
+    #[test]
+    fn test_unified_diff_splits_distant_changes_and_marks_missing_newline() {
+        let old: String = (1..=20).map(|i| format!("line {i}\n")).collect::<String>() + "end";
+        let new = old
+            .replace("line 2\n", "/// Two.\nline 2\n")
+            .replace("line 18\n", "")
+            .replace("end", "end\n");
+        let diff = unified_diff("f.rs", &old, &new, 2);
+        let headers: Vec<&str> = diff.lines().filter(|l| l.starts_with("@@")).collect();
+        assert_eq!(
+            headers,
+            vec!["@@ -1,3 +1,4 @@", "@@ -16,6 +17,5 @@"],
+            "DIFF:\n{diff}"
+        );
+        assert!(
+            diff.ends_with("-end\n\\ No newline at end of file\n+end\n"),
+            "DIFF:\n{diff}"
+        );
+    }
+}
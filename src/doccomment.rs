@@ -0,0 +1,354 @@
+/// The three ways an outer doc comment can be written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocForm {
+    /// `/// text`
+    Line,
+    /// `/** text */`
+    Block,
+    /// `#[doc = "text"]`
+    Attr,
+}
+
+/// What a source line is, as far as the doc and attribute block above an item is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    /// Anything else: item signatures, bodies, plain comments.
+    Code,
+    /// An empty or whitespace-only line.
+    Blank,
+    /// Part of an attribute other than a doc attribute (`#[derive(..)]`, `#[doc(hidden)]`, …).
+    Attr,
+    /// Part of an outer doc comment.
+    Doc(DocForm),
+}
+
+impl LineKind {
+    /// Returns `true` for any line of a doc comment.
+    pub fn is_doc(self) -> bool {
+        matches!(self, LineKind::Doc(_))
+    }
+}
+
+/// Returns the index of the line on which the attribute starting at `lines[start]` ends, following brackets and
+/// skipping over string literals (including raw strings), so multi-line attributes are one unit.
+fn attr_end(lines: &[&str], start: usize) -> usize {
+    let mut depth = 0usize;
+    let mut in_str = false;
+    let mut raw_hashes: Option<usize> = None;
+    for (i, line) in lines.iter().enumerate().skip(start) {
+        let chars: Vec<char> = line.chars().collect();
+        let mut k = 0;
+        while k < chars.len() {
+            let c = chars[k];
+            if let Some(hashes) = raw_hashes {
+                if c == '"' && chars[k + 1..].iter().take_while(|&&h| h == '#').count() >= hashes {
+                    raw_hashes = None;
+                    k += hashes;
+                }
+            } else if in_str {
+                if c == '\\' {
+                    k += 1;
+                } else if c == '"' {
+                    in_str = false;
+                }
+            } else if c == 'r' && matches!(chars.get(k + 1), Some('"') | Some('#')) {
+                let hashes = chars[k + 1..].iter().take_while(|&&h| h == '#').count();
+                if chars.get(k + 1 + hashes) == Some(&'"') {
+                    raw_hashes = Some(hashes);
+                    k += 1 + hashes;
+                }
+            } else if c == '"' {
+                in_str = true;
+            } else if c == '[' {
+                depth += 1;
+            } else if c == ']' {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return i;
+                }
+            }
+            k += 1;
+        }
+    }
+    lines.len().saturating_sub(1)
+}
+
+/// Returns the index of the line holding the `*/` that closes the block comment opened on `lines[start]`.
+fn block_end(lines: &[&str], start: usize) -> usize {
+    let first = lines[start].trim_start();
+    if first[3..].contains("*/") {
+        return start;
+    }
+    (start + 1..lines.len())
+        .find(|&i| lines[i].contains("*/"))
+        .unwrap_or(lines.len().saturating_sub(1))
+}
+
+/// Returns `true` if an attribute line starts a doc attribute (`#[doc = …]`), as opposed to `#[doc(hidden)]`.
+fn is_doc_attr(trimmed: &str) -> bool {
+    trimmed
+        .strip_prefix("#[doc")
+        .is_some_and(|rest| rest.trim_start().starts_with('='))
+}
+
+/// Classifies every line of `src`; multi-line doc attributes, block doc comments and attributes mark all of their
+/// lines.
+///
+/// Notes:
+/// - Inner docs (`//!`, `/*!`) and plain comments (`//`, `////`, `/***`) are `Code`: they never belong to the
+///   next item.
+pub fn classify_lines(src: &str) -> Vec<LineKind> {
+    let lines: Vec<&str> = src.lines().collect();
+    let mut kinds = vec![LineKind::Code; lines.len()];
+    let mut i = 0;
+    while i < lines.len() {
+        let t = lines[i].trim_start();
+        let (kind, end) = if t.is_empty() {
+            (LineKind::Blank, i)
+        } else if t.starts_with("///") && !t.starts_with("////") {
+            (LineKind::Doc(DocForm::Line), i)
+        } else if t.starts_with("/**") && !t.starts_with("/***") && !t.starts_with("/**/") {
+            (LineKind::Doc(DocForm::Block), block_end(&lines, i))
+        } else if t.starts_with("#[") || t.starts_with("#![") {
+            let kind = if is_doc_attr(t) {
+                LineKind::Doc(DocForm::Attr)
+            } else {
+                LineKind::Attr
+            };
+            (kind, attr_end(&lines, i))
+        } else {
+            (LineKind::Code, i)
+        };
+        for k in &mut kinds[i..=end] {
+            *k = kind;
+        }
+        i = end + 1;
+    }
+    kinds
+}
+
+/// Returns the doc form most doc blocks in a file use, or `Line` for a file without docs (ties go to `Line`, then
+/// `Block`).
+pub fn dominant_form(kinds: &[LineKind]) -> DocForm {
+    let mut counts = [0usize; 3];
+    let mut prev = LineKind::Code;
+    for &k in kinds {
+        if let LineKind::Doc(form) = k {
+            if prev != k {
+                counts[form as usize] += 1;
+            }
+        }
+        prev = k;
+    }
+    [DocForm::Line, DocForm::Block, DocForm::Attr]
+        .into_iter()
+        .rev()
+        .max_by_key(|&f| counts[f as usize])
+        .unwrap_or(DocForm::Line)
+}
+
+/// Rewrites a `///` doc block in another form, keeping its indentation and trailing newline.
+///
+/// Parameters:
+/// - `doc`: The block, one `///` line per doc line (as produced by the sanitizer and `indent_like`).
+/// - `form`: The form to write.
+///
+/// Returns:
+/// - The block as `/** … */` (with ` * ` line prefixes) or as one `#[doc = "…"]` per line. A block containing
+///   `*/` cannot be a block comment and is returned unchanged.
+pub fn render_doc(doc: &str, form: DocForm) -> String {
+    let indent: String = doc
+        .lines()
+        .next()
+        .unwrap_or("")
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .collect();
+    let texts: Vec<&str> = doc
+        .lines()
+        .map(|l| {
+            let t = l.trim_start();
+            t.strip_prefix("///").unwrap_or(t)
+        })
+        .collect();
+    let mut out: Vec<String> = match form {
+        DocForm::Line => return doc.to_string(),
+        DocForm::Block if texts.iter().any(|t| t.contains("*/")) => return doc.to_string(),
+        DocForm::Block => {
+            let mut v = vec![format!("{indent}/**")];
+            v.extend(texts.iter().map(|t| format!("{indent} *{t}")));
+            v.push(format!("{indent} */"));
+            v
+        }
+        DocForm::Attr => texts
+            .iter()
+            .map(|t| {
+                let escaped = t.replace('\\', "\\\\").replace('"', "\\\"");
+                format!("{indent}#[doc = \"{escaped}\"]")
+            })
+            .collect(),
+    };
+    if doc.ends_with('\n') {
+        out.push(String::new());
+    }
+    out.join("\n")
+}
+
+/// Reads the value of a `#[doc = "…"]` attribute; `None` for values that are not a string literal (such as
+/// `include_str!(..)`).
+fn doc_attr_value(attr: &str) -> Option<String> {
+    let rest = attr.trim_start().strip_prefix("#[doc")?.trim_start();
+    let lit = rest.strip_prefix('=')?.trim_start();
+    if let Some(raw) = lit.strip_prefix('r') {
+        let hashes = raw.chars().take_while(|&c| c == '#').count();
+        let body = raw[hashes..].strip_prefix('"')?;
+        let close = format!("\"{}", "#".repeat(hashes));
+        return Some(body[..body.find(&close)?].to_string());
+    }
+    let mut chars = lit.strip_prefix('"')?.chars().peekable();
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                'r' => {}
+                '\n' => {
+                    // line continuation: the next line's indentation is dropped too
+                    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                }
+                other => value.push(other),
+            },
+            _ => value.push(c),
+        }
+    }
+    None
+}
+
+/// Converts a doc comment in any of the three forms to `///` lines (without indentation), so existing docs can be
+/// parsed and merged the same way whatever form they were written in.
+///
+/// Lines that are not doc comments, and doc attributes whose value is not a string literal, are kept as they are.
+pub fn to_line_comments(doc: &str) -> String {
+    let lines: Vec<&str> = doc.lines().collect();
+    let kinds = classify_lines(doc);
+    let mut out: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let mut end = i;
+        while end + 1 < lines.len() && kinds[end + 1] == kinds[i] && !starts_unit(lines[end + 1]) {
+            end += 1;
+        }
+        match kinds[i] {
+            LineKind::Doc(DocForm::Line) => out.push(lines[i].trim().to_string()),
+            LineKind::Doc(DocForm::Block) => {
+                let text = lines[i..=end].join("\n");
+                let inner = text.trim().trim_start_matches("/**");
+                let inner = inner.strip_suffix("*/").unwrap_or(inner);
+                let mut body: Vec<String> = inner
+                    .lines()
+                    .map(|l| {
+                        let t = l.trim_start();
+                        let t = t.strip_prefix('*').unwrap_or(t).trim_end();
+                        match t.chars().next() {
+                            None | Some(' ') => format!("///{t}"),
+                            Some(_) => format!("/// {t}"),
+                        }
+                    })
+                    .collect();
+                while body.first().is_some_and(|l| l == "///") {
+                    body.remove(0);
+                }
+                while body.last().is_some_and(|l| l == "///") {
+                    body.pop();
+                }
+                out.extend(body);
+            }
+            LineKind::Doc(DocForm::Attr) => {
+                let attr = lines[i..=end].join("\n");
+                match doc_attr_value(&attr) {
+                    Some(value) => out.extend(value.split('\n').map(|v| format!("///{v}"))),
+                    None => out.extend(lines[i..=end].iter().map(|l| l.trim().to_string())),
+                }
+            }
+            _ => out.extend(lines[i..=end].iter().map(|l| l.to_string())),
+        }
+        i = end + 1;
+    }
+    out.join("\n")
+}
+
+/// Returns `true` for a line that opens a new doc unit (`///`, `/**` or `#[`), so adjacent units of the same
+/// form are converted one at a time.
+fn starts_unit(line: &str) -> bool {
+    let t = line.trim_start();
+    t.starts_with("///") || t.starts_with("/**") || t.starts_with("#[")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---------- classify_lines ----------
+
+    #[test]
+    fn test_classify_lines_spans_multi_line_attrs_and_blocks() {
+        let src = "/**\n * Block ] doc.\n */\n#[doc = r\"first\n]second\"]\n#[derive(\n    Debug,\n)]\n#[doc(hidden)]\n// plain\npub struct S;\n";
+        let kinds = classify_lines(src);
+        use LineKind::*;
+        assert_eq!(
+            kinds,
+            vec![
+                Doc(DocForm::Block),
+                Doc(DocForm::Block),
+                Doc(DocForm::Block),
+                Doc(DocForm::Attr),
+                Doc(DocForm::Attr),
+                Attr,
+                Attr,
+                Attr,
+                Attr,
+                Code,
+                Code,
+            ],
+            "a `]` inside the raw string does not end the doc attribute"
+        );
+        assert_eq!(
+            dominant_form(&kinds),
+            DocForm::Block,
+            "ties prefer `///`, then `/**`"
+        );
+    }
+
+    // ---------- render_doc / to_line_comments ----------
+
+    #[test]
+    fn test_render_doc_round_trips_through_to_line_comments() {
+        let doc = "    /// Adds \"one\".\n    ///\n    /// Uses `a\\b`.\n";
+        let block = render_doc(doc, DocForm::Block);
+        assert_eq!(
+            block,
+            "    /**\n     * Adds \"one\".\n     *\n     * Uses `a\\b`.\n     */\n"
+        );
+        let attr = render_doc(doc, DocForm::Attr);
+        assert_eq!(
+            attr,
+            "    #[doc = \" Adds \\\"one\\\".\"]\n    #[doc = \"\"]\n    #[doc = \" Uses `a\\\\b`.\"]\n"
+        );
+
+        let plain = "/// Adds \"one\".\n///\n/// Uses `a\\b`.";
+        assert_eq!(to_line_comments(&block), plain);
+        assert_eq!(to_line_comments(&attr), plain);
+        assert_eq!(
+            to_line_comments("#[doc = r#\"Multi\nline\"#]"),
+            "///Multi\n///line",
+            "a raw doc string keeps its text verbatim, one `///` line per line"
+        );
+        assert_eq!(
+            render_doc("/// Ends */ here\n", DocForm::Block),
+            "/// Ends */ here\n",
+            "a block comment cannot contain `*/`"
+        );
+    }
+}
@@ -33,6 +33,12 @@ pub enum Error {
         source: serde_json::Error,
     },
 
+    // prompt templates
+    PromptTemplate {
+        name: String,
+        problems: Vec<String>,
+    },
+
     // integration points (foreign error types → string)
     External {
         context: &'static str,
@@ -82,6 +88,14 @@ impl fmt::Display for Error {
             ToolWait { tool, source } => write!(f, "failed to wait on {}: {}", tool, source),
             ToolStatus { tool, code, .. } => write!(f, "{} exited with status {:?}", tool, code),
             Json { context, source } => write!(f, "JSON error in {}: {}", context, source),
+            PromptTemplate { name, problems } => {
+                write!(
+                    f,
+                    "invalid prompt template {}: {}",
+                    name,
+                    problems.join("; ")
+                )
+            }
             External { context, message } => write!(f, "{}: {}", context, message),
         }
     }
@@ -123,7 +137,9 @@ impl std::error::Error for Error {
             ToolSpawn { source, .. } => Some(source),
             ToolWait { source, .. } => Some(source),
             Json { source, .. } => Some(source),
-            ToolStatus { .. } | ConfigDirUnavailable | External { .. } => None,
+            ToolStatus { .. } | ConfigDirUnavailable | PromptTemplate { .. } | External { .. } => {
+                None
+            }
         }
     }
 }
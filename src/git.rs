@@ -0,0 +1,235 @@
+use crate::error::Result;
+use crate::model::Row;
+use crate::regexes::re_hunk_header;
+use crate::runner::ToolRunner;
+use crate::sidecar::relative_path;
+
+use tracing::{debug, instrument};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+/// Changed line ranges per file, as inclusive 1-based `(first, last)` pairs on the new side of the diff, keyed by
+/// the file's path relative to the repository root.
+pub type ChangedLines = BTreeMap<String, Vec<(u32, u32)>>;
+
+/// Parses `git diff --unified=0` output into the changed line ranges of each file.
+///
+/// A hunk that only deletes lines (`+c,0`) is recorded as `(c, c)`, the line the deletion follows, so the
+/// item the lines were removed from is still selected. Deleted files are skipped.
+fn parse_diff(diff: &str) -> ChangedLines {
+    let mut changes = ChangedLines::new();
+    let mut file: Option<String> = None;
+    for line in diff.lines() {
+        if let Some(path) = line.strip_prefix("+++ ") {
+            file = path.strip_prefix("b/").map(str::to_string);
+            continue;
+        }
+        let (Some(f), Some(caps)) = (&file, re_hunk_header().captures(line)) else {
+            continue;
+        };
+        let start: u32 = caps[1].parse().unwrap_or(0);
+        let count: u32 = caps.get(2).map_or(1, |c| c.as_str().parse().unwrap_or(1));
+        let last = start + count.saturating_sub(1);
+        changes.entry(f.clone()).or_default().push((start, last));
+    }
+    changes
+}
+
+/// The top-level directory of the repository the current directory is in (`git rev-parse --show-toplevel`).
+///
+/// Errors:
+/// - `Error::ToolSpawn` if `git` cannot be started; `Error::ToolStatus` if this is not a repository.
+pub fn repo_root(runner: &dyn ToolRunner) -> Result<PathBuf> {
+    let out = runner.run_text("git", &["rev-parse", "--show-toplevel"])?;
+    Ok(PathBuf::from(out.trim()))
+}
+
+/// Collects the lines changed in the working tree since `since`, or staged in the index when `staged` is set.
+///
+/// Parameters:
+/// - `runner`: Runs `git diff --unified=0`; paths in the diff are relative to the repository root.
+/// - `since`: A revision (`main`, `HEAD~3`, a commit hash); the diff includes uncommitted changes.
+/// - `staged`: Diff the index against `HEAD` instead (for pre-commit hooks). Takes precedence over `since`.
+///
+/// Returns:
+/// - The changed line ranges keyed by file path.
+///
+/// Errors:
+/// - `Error::ToolSpawn` if `git` cannot be started.
+/// - `Error::ToolStatus` if `git diff` fails (e.g. not a repository, unknown revision).
+#[instrument(level = "debug", skip(runner))]
+pub fn changed_lines(
+    runner: &dyn ToolRunner,
+    since: Option<&str>,
+    staged: bool,
+) -> Result<ChangedLines> {
+    let mut args = vec![
+        "diff",
+        "--no-color",
+        "--no-ext-diff",
+        "--unified=0",
+        "--src-prefix=a/",
+        "--dst-prefix=b/",
+    ];
+    if staged {
+        args.push("--cached");
+    } else if let Some(rev) = since {
+        args.push(rev);
+    }
+    args.push("--");
+    let diff = runner.run_text("git", &args)?;
+    let changes = parse_diff(&diff);
+    debug!(files = changes.len(), "parsed git diff");
+    Ok(changes)
+}
+
+/// Returns the fqpaths of the rows whose span overlaps a changed line range.
+///
+/// Harvested paths are relative to the current directory (or absolute), diff paths to the repository root, so each
+/// row's file is made relative to `root` before it is looked up. A row without an end line is treated as covering
+/// only its start line; rows without a start line are never selected.
+pub fn touched_items(rows: &[Row], changes: &ChangedLines, root: &Path) -> BTreeSet<String> {
+    rows.iter()
+        .filter(|r| {
+            let Some(start) = r.span.start_line else {
+                return false;
+            };
+            let end = r.span.end_line.unwrap_or(start).max(start);
+            changes
+                .get(&relative_path(root, Path::new(&r.file)))
+                .is_some_and(|ranges| ranges.iter().any(|&(lo, hi)| lo <= end && start <= hi))
+        })
+        .map(|r| r.fqpath.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Span;
+
+    use std::cell::RefCell;
+
+    // ---------- helpers ----------
+
+    struct FakeGit {
+        diff: &'static str,
+        args: RefCell<Vec<String>>,
+    }
+
+    impl ToolRunner for FakeGit {
+        fn run_json_lines(&self, _tool: &'static str, _args: &[&str]) -> Result<Vec<String>> {
+            Ok(vec![])
+        }
+
+        fn run_text(&self, _tool: &'static str, args: &[&str]) -> Result<String> {
+            *self.args.borrow_mut() = args.iter().map(|a| a.to_string()).collect();
+            Ok(self.diff.to_string())
+        }
+    }
+
+    fn mk_row(fqpath: &str, file: &str, start: u32, end: Option<u32>) -> Row {
+        Row {
+            kind: "fn".into(),
+            name: fqpath.rsplit("::").next().unwrap_or("").into(),
+            crate_name: Some("demo".into()),
+            module_path: None,
+            fqpath: fqpath.into(),
+            visibility: "pub".into(),
+            file: file.into(),
+            span: Span {
+                start_line: Some(start),
+                end_line: end,
+                start_byte: None,
+                end_byte: None,
+            },
+            signature: String::new(),
+            has_body: true,
+            doc: None,
+            body_text: None,
+            callers: None,
+            file_hash: None,
+        }
+    }
+
+    const DIFF: &str = "\
+diff --git a/src/lib.rs b/src/lib.rs
+index 1111111..2222222 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -12 +12 @@ pub fn parse(input: &str) -> u8 {
+-    0
++    1
+@@ -40,3 +39,0 @@ impl Parser {
+diff --git a/src/old.rs b/src/old.rs
+deleted file mode 100644
+--- a/src/old.rs
++++ /dev/null
+@@ -1,2 +0,0 @@
+-fn gone() {}
+-
+";
+
+    // ---------- changed_lines ----------
+
+    #[test]
+    fn test_changed_lines_parses_hunks_and_passes_the_base() {
+        let git = FakeGit {
+            diff: DIFF,
+            args: RefCell::new(vec![]),
+        };
+        let changes = changed_lines(&git, Some("main"), false).unwrap();
+        assert_eq!(
+            changes,
+            ChangedLines::from([("src/lib.rs".to_string(), vec![(12, 12), (39, 39)])]),
+            "pure deletions map to the preceding line and deleted files are skipped"
+        );
+        assert_eq!(git.args.borrow().iter().rev().nth(1).unwrap(), "main");
+
+        changed_lines(&git, Some("main"), true).unwrap();
+        assert!(
+            git.args.borrow().contains(&"--cached".to_string())
+                && !git.args.borrow().contains(&"main".to_string()),
+            "--staged diffs the index and ignores the revision: {:?}",
+            git.args.borrow()
+        );
+    }
+
+    // ---------- touched_items ----------
+
+    #[test]
+    fn test_touched_items_selects_rows_overlapping_changes() {
+        let rows = vec![
+            mk_row("crate::parse", "/repo/./src/lib.rs", 10, Some(14)),
+            mk_row("crate::untouched", "/repo/src/lib.rs", 16, Some(30)),
+            mk_row("crate::Parser::finish", "/repo/src/lib.rs", 35, Some(45)),
+            mk_row("crate::other", "/repo/src/other.rs", 12, Some(12)),
+        ];
+        let changes = ChangedLines::from([("src/lib.rs".to_string(), vec![(12, 12), (39, 39)])]);
+        assert_eq!(
+            touched_items(&rows, &changes, Path::new("/repo")),
+            BTreeSet::from([
+                "crate::parse".to_string(),
+                "crate::Parser::finish".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn test_touched_items_compares_paths_from_the_repo_root() {
+        // Running from `<root>/member`: harvested paths are relative to it, diff paths to the root.
+        let cwd = std::env::current_dir().unwrap();
+        let root = cwd.parent().unwrap();
+        let member = cwd.file_name().unwrap().to_string_lossy();
+        let rows = vec![
+            mk_row("crate::parse", "./src/lib.rs", 10, Some(14)),
+            mk_row("other::parse", "../other/src/lib.rs", 10, Some(14)),
+        ];
+        let changes = ChangedLines::from([(format!("{member}/src/lib.rs"), vec![(12, 12)])]);
+        assert_eq!(
+            touched_items(&rows, &changes, root),
+            BTreeSet::from(["crate::parse".to_string()])
+        );
+    }
+}
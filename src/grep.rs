@@ -220,3 +220,170 @@ pub fn qualified_paths_in_span(
     }
     Ok(paths)
 }
+
+/// An operation in a function body that can panic or that needs a safety argument.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hazard {
+    /// The kind of operation: "unwrap", "expect", "panic", "unreachable", "index" or "unsafe".
+    pub kind: String,
+    /// The matched source text, shortened to its first line.
+    pub text: String,
+}
+
+impl Hazard {
+    /// Returns `true` if the operation can panic (everything except `unsafe` blocks).
+    pub fn panics(&self) -> bool {
+        self.kind != "unsafe"
+    }
+
+    /// Describes the hazard as a one-line fact for prompts and warnings.
+    pub fn describe(&self) -> String {
+        let what = match self.kind.as_str() {
+            "unwrap" | "expect" => "panics on `None` / `Err`",
+            "panic" => "panics when reached",
+            "unreachable" => "panics if reached",
+            "index" => "panics if the index is out of bounds",
+            _ => "unsafe block",
+        };
+        format!("`{}` ({what})", self.text)
+    }
+}
+
+/// Patterns for [`hazards_in_span`], paired with the hazard kind they report.
+const HAZARD_PATTERNS: &[(&str, &str)] = &[
+    ("$R.unwrap()", "unwrap"),
+    ("$R.expect($$$A)", "expect"),
+    ("panic!($$$A)", "panic"),
+    ("unreachable!($$$A)", "unreachable"),
+    ("$A[$I]", "index"),
+    ("unsafe { $$$B }", "unsafe"),
+];
+
+/// Finds operations that can panic (`unwrap()`, `expect(`, `panic!`, `unreachable!`, indexing) and `unsafe`
+/// blocks within a byte span of a source file.
+///
+/// Parameters:
+/// - `runner`: The [`ToolRunner`] used to execute `ast-grep`.
+/// - `file`: The source file to search.
+/// - `start_byte`: The starting byte offset (inclusive) of the function.
+/// - `end_byte`: The ending byte offset (exclusive) of the function.
+///
+/// Returns:
+/// - The hazards in pattern order, without duplicates (same kind and text), capped at 20 entries.
+///
+/// Errors:
+/// - Returns errors from `records_in_span` if `ast-grep` fails or prints malformed JSON.
+///
+/// Notes:
+/// - Matches are syntactic: a call is reported even if it sits in a branch that can never run.
+/// - Multi-line matches (typically `unsafe` blocks) are shortened to their first line, at most 60 chars.
+#[instrument(level = "debug", skip(runner))]
+pub fn hazards_in_span(
+    runner: &dyn ToolRunner,
+    file: &str,
+    start_byte: u64,
+    end_byte: u64,
+) -> Result<Vec<Hazard>> {
+    let mut out: Vec<Hazard> = Vec::new();
+    for (pat, kind) in HAZARD_PATTERNS {
+        for r in records_in_span(runner, file, pat, start_byte, end_byte)? {
+            let Some(text) = r.text.as_deref() else {
+                continue;
+            };
+            let first = text.lines().next().unwrap_or("").trim();
+            let mut short: String = first.chars().take(60).collect();
+            if short.len() < text.trim().len() {
+                short.push('…');
+            }
+            let hazard = Hazard {
+                kind: kind.to_string(),
+                text: short,
+            };
+            if !out.contains(&hazard) {
+                out.push(hazard);
+            }
+        }
+    }
+    out.truncate(20);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    // ---------- helpers ----------
+
+    /// Answers `ast-grep` invocations with canned JSON lines keyed by the `-p` pattern.
+    struct FakeRunner {
+        by_pattern: BTreeMap<&'static str, Vec<String>>,
+    }
+
+    impl ToolRunner for FakeRunner {
+        fn run_json_lines(&self, _tool: &'static str, args: &[&str]) -> Result<Vec<String>> {
+            let pat = args
+                .iter()
+                .position(|a| *a == "-p")
+                .map(|i| args[i + 1])
+                .unwrap_or("");
+            Ok(self.by_pattern.get(pat).cloned().unwrap_or_default())
+        }
+
+        fn run_text(&self, _tool: &'static str, _args: &[&str]) -> Result<String> {
+            Ok(String::new())
+        }
+    }
+
+    fn sg_line(text: &str, start: u64, end: u64) -> String {
+        serde_json::json!({
+            "file": "src/lib.rs",
+            "range": { "byteOffset": { "start": start, "end": end } },
+            "text": text,
+        })
+        .to_string()
+    }
+
+    // ---------- hazards_in_span ----------
+
+    #[test]
+    fn test_hazards_in_span_collects_dedups_and_filters_by_span() {
+        let runner = FakeRunner {
+            by_pattern: BTreeMap::from([
+                (
+                    "$R.unwrap()",
+                    vec![
+                        sg_line("opt.unwrap()", 10, 22),
+                        sg_line("opt.unwrap()", 30, 42),
+                        sg_line("other.unwrap()", 500, 514),
+                    ],
+                ),
+                ("$A[$I]", vec![sg_line("buf[i]", 50, 56)]),
+                (
+                    "unsafe { $$$B }",
+                    vec![sg_line("unsafe {\n    ptr.read()\n}", 60, 90)],
+                ),
+            ]),
+        };
+
+        let got = hazards_in_span(&runner, "src/lib.rs", 0, 100).expect("scan succeeds");
+        let kinds: Vec<(&str, &str)> = got
+            .iter()
+            .map(|h| (h.kind.as_str(), h.text.as_str()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("unwrap", "opt.unwrap()"),
+                ("index", "buf[i]"),
+                ("unsafe", "unsafe {…"),
+            ],
+            "out-of-span and duplicate matches must be dropped"
+        );
+        assert!(got[0].panics() && !got[2].panics());
+        assert_eq!(
+            got[1].describe(),
+            "`buf[i]` (panics if the index is out of bounds)"
+        );
+    }
+}
@@ -1,9 +1,12 @@
 use crate::error::{Error, Result};
 use crate::model::Row;
 use crate::runner::{ProcRunner, ToolRunner};
+use crate::util::stable_hash;
 
 use tracing::instrument;
 
+use std::collections::BTreeMap;
+use std::fs;
 use std::path::{Path, PathBuf};
 
 /// Escapes a string for shell usage by wrapping it in single quotes if it contains non-alphanumeric characters or special shell metacharacters like `.` or `-`. If the string is already safe (containing only ASCII alphanumeric characters and allowed special characters), it is returned unchanged.
@@ -110,13 +113,29 @@ pub fn run_nushell_harvest(script_path: &Path, targets: &[PathBuf]) -> Result<Ve
     let runner = ProcRunner;
     let stdout = runner.run_text("nu", &["--no-config-file", "-c", &call])?;
 
-    let rows: Vec<Row> = serde_json::from_str(&stdout).map_err(|e| Error::Json {
+    let mut rows: Vec<Row> = serde_json::from_str(&stdout).map_err(|e| Error::Json {
         context: "nu rust-ast JSON",
         source: e,
     })?;
+    stamp_file_hashes(&mut rows);
     Ok(rows)
 }
 
+/// Records on every row the [`stable_hash`] of its source file as it is now, i.e. the text its spans refer to.
+///
+/// Called right after harvesting, so a file edited while docs are being generated is seen as drifted when the
+/// docs are patched in, instead of having its new text trusted with the old line numbers. Rows whose file cannot
+/// be read get no hash.
+pub fn stamp_file_hashes(rows: &mut [Row]) {
+    let mut hashes: BTreeMap<String, Option<String>> = BTreeMap::new();
+    for row in rows {
+        row.file_hash = hashes
+            .entry(row.file.clone())
+            .or_insert_with_key(|file| fs::read_to_string(file).ok().map(|text| stable_hash(&text)))
+            .clone();
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,228 @@
+use crate::model::Row;
+use crate::regexes::{re_code_span, re_ident_path};
+
+use std::collections::BTreeSet;
+
+/// Splits an fqpath into its segments below the crate root.
+///
+/// Both `crate::a::B` and `<crate_name>::a::B` become `["a", "B"]`.
+fn crate_relative(path: &str, crate_name: Option<&str>) -> Vec<String> {
+    let mut segs: Vec<&str> = path.split("::").filter(|s| !s.is_empty()).collect();
+    if segs
+        .first()
+        .is_some_and(|s| *s == "crate" || Some(*s) == crate_name)
+    {
+        segs.remove(0);
+    }
+    segs.into_iter().map(str::to_string).collect()
+}
+
+/// Harvested item paths used to turn backticked names in docs into intra-doc links.
+#[derive(Debug, Default)]
+pub struct SymbolIndex {
+    /// Crate-relative paths of every linkable item (functions, types, traits…; not fields).
+    paths: BTreeSet<Vec<String>>,
+}
+
+impl SymbolIndex {
+    /// Indexes every harvested row except struct fields.
+    pub fn from_rows(rows: &[Row]) -> Self {
+        let paths = rows
+            .iter()
+            .filter(|r| r.kind != "field" && !r.fqpath.is_empty())
+            .map(|r| crate_relative(&r.fqpath, r.crate_name.as_deref()))
+            .filter(|p| !p.is_empty())
+            .collect();
+        Self { paths }
+    }
+
+    /// Resolves a mentioned name (`Pipeline`, `Pipeline::wanted`, `crate::pipeline::Pipeline`) to the single
+    /// harvested item it can refer to.
+    ///
+    /// Returns:
+    /// - The item's crate-relative path, or `None` if no item or more than one item matches.
+    fn resolve(&self, mention: &str) -> Option<&Vec<String>> {
+        let absolute = mention.starts_with("crate::");
+        let segs = crate_relative(mention, None);
+        if segs.is_empty() {
+            return None;
+        }
+        let mut hits = self.paths.iter().filter(|p| {
+            if absolute {
+                **p == segs
+            } else {
+                p.ends_with(&segs)
+            }
+        });
+        let first = hits.next()?;
+        hits.next().is_none().then_some(first)
+    }
+
+    /// Returns the module an item lives in, as crate-relative segments.
+    ///
+    /// The harvested `module_path` is used when present; otherwise the fqpath minus its last segment and any
+    /// type segments before it (so a method `m::Type::f` lives in `m`).
+    fn module_of(item: &Row) -> Vec<String> {
+        match &item.module_path {
+            Some(m) => {
+                let joined = m.join("::");
+                crate_relative(&joined, item.crate_name.as_deref())
+            }
+            None => {
+                let mut segs = crate_relative(&item.fqpath, item.crate_name.as_deref());
+                segs.pop();
+                while segs
+                    .last()
+                    .is_some_and(|s| s.starts_with(|c: char| c.is_ascii_uppercase()))
+                {
+                    segs.pop();
+                }
+                segs
+            }
+        }
+    }
+
+    /// Rewrites backticked mentions of harvested items into intra-doc links.
+    ///
+    /// Each code span that looks like a path (optionally followed by `()`) and resolves to exactly one
+    /// harvested item becomes ``[`path`]``. The path is the shortest one that resolves from the documented
+    /// item's module: relative to it when the target is defined in that module or below, otherwise
+    /// `crate::`-absolute.
+    ///
+    /// Parameters:
+    /// - `doc`: A sanitized `///` block.
+    /// - `item`: The documented item; its module is the resolution scope and its parameters are never linked.
+    ///
+    /// Returns:
+    /// - The doc with links added. Code inside fences, spans that are already links, ambiguous names and
+    ///   names with no harvested match are left unchanged.
+    pub fn link_mentions(&self, doc: &str, item: &Row) -> String {
+        let module = Self::module_of(item);
+        let mut in_fence = false;
+        let mut out: Vec<String> = Vec::new();
+
+        for line in doc.lines() {
+            let content = line.trim_start().trim_start_matches('/').trim_start();
+            if content.starts_with("```") {
+                in_fence = !in_fence;
+            }
+            if in_fence || content.starts_with("```") {
+                out.push(line.to_string());
+                continue;
+            }
+
+            let mut rewritten = String::with_capacity(line.len());
+            let mut last = 0;
+            for caps in re_code_span().captures_iter(line) {
+                let whole = caps.get(0).expect("group 0 always matches");
+                let mention = &caps[1];
+                let already_linked = line[..whole.start()].ends_with('[');
+                let Some(path) = (!already_linked)
+                    .then(|| re_ident_path().captures(mention))
+                    .flatten()
+                    .filter(|c| !item.signature.contains(&format!("{}:", &c[1])))
+                    .and_then(|c| self.resolve(&c[1]))
+                else {
+                    continue;
+                };
+
+                let shortest = if path.len() > module.len() && path.starts_with(&module) {
+                    path[module.len()..].join("::")
+                } else {
+                    format!("crate::{}", path.join("::"))
+                };
+                let parens = if mention.ends_with("()") { "()" } else { "" };
+                rewritten.push_str(&line[last..whole.start()]);
+                rewritten.push_str(&format!("[`{shortest}{parens}`]"));
+                last = whole.end();
+            }
+            rewritten.push_str(&line[last..]);
+            out.push(rewritten);
+        }
+        out.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Span;
+
+    // ---------- helpers ----------
+
+    fn mk_row(kind: &str, fqpath: &str, signature: &str) -> Row {
+        Row {
+            kind: kind.into(),
+            name: fqpath.rsplit("::").next().unwrap_or("").into(),
+            crate_name: Some("demo".into()),
+            module_path: None,
+            fqpath: fqpath.into(),
+            visibility: "pub".into(),
+            file: "src/lib.rs".into(),
+            span: Span {
+                start_line: None,
+                end_line: None,
+                start_byte: None,
+                end_byte: None,
+            },
+            signature: signature.into(),
+            has_body: true,
+            doc: None,
+            body_text: None,
+            callers: None,
+            file_hash: None,
+        }
+    }
+
+    fn index() -> SymbolIndex {
+        SymbolIndex::from_rows(&[
+            mk_row("struct", "crate::pipeline::Pipeline", ""),
+            mk_row("fn", "crate::pipeline::Pipeline::wanted", ""),
+            mk_row("fn", "crate::pipeline::run_generation", ""),
+            mk_row("struct", "crate::model::Row", ""),
+            mk_row("field", "crate::model::Row::name", ""),
+            mk_row("fn", "crate::a::open", ""),
+            mk_row("fn", "crate::b::open", ""),
+        ])
+    }
+
+    // ---------- link_mentions ----------
+
+    #[test]
+    fn test_link_mentions_uses_shortest_resolving_path() {
+        let item = mk_row(
+            "fn",
+            "crate::pipeline::run_generation",
+            "pub fn run_generation()",
+        );
+        let doc = "/// Builds a `Pipeline` of `Row`s, then calls `Pipeline::wanted()`.";
+        assert_eq!(
+            index().link_mentions(doc, &item),
+            "/// Builds a [`Pipeline`] of [`crate::model::Row`]s, then calls [`Pipeline::wanted()`]."
+        );
+    }
+
+    #[test]
+    fn test_link_mentions_resolves_from_a_methods_module() {
+        let item = mk_row(
+            "fn",
+            "crate::pipeline::Pipeline::wanted",
+            "pub fn wanted(&self)",
+        );
+        assert_eq!(
+            index().link_mentions("/// See `run_generation`.", &item),
+            "/// See [`run_generation`]."
+        );
+    }
+
+    #[test]
+    fn test_link_mentions_leaves_ambiguous_unknown_and_fenced_names() {
+        let item = mk_row("fn", "crate::model::load", "pub fn load(name: &str)");
+        let doc = "/// Calls `open`, returns `Vec<Row>`, reads `name`, see [`Row`] and `Missing`.\n/// ```\n/// let r: Row = `Row`;\n/// ```";
+        let got = index().link_mentions(doc, &item);
+        assert_eq!(
+            got, doc,
+            "ambiguous, generic, field-or-param, linked, unknown and fenced mentions must not change"
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use crate::error::{Error, Result};
+use crate::util::{fqpath_to_filename, stable_hash};
+
+use async_trait::async_trait;
+use awful_aj::api;
+use awful_aj::config::AwfulJadeConfig;
+use awful_aj::template::ChatTemplate;
+use tracing::debug;
+
+use std::path::PathBuf;
+
+/// A single question sent to the model for one item, along with the template it should be answered with.
+pub struct LlmRequest<'a> {
+    /// Fully-qualified path of the item being documented.
+    pub fqpath: &'a str,
+    /// Name of the template (e.g. `rustdoc_fn`), used by backends that key answers by template.
+    pub template_name: &'a str,
+    /// The loaded chat template.
+    pub template: &'a ChatTemplate,
+    /// The fully rendered prompt.
+    pub question: String,
+}
+
+/// A backend that answers documentation prompts: Awful Jade, canned fixtures, or replayed transcripts.
+///
+/// The pipeline sends every prompt through this trait (first drafts, follow-ups and critiques), so a backend
+/// only has to turn one [`LlmRequest`] into the model's text. Answers must come back as the model wrote them;
+/// sanitizing, validation and retries are left to the caller.
+#[async_trait(?Send)]
+pub trait LlmClient {
+    /// Sends a request to the backend and returns the raw, unsanitized answer.
+    ///
+    /// Parameters:
+    /// - `req`: The item, template and prompt to ask about.
+    ///
+    /// Returns:
+    /// - A `Result<String>` containing the model's answer.
+    ///
+    /// Errors:
+    /// - Backend-specific; implementations report failures as `Error::External` or `Error::Io`.
+    async fn ask(&self, req: &LlmRequest<'_>) -> Result<String>;
+}
+
+/// The default backend: forwards every request to Awful Jade via `api::ask`.
+pub struct AwfulJadeClient {
+    /// Configuration settings for the Awful Jade system.
+    pub cfg: AwfulJadeConfig,
+}
+
+#[async_trait(?Send)]
+impl LlmClient for AwfulJadeClient {
+    /// Calls `api::ask` without a vector store or brain and returns the answer verbatim.
+    ///
+    /// Errors:
+    /// - `Error::External` if the Awful Jade request fails; the message is prefixed with the item's fqpath.
+    async fn ask(&self, req: &LlmRequest<'_>) -> Result<String> {
+        api::ask(&self.cfg, req.question.clone(), req.template, None, None)
+            .await
+            .map_err(|e| Error::External {
+                context: "LLM ask() failed",
+                message: format!("{}: {}", req.fqpath, e),
+            })
+    }
+}
+
+/// An offline backend that answers from canned files in a directory.
+///
+/// For each request the first existing file among the following is returned:
+/// 1. `<dir>/<fqpath>.<template_name>.txt`
+/// 2. `<dir>/<fqpath>.txt`
+/// 3. `<dir>/<prompt hash>.txt`
+///
+/// where `<fqpath>` is rendered with [`fqpath_to_filename`] and `<prompt hash>` is [`stable_hash`] of the
+/// question. This makes end-to-end runs deterministic and lets tests exercise the whole pipeline without a
+/// model.
+pub struct FixtureClient {
+    /// Directory holding the canned answers.
+    pub dir: PathBuf,
+}
+
+impl FixtureClient {
+    /// Lists the candidate fixture files for a request in lookup order.
+    fn candidates(&self, req: &LlmRequest<'_>) -> Vec<PathBuf> {
+        let stem = fqpath_to_filename(req.fqpath);
+        vec![
+            self.dir.join(format!("{stem}.{}.txt", req.template_name)),
+            self.dir.join(format!("{stem}.txt")),
+            self.dir.join(format!("{}.txt", stable_hash(&req.question))),
+        ]
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmClient for FixtureClient {
+    /// Returns the contents of the first matching fixture file.
+    ///
+    /// Errors:
+    /// - `Error::Io` if a matching file exists but cannot be read.
+    /// - `Error::External` if no fixture matches; the message lists every path that was tried.
+    async fn ask(&self, req: &LlmRequest<'_>) -> Result<String> {
+        let candidates = self.candidates(req);
+        for path in &candidates {
+            if path.is_file() {
+                debug!(fixture = %path.display(), "answering from fixture");
+                return std::fs::read_to_string(path).map_err(|e| Error::Io {
+                    path: Some(path.clone()),
+                    source: e,
+                });
+            }
+        }
+        Err(Error::External {
+            context: "no fixture for LLM request",
+            message: format!(
+                "{}: tried {}",
+                req.fqpath,
+                candidates
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---------- helpers ----------
+
+    fn mk_template() -> ChatTemplate {
+        ChatTemplate {
+            system_prompt: String::new(),
+            messages: vec![],
+            response_format: None,
+            pre_user_message_content: None,
+            post_user_message_content: None,
+        }
+    }
+
+    // ---------- FixtureClient ----------
+
+    #[tokio::test]
+    async fn test_fixture_client_prefers_template_specific_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::write(dir.path().join("crate.m.f.rustdoc_fn.txt"), "/// Specific.").unwrap();
+        std::fs::write(dir.path().join("crate.m.f.txt"), "/// Generic.").unwrap();
+
+        let client = FixtureClient {
+            dir: dir.path().to_path_buf(),
+        };
+        let tpl = mk_template();
+        let req = LlmRequest {
+            fqpath: "crate::m::f",
+            template_name: "rustdoc_fn",
+            template: &tpl,
+            question: "q".into(),
+        };
+        assert_eq!(client.ask(&req).await.unwrap(), "/// Specific.");
+
+        let req = LlmRequest {
+            template_name: "rustdoc_critique",
+            ..req
+        };
+        assert_eq!(
+            client.ask(&req).await.unwrap(),
+            "/// Generic.",
+            "Expected fallback to the fqpath-only fixture"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fixture_client_falls_back_to_prompt_hash_then_errors() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let question = "# Rust Function Documentation Task".to_string();
+        std::fs::write(
+            dir.path().join(format!("{}.txt", stable_hash(&question))),
+            "/// By hash.",
+        )
+        .unwrap();
+
+        let client = FixtureClient {
+            dir: dir.path().to_path_buf(),
+        };
+        let tpl = mk_template();
+        let req = LlmRequest {
+            fqpath: "crate::m::g",
+            template_name: "rustdoc_fn",
+            template: &tpl,
+            question,
+        };
+        assert_eq!(client.ask(&req).await.unwrap(), "/// By hash.");
+
+        let req = LlmRequest {
+            question: "something else".into(),
+            ..req
+        };
+        let err = client
+            .ask(&req)
+            .await
+            .expect_err("missing fixture must fail");
+        assert!(
+            matches!(err, Error::External { .. }),
+            "Expected Error::External, got {err:?}"
+        );
+    }
+}
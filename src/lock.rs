@@ -0,0 +1,294 @@
+use crate::doccomment::to_line_comments;
+use crate::error::{Error, Result};
+use crate::model::Row;
+use crate::sidecar::{resolve_includes, row_doc};
+use crate::style::strip_doc_prefix;
+use crate::util::stable_hash;
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Default lockfile name, relative to the directory `awful_rustdocs` is run from.
+pub const LOCK_FILE: &str = ".awful_rustdocs.lock";
+
+/// Fingerprints of an item at the time its doc was written.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LockEntry {
+    /// [`stable_hash`] of the signature.
+    pub signature: String,
+    /// [`stable_hash`] of the body text.
+    pub body: String,
+    /// [`doc_fingerprint`] of the written doc.
+    pub doc: String,
+}
+
+/// Maps each fqpath whose doc was written by the tool to the fingerprints taken at that time.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// Entries keyed by fqpath.
+    pub entries: BTreeMap<String, LockEntry>,
+}
+
+/// How an item relates to its lockfile entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocState {
+    /// Code and doc are unchanged since the doc was written.
+    Fresh,
+    /// The doc is the generated one, but the code changed since.
+    Stale {
+        /// The signature hash differs.
+        signature_changed: bool,
+        /// The body hash differs.
+        body_changed: bool,
+    },
+    /// The doc was edited (or removed) by hand since it was written; the tool leaves it alone.
+    HandEdited,
+}
+
+impl DocState {
+    /// Names what changed in a stale item (`signature`, `body` or `signature and body`), or `None` if the
+    /// item is not stale.
+    pub fn changed(&self) -> Option<&'static str> {
+        match self {
+            DocState::Stale {
+                signature_changed: true,
+                body_changed: true,
+            } => Some("signature and body"),
+            DocState::Stale {
+                signature_changed: true,
+                ..
+            } => Some("signature"),
+            DocState::Stale { .. } => Some("body"),
+            _ => None,
+        }
+    }
+}
+
+/// Hashes a doc's words, ignoring `///` markers, indentation and line wrapping.
+///
+/// This keeps the fingerprint stable across `--wrap` reflows and re-indentation, so only real edits to the
+/// text count as hand edits.
+pub fn doc_fingerprint(doc: &str) -> String {
+    let words: Vec<&str> = doc
+        .lines()
+        .flat_map(|l| strip_doc_prefix(l).split_whitespace())
+        .collect();
+    stable_hash(&words.join(" "))
+}
+
+impl Lockfile {
+    /// Reads a lockfile, or returns an empty one if the file does not exist.
+    ///
+    /// Errors:
+    /// - `Error::Io` if the file exists but cannot be read.
+    /// - `Error::Json` if the file is not a valid lockfile.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            debug!(file = %path.display(), "no lockfile yet");
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(path).map_err(|e| Error::Io {
+            path: Some(path.to_path_buf()),
+            source: e,
+        })?;
+        serde_json::from_str(&text).map_err(|e| Error::Json {
+            context: "parse lockfile",
+            source: e,
+        })
+    }
+
+    /// Writes the lockfile as pretty-printed JSON.
+    ///
+    /// Errors:
+    /// - `Error::Json` if serialization fails.
+    /// - `Error::Io` if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let bytes = serde_json::to_vec_pretty(self).map_err(|e| Error::Json {
+            context: "serialize lockfile",
+            source: e,
+        })?;
+        std::fs::write(path, bytes).map_err(|e| Error::Io {
+            path: Some(path.to_path_buf()),
+            source: e,
+        })
+    }
+
+    /// Records the fingerprints of an item whose doc was just written.
+    ///
+    /// A doc that includes a sidecar (`#[doc = include_str!(..)]`) is fingerprinted by the sidecar's text, and
+    /// block and attribute docs by their `///` form, so the form the doc is written in never counts as an edit.
+    pub fn record(&mut self, row: &Row, doc: &str) {
+        self.entries.insert(
+            row.fqpath.clone(),
+            LockEntry {
+                signature: stable_hash(&row.signature),
+                body: stable_hash(row.body_text.as_deref().unwrap_or("")),
+                doc: doc_fingerprint(&to_line_comments(&resolve_includes(
+                    doc,
+                    Path::new(&row.file),
+                ))),
+            },
+        );
+    }
+
+    /// Compares a freshly harvested item with its entry.
+    ///
+    /// Returns:
+    /// - `None` if the item is not in the lockfile (its doc was never written by the tool).
+    /// - The item's [`DocState`] otherwise.
+    pub fn state(&self, row: &Row) -> Option<DocState> {
+        let entry = self.entries.get(&row.fqpath)?;
+        if doc_fingerprint(&to_line_comments(&row_doc(row))) != entry.doc {
+            return Some(DocState::HandEdited);
+        }
+        let signature_changed = stable_hash(&row.signature) != entry.signature;
+        let body_changed = stable_hash(row.body_text.as_deref().unwrap_or("")) != entry.body;
+        Some(if signature_changed || body_changed {
+            DocState::Stale {
+                signature_changed,
+                body_changed,
+            }
+        } else {
+            DocState::Fresh
+        })
+    }
+
+    /// Returns the stale items among `rows`, each with what changed (see [`DocState::changed`]).
+    pub fn stale<'r>(&self, rows: &'r [Row]) -> Vec<(&'r Row, &'static str)> {
+        rows.iter()
+            .filter_map(|r| Some((r, self.state(r)?.changed()?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::Span;
+
+    // ---------- helpers ----------
+
+    fn mk_row(signature: &str, body: &str, doc: Option<&str>) -> Row {
+        Row {
+            kind: "fn".into(),
+            name: "f".into(),
+            crate_name: Some("demo".into()),
+            module_path: None,
+            fqpath: "crate::f".into(),
+            visibility: "pub".into(),
+            file: "src/lib.rs".into(),
+            span: Span {
+                start_line: Some(1),
+                end_line: None,
+                start_byte: None,
+                end_byte: None,
+            },
+            signature: signature.into(),
+            has_body: true,
+            doc: doc.map(String::from),
+            body_text: Some(body.into()),
+            callers: None,
+            file_hash: None,
+        }
+    }
+
+    // ---------- Lockfile::state ----------
+
+    #[test]
+    fn test_state_distinguishes_fresh_stale_and_hand_edited() {
+        let written = "/// Adds one to `x`, returning the\n/// incremented value.";
+        let mut lock = Lockfile::default();
+        lock.record(&mk_row("pub fn f(x: i32)", "{ x + 1 }", None), written);
+
+        let rewrapped = "    /// Adds one to `x`, returning the incremented value.";
+        assert_eq!(
+            lock.state(&mk_row("pub fn f(x: i32)", "{ x + 1 }", Some(rewrapped))),
+            Some(DocState::Fresh),
+            "reflowing or re-indenting must not count as an edit"
+        );
+        assert_eq!(
+            lock.state(&mk_row("pub fn f(x: i64)", "{ x + 1 }", Some(written))),
+            Some(DocState::Stale {
+                signature_changed: true,
+                body_changed: false
+            })
+        );
+        assert_eq!(
+            lock.state(&mk_row("pub fn f(x: i64)", "{ x + 2 }", Some("/// Mine."))),
+            Some(DocState::HandEdited),
+            "hand edits win over code changes"
+        );
+
+        let other = Row {
+            fqpath: "crate::g".into(),
+            ..mk_row("", "", None)
+        };
+        assert_eq!(lock.state(&other), None);
+    }
+
+    #[test]
+    fn test_state_reads_block_and_attribute_docs_as_their_lines() {
+        let mut lock = Lockfile::default();
+        lock.record(
+            &mk_row("pub fn f()", "{}", None),
+            "/// Does f.\n///\n/// # Panics\n/// Never.",
+        );
+
+        for harvested in [
+            "/**\n * Does f.\n *\n * # Panics\n * Never.\n */",
+            "#[doc = \"Does f.\"]\n#[doc = \"\"]\n#[doc = \"# Panics\"]\n#[doc = \"Never.\"]",
+        ] {
+            assert_eq!(
+                lock.state(&mk_row("pub fn f()", "{}", Some(harvested))),
+                Some(DocState::Fresh),
+                "written with --match-doc-style, not edited: {harvested}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_state_fingerprints_sidecar_docs_by_their_markdown() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::create_dir_all(dir.path().join("docs")).unwrap();
+        let md = dir.path().join("docs").join("crate.f.md");
+        std::fs::write(&md, "Does f.\n").unwrap();
+        let include = "#[doc = include_str!(\"../docs/crate.f.md\")]";
+        let row = Row {
+            file: dir.path().join("src/lib.rs").to_string_lossy().into_owned(),
+            ..mk_row("pub fn f()", "{}", Some(include))
+        };
+
+        let mut lock = Lockfile::default();
+        lock.record(&row, "/// Does f.");
+        assert_eq!(lock.state(&row), Some(DocState::Fresh));
+
+        std::fs::write(&md, "Does f, as I say.\n").unwrap();
+        assert_eq!(
+            lock.state(&row),
+            Some(DocState::HandEdited),
+            "an edited sidecar is a hand edit even though the include line is unchanged"
+        );
+    }
+
+    #[test]
+    fn test_load_and_save_round_trip() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join(LOCK_FILE);
+        assert!(Lockfile::load(&path).unwrap().entries.is_empty());
+
+        let mut lock = Lockfile::default();
+        lock.record(&mk_row("pub fn f()", "{}", None), "/// Does f.");
+        lock.save(&path).unwrap();
+
+        let loaded = Lockfile::load(&path).unwrap();
+        assert_eq!(loaded.entries, lock.entries);
+        let stale_rows = [mk_row("pub fn f()", "{ changed() }", Some("/// Does f."))];
+        let stale = loaded.stale(&stale_rows);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale[0].1, "body");
+    }
+}
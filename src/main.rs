@@ -1,23 +1,53 @@
 mod defaults;
 
+mod ast;
+mod audit;
+mod backup;
 mod cli;
+mod coverage;
+mod diff;
+mod doccomment;
 mod error;
+mod git;
 mod grep;
 mod harvest;
+mod links;
+mod llm;
+mod lock;
 mod logging;
 mod model;
 mod patch;
 mod pipeline;
+mod project;
 mod prompt;
 mod regexes;
+mod report;
+mod review;
 mod runner;
 mod sanitize;
+mod sidecar;
+mod style;
+mod transcript;
 mod util;
+mod validate;
 
+use crate::audit::AuditFormat;
+use crate::backup::BACKUP_DIR;
 use crate::cli::{Cli, Command, config_root, run_init};
+use crate::coverage::{CoverageFormat, CoverageReport};
 use crate::error::{Error, Result};
 use crate::harvest::run_nushell_harvest;
-use crate::patch::patch_files_with_docs;
+use crate::llm::{AwfulJadeClient, FixtureClient, LlmClient};
+use crate::lock::Lockfile;
+use crate::patch::{PatchOptions, patch_files_with_docs, plan_patches, preview_patches};
+use crate::project::ProjectConfig;
+use crate::prompt::Prompts;
+use crate::report::ReportFormat;
+use crate::review::ReviewLog;
+use crate::runner::ProcRunner;
+use crate::sanitize::sanitize_llm_doc;
+use crate::style::StyleProfile;
+use crate::transcript::{Recorder, ReplayClient};
 
 use awful_aj::config::{AwfulJadeConfig, load_config};
 use awful_aj::template::{self, ChatTemplate};
@@ -25,6 +55,7 @@ use clap::Parser;
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::{EnvFilter, prelude::*};
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -59,7 +90,7 @@ fn init_tracing() {
 ///
 /// Parses command-line arguments and routes execution to either initialization (`Init`) or runtime processing (`Run`).
 /// In `Run` mode, it loads the configuration, templates, and performs AST harvesting via Nushell, then runs LLM-powered
-/// documentation generation. The generated documentation is serialized to `target/llm_rustdocs/docs.json` and optionally
+/// documentation generation. The generated documentation is written to the output directory (`--out-dir`, `docs.json` by default) and optionally
 /// patched into source files. Logging and error handling are integrated throughout.
 ///
 /// # Parameters
@@ -101,41 +132,273 @@ async fn main() -> Result<()> {
             info!("init completed");
             Ok(())
         }
-        Command::Run(opts) => {
+        Command::Audit(opts) => {
+            let targets: Vec<PathBuf> = if opts.targets.is_empty() {
+                vec![PathBuf::from(".")]
+            } else {
+                opts.targets.clone()
+            };
+            info!(?targets, "audit: harvesting AST rows via Nushell");
+            let rows = run_nushell_harvest(&opts.script, &targets)?;
+            let lock = Lockfile::load(&opts.lockfile)?;
+            let report = audit::audit_rows(&rows, &lock);
+            info!(
+                rows = rows.len(),
+                findings = report.findings.len(),
+                "audit completed"
+            );
+            match opts.format {
+                AuditFormat::Table => print!("{}", audit::render_table(&report)),
+                AuditFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&report).map_err(|e| Error::Json {
+                        context: "serialize audit report",
+                        source: e,
+                    })?
+                ),
+            }
+            Ok(())
+        }
+        Command::Coverage(opts) => {
+            let targets: Vec<PathBuf> = if opts.targets.is_empty() {
+                vec![PathBuf::from(".")]
+            } else {
+                opts.targets.clone()
+            };
+            info!(?targets, "coverage: harvesting AST rows via Nushell");
+            let rows = run_nushell_harvest(&opts.script, &targets)?;
+            let mut report = CoverageReport::from_rows(&rows);
+            if let Some(path) = &opts.previous {
+                report.diff(&CoverageReport::load(path)?);
+            }
+            let public = report.total.public.percent();
+            info!(
+                items = report.total.all().total,
+                public = format!("{public:.1}%"),
+                "coverage computed"
+            );
+
+            match opts.format {
+                CoverageFormat::Markdown => print!("{}", report.render_markdown()),
+                CoverageFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&report).map_err(|e| Error::Json {
+                        context: "serialize coverage report",
+                        source: e,
+                    })?
+                ),
+                CoverageFormat::Badge => println!("{}", report.badge()),
+            }
+
+            match opts.fail_under {
+                Some(min) if public < min => Err(Error::External {
+                    context: "coverage below --fail-under",
+                    message: format!("public items are {public:.1}% documented, required {min}%"),
+                }),
+                _ => Ok(()),
+            }
+        }
+        Command::Undo {
+            run_id,
+            list,
+            out_dir,
+        } => {
+            let root = out_dir.join(BACKUP_DIR);
+            if list {
+                for run in backup::list_runs(&root)? {
+                    println!("{}  {} file(s)", run.run_id, run.files.len());
+                    for f in &run.files {
+                        println!("    {}", f.path);
+                    }
+                }
+                return Ok(());
+            }
+            for path in backup::undo(&root, run_id.as_deref())? {
+                info!(file = %path, "restored");
+            }
+            Ok(())
+        }
+        Command::Apply(opts) => {
+            let docs = opts
+                .docs
+                .clone()
+                .unwrap_or_else(|| opts.out_dir.join(ReportFormat::Json.file_name()));
+            info!(file=%docs.display(), "apply: reading generated docs");
+            let mut results = report::load_results(&docs)?;
+            if opts.sanitize {
+                for r in &mut results {
+                    r.llm_doc = sanitize_llm_doc(&r.llm_doc);
+                }
+            }
+            info!(items = results.len(), "loaded generated docs");
+
+            // --wrap wins over the project file, as in `run`
+            let project = ProjectConfig::load(Path::new("."))?;
+            let patch_opts = PatchOptions {
+                overwrite: opts.overwrite,
+                merge: opts.merge,
+                wrap: opts.wrap.or(project.wrap),
+                match_doc_style: opts.match_doc_style,
+                sidecar: opts.sidecar.clone(),
+                backups: Some(opts.out_dir.join(BACKUP_DIR)),
+            };
+            if opts.diff || opts.patch_out.is_some() {
+                preview_patches(&results, &patch_opts, opts.diff, opts.patch_out.as_deref())?;
+                info!("apply: preview only; source files left untouched");
+                return Ok(());
+            }
+            let written = patch_files_with_docs(&results, &patch_opts)?;
+            info!(items = written.len(), "apply: patching complete");
+
+            // Lockfile: harvest the patched files and record the written items, as `run --write` does
+            let recorded: Vec<_> = results
+                .iter()
+                .filter(|r| r.kind != "field" && written.contains(&r.fqpath))
+                .collect();
+            if !recorded.is_empty() {
+                let targets: Vec<PathBuf> = recorded
+                    .iter()
+                    .map(|r| PathBuf::from(&r.file))
+                    .collect::<BTreeSet<_>>()
+                    .into_iter()
+                    .collect();
+                let rows = run_nushell_harvest(&opts.script, &targets)?;
+                let mut lock = Lockfile::load(&opts.lockfile)?;
+                for r in recorded {
+                    if let Some(row) = rows.iter().find(|h| h.fqpath == r.fqpath) {
+                        lock.record(row, &r.llm_doc);
+                    }
+                }
+                lock.save(&opts.lockfile)?;
+                info!(file=%opts.lockfile.display(), entries = lock.entries.len(), "updated lockfile");
+            }
+            Ok(())
+        }
+        Command::Run(mut opts) => {
             info!("run: starting");
             debug!(?opts, "effective options");
 
-            // Resolve config path
-            let cfg_path: String = if Path::new(&opts.config).is_absolute() {
-                opts.config.clone()
+            // Load + validate prompt templates before doing any work
+            let prompt_dir = config_root().ok().map(|r| r.join("prompts"));
+            let prompts = Prompts::load(
+                opts.fn_prompt.as_deref(),
+                opts.struct_prompt.as_deref(),
+                prompt_dir.as_deref(),
+            )
+            .inspect_err(|e| error!(error=%e, "invalid prompt template"))?;
+            info!(fn_prompt=%prompts.fn_prompt.name, struct_prompt=%prompts.struct_prompt.name, "loaded prompt templates");
+
+            // House style: --style wins over the project file
+            let project = ProjectConfig::load(Path::new("."))?;
+            opts.wrap = opts.wrap.or(project.wrap);
+            let style: Option<StyleProfile> = match (&opts.style, &project.style) {
+                (Some(name), _) => Some(StyleProfile::builtin(name)?),
+                (None, Some(setting)) => Some(setting.resolve()?),
+                (None, None) => None,
+            };
+            if let Some(p) = &style {
+                info!(style=%p.name, "applying house style");
+            }
+
+            // Targets
+            let targets: Vec<PathBuf> = if opts.targets.is_empty() {
+                info!("no targets provided; defaulting to current directory '.'");
+                vec![PathBuf::from(".")]
             } else {
-                let root = config_root()?;
-                debug!(root=?root, file=?opts.config, "resolved config root");
-                root.join(&opts.config).to_string_lossy().into_owned()
+                info!(count = opts.targets.len(), "received explicit targets");
+                opts.targets.clone()
             };
-            info!(cfg_path = %cfg_path, "loading Awful Jade config");
-
-            // Load AJ config
-            let mut cfg: AwfulJadeConfig = load_config(&cfg_path).map_err(|e| {
-                error!(error=%e, cfg_path=%cfg_path, "failed to load Awful Jade config");
-                Error::External {
-                    context: "Failed to load Awful Jade config",
-                    message: format!("{}: {}", cfg_path, e),
-                }
-            })?;
+            debug!(?targets, "targets to analyze");
 
-            if let Some(name) = &opts.session {
-                info!(session = %name, "ensuring AJ conversation + session config");
-                cfg.ensure_conversation_and_config(name)
-                    .await
-                    .map_err(|e| {
-                        error!(error=%e, session=%name, "ensure_conversation_and_config failed");
-                        Error::External {
-                            context: "ensure_conversation_and_config failed",
-                            message: e.to_string(),
-                        }
-                    })?;
+            // Harvest
+            info!("harvesting AST rows via Nushell");
+            let rows = run_nushell_harvest(&opts.script, &targets)?;
+            info!(rows = rows.len(), "harvest completed");
+
+            // Lockfile: report docs whose code changed since they were generated
+            let mut lock = Lockfile::load(&opts.lockfile)?;
+            let stale = lock.stale(&rows);
+            for (row, changed) in &stale {
+                warn!(fqpath=%row.fqpath, file=%row.file, "{changed} changed since the doc was generated");
             }
+            if opts.stale {
+                info!(
+                    items = stale.len(),
+                    "--stale: regenerating only stale items (implies --overwrite)"
+                );
+                opts.selection = Some(stale.iter().map(|(r, _)| r.fqpath.clone()).collect());
+                opts.overwrite = true;
+            }
+
+            // Git: restrict to items overlapping changed lines
+            if opts.since.is_some() || opts.staged {
+                let changes = git::changed_lines(&ProcRunner, opts.since.as_deref(), opts.staged)?;
+                let touched = git::touched_items(&rows, &changes, &git::repo_root(&ProcRunner)?);
+                info!(
+                    files = changes.len(),
+                    items = touched.len(),
+                    "git: selected items overlapping changed lines"
+                );
+                opts.selection = Some(match opts.selection.take() {
+                    Some(picked) => picked.intersection(&touched).cloned().collect(),
+                    None => touched,
+                });
+            }
+            if opts.selection.as_ref().is_some_and(|s| s.is_empty()) {
+                info!("no items selected; nothing to do");
+                return Ok(());
+            }
+            let harvested = rows.clone();
+
+            if let Some(dir) = &opts.emit_prompts {
+                info!(dir = %dir.display(), "exporting prompts (no LLM requests)");
+                let written = pipeline::emit_prompts(&opts, &prompts, rows, dir)?;
+                info!(prompts = written, "run: completed successfully");
+                return Ok(());
+            }
+
+            // Pick the LLM backend
+            let client: Box<dyn LlmClient> = if let Some(dir) = &opts.fixtures {
+                info!(fixtures = %dir.display(), "answering LLM requests from fixtures");
+                Box::new(FixtureClient { dir: dir.clone() })
+            } else if let Some(dir) = &opts.replay {
+                info!(replay = %dir.display(), "answering LLM requests from recorded transcripts");
+                Box::new(ReplayClient::from_dir(dir)?)
+            } else {
+                // Resolve config path
+                let cfg_path: String = if Path::new(&opts.config).is_absolute() {
+                    opts.config.clone()
+                } else {
+                    let root = config_root()?;
+                    debug!(root=?root, file=?opts.config, "resolved config root");
+                    root.join(&opts.config).to_string_lossy().into_owned()
+                };
+                info!(cfg_path = %cfg_path, "loading Awful Jade config");
+
+                // Load AJ config
+                let mut cfg: AwfulJadeConfig = load_config(&cfg_path).map_err(|e| {
+                    error!(error=%e, cfg_path=%cfg_path, "failed to load Awful Jade config");
+                    Error::External {
+                        context: "Failed to load Awful Jade config",
+                        message: format!("{}: {}", cfg_path, e),
+                    }
+                })?;
+
+                if let Some(name) = &opts.session {
+                    info!(session = %name, "ensuring AJ conversation + session config");
+                    cfg.ensure_conversation_and_config(name)
+                        .await
+                        .map_err(|e| {
+                            error!(error=%e, session=%name, "ensure_conversation_and_config failed");
+                            Error::External {
+                                context: "ensure_conversation_and_config failed",
+                                message: e.to_string(),
+                            }
+                        })?;
+                }
+
+                Box::new(AwfulJadeClient { cfg })
+            };
 
             // Load templates
             info!(fn_template=%opts.fn_template, "loading function template");
@@ -160,71 +423,112 @@ async fn main() -> Result<()> {
                     }
                 })?;
 
+            let tpl_critique: Option<ChatTemplate> = if opts.critique {
+                info!(critique_template=%opts.critique_template, "loading critique template");
+                let tpl = template::load_template(&opts.critique_template)
+                    .await
+                    .map_err(|e| {
+                        error!(error=%e, template=%opts.critique_template, "failed to load critique template");
+                        Error::External {
+                            context: "Failed to load critique template",
+                            message: format!("'{}': {}", opts.critique_template, e),
+                        }
+                    })?;
+                Some(tpl)
+            } else {
+                None
+            };
+
             // Build context
             let ctx = pipeline::Ctx {
-                cfg,
+                client,
                 tpl_fn,
                 tpl_struct,
-                opts: opts.clone(),
-            };
-
-            // Targets
-            let targets: Vec<PathBuf> = if ctx.opts.targets.is_empty() {
-                info!("no targets provided; defaulting to current directory '.'");
-                vec![PathBuf::from(".")]
-            } else {
-                info!(count = ctx.opts.targets.len(), "received explicit targets");
-                ctx.opts.targets.clone()
+                tpl_critique,
+                prompts,
+                style,
+                recorder: opts.record.clone().map(Recorder::new),
+                opts: (*opts).clone(),
             };
-            debug!(?targets, "targets to analyze");
-
-            // Harvest
-            info!("harvesting AST rows via Nushell");
-            let rows = run_nushell_harvest(&ctx.opts.script, &targets)?;
-            info!(rows = rows.len(), "harvest completed");
 
             // Generate
             info!("starting LLM doc generation");
             let all_results = pipeline::run_generation(&ctx, rows).await?;
             info!(generated = all_results.len(), "generation finished");
 
-            // Persist results
-            let out_dir = PathBuf::from("target/llm_rustdocs");
-            debug!(dir=?out_dir, "ensuring output directory");
-            std::fs::create_dir_all(&out_dir).map_err(|e| {
-                error!(error=%e, ?out_dir, "failed to create output directory");
-                Error::Io {
-                    path: Some(out_dir.clone()),
-                    source: e,
-                }
-            })?;
-            let out_json = out_dir.join("docs.json");
-            info!(file=%out_json.to_string_lossy(), "writing docs.json");
-            std::fs::write(
-                &out_json,
-                serde_json::to_vec_pretty(&all_results).map_err(|e| {
-                    error!(error=%e, "failed to serialize docs.json");
-                    Error::Json {
-                        context: "serialize docs.json",
-                        source: e,
-                    }
-                })?,
-            )
-            .map_err(|e| {
-                error!(error=%e, file=%out_json.to_string_lossy(), "failed to write docs.json");
-                Error::Io {
-                    path: Some(out_json.clone()),
-                    source: e,
-                }
-            })?;
-            info!(file=%out_json.to_string_lossy(), "wrote docs.json");
+            let patch_opts = PatchOptions {
+                overwrite: ctx.opts.overwrite,
+                merge: ctx.opts.merge,
+                wrap: ctx.opts.wrap,
+                match_doc_style: ctx.opts.match_doc_style,
+                sidecar: ctx.opts.sidecar.clone(),
+                backups: Some(ctx.opts.out_dir.join(BACKUP_DIR)),
+            };
+
+            // Interactive review: keep only accepted edits
+            let all_results = if ctx.opts.review {
+                let pending: BTreeSet<String> = plan_patches(&all_results, &patch_opts)?
+                    .into_iter()
+                    .flat_map(|p| p.items)
+                    .collect();
+                let current: BTreeMap<String, String> = harvested
+                    .iter()
+                    .filter_map(|r| Some((r.fqpath.clone(), r.doc.clone()?)))
+                    .collect();
+                let log_path = ctx.opts.out_dir.join(review::REVIEW_LOG);
+                let mut log = ReviewLog::load(&log_path)?;
+                info!(pending = pending.len(), "reviewing pending doc edits");
+                let stdin = std::io::stdin();
+                let mut console = review::Console {
+                    input: &mut stdin.lock(),
+                    output: &mut std::io::stderr(),
+                    editor: &review::edit_in_editor,
+                };
+                let kept = review::review_results(
+                    all_results,
+                    &pending,
+                    &current,
+                    &mut log,
+                    &mut console,
+                )?;
+                log.save(&log_path)?;
+                kept
+            } else {
+                all_results
+            };
+
+            // Persist results (after review, so `apply` never re-applies a rejected edit)
+            for path in report::write_reports(&ctx.opts.out_dir, &all_results, &ctx.opts.format)? {
+                info!(file=%path.display(), "wrote generated docs");
+            }
+
+            // Preview doc edits as a diff / patch file
+            if ctx.opts.diff || ctx.opts.patch_out.is_some() {
+                preview_patches(
+                    &all_results,
+                    &patch_opts,
+                    ctx.opts.diff,
+                    ctx.opts.patch_out.as_deref(),
+                )?;
+            }
 
             // Patch source files
             if ctx.opts.write {
                 info!("patching source files with generated rustdoc");
-                patch_files_with_docs(&all_results, ctx.opts.overwrite)?;
-                info!("patching complete");
-            } else {
+                let written = patch_files_with_docs(&all_results, &patch_opts)?;
+                info!(items = written.len(), "patching complete");
+
+                for r in all_results
+                    .iter()
+                    .filter(|r| r.kind != "field" && written.contains(&r.fqpath))
+                {
+                    if let Some(row) = harvested.iter().find(|h| h.fqpath == r.fqpath) {
+                        lock.record(row, &r.llm_doc);
+                    }
+                }
+                lock.save(&ctx.opts.lockfile)?;
+                info!(file=%ctx.opts.lockfile.display(), entries = lock.entries.len(), "updated lockfile");
+            } else if !ctx.opts.diff && ctx.opts.patch_out.is_none() {
                 warn!("--write not set; skipping patching of source files");
             }
 
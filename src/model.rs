@@ -46,6 +46,10 @@ pub struct Row {
     pub body_text: Option<String>,
     /// Optional list of caller names (e.g., functions calling this item).
     pub callers: Option<Vec<String>>,
+    /// `stable_hash` of the whole source file right after harvesting, set by
+    /// [`crate::harvest::stamp_file_hashes`]; copied into [`LlmDocResult::file_hash`].
+    #[serde(default)]
+    pub file_hash: Option<String>,
 }
 
 impl Row {
@@ -106,7 +110,9 @@ impl Row {
 }
 
 /// Result of LLM-generated documentation for a code item, containing metadata and generated content.
-#[derive(Debug, Serialize, Clone)]
+/// Records written to `docs.json` are read back by `awful_rustdocs apply`; the lists, flags and verdict may be
+/// omitted there.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct LlmDocResult {
     /// The kind of documentation (e.g., "function", "type", "struct").
     pub kind: String,
@@ -117,17 +123,36 @@ pub struct LlmDocResult {
     /// The starting line number of the item in the source file (optional).
     pub start_line: Option<u32>,
     /// The ending line number of the item in the source file (optional).
+    #[serde(default)]
     pub end_line: Option<u32>,
     /// The function or item signature (e.g., "fn foo(x: i32) -> u32").
     pub signature: String,
     /// List of calling functions that reference this item.
+    #[serde(default)]
     pub callers: Vec<String>,
     /// List of symbols referenced by this item (e.g., variables, functions).
+    #[serde(default)]
     pub referenced_symbols: Vec<String>,
     /// The generated documentation content from LLM (e.g., markdown or text).
     pub llm_doc: String,
     /// Whether the item already had existing documentation before generation.
+    #[serde(default)]
     pub had_existing_doc: bool,
+    /// The item's doc when it was harvested, as `///` lines; `None` when it had none. Shown next to the new doc
+    /// in the markdown and HTML reports.
+    #[serde(default)]
+    pub existing_doc: Option<String>,
+    /// Validation warnings for the generated doc (e.g. references to items that do not exist in the crate).
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Verdict of the critique pass (`"approved"`, `"revised"`, `"discarded"` or `"empty"`), or `None` when it did
+    /// not run.
+    #[serde(default)]
+    pub critique: Option<String>,
+    /// `stable_hash` of the whole source file when the item was harvested (the file `start_line` refers to);
+    /// the patcher re-locates the item by its signature when the file no longer matches it.
+    #[serde(default)]
+    pub file_hash: Option<String>,
 }
 
 /// Enum field documentation strings
@@ -288,6 +313,7 @@ mod tests {
             doc: None,
             body_text: body_text.map(str::to_string),
             callers: None,
+            file_hash: None,
         }
     }
 
@@ -309,6 +335,7 @@ mod tests {
             doc: Some("  hello  ".into()),
             body_text: None,
             callers: None,
+            file_hash: None,
         };
         assert!(
             row.had_doc(),
@@ -504,4 +531,26 @@ mod tests {
             &out[..out.len().min(10)]
         );
     }
+
+    // ---------- LlmDocResult ----------
+
+    #[test]
+    fn test_llm_doc_result_reads_hand_trimmed_docs_json() {
+        let json = r#"[{
+            "kind": "fn",
+            "fqpath": "crate::answer",
+            "file": "src/lib.rs",
+            "start_line": 3,
+            "signature": "pub fn answer() -> u8",
+            "llm_doc": "/// Returns the answer."
+        }]"#;
+        let results: Vec<LlmDocResult> =
+            serde_json::from_str(json).expect("omitted lists and flags default");
+        assert_eq!(results[0].end_line, None);
+        assert!(!results[0].had_existing_doc && results[0].warnings.is_empty());
+
+        let round_trip = serde_json::to_string(&results).unwrap();
+        let again: Vec<LlmDocResult> = serde_json::from_str(&round_trip).unwrap();
+        assert_eq!(again[0].llm_doc, "/// Returns the answer.");
+    }
 }
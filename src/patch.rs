@@ -1,14 +1,39 @@
+use crate::ast::{DocSlot, find_item, parse_items, verify_items};
+use crate::backup::{Backup, atomic_write};
+use crate::diff::unified_diff;
+use crate::doccomment::{DocForm, LineKind, classify_lines, dominant_form, render_doc};
 use crate::error::{Error, Result};
 use crate::model::LlmDocResult;
 use crate::regexes::{find_sig_line_near, re_field, re_fn_sig, re_struct};
+use crate::sanitize::reflow_doc;
+use crate::sidecar::{include_attr, markdown, sidecar_path};
+use crate::util::stable_hash;
 
-use tracing::instrument;
+use tracing::{debug, info, instrument, warn};
 
 use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Options controlling how generated docs are written into source files.
+#[derive(Debug, Clone, Default)]
+pub struct PatchOptions {
+    /// Replace existing doc blocks instead of skipping documented items.
+    pub overwrite: bool,
+    /// Replace the existing doc blocks of items whose generated doc was merged with it (`--merge`).
+    pub merge: bool,
+    /// Reflow doc prose so lines, including their indentation, fit in this many columns.
+    pub wrap: Option<usize>,
+    /// Write docs in the form (`///`, `/** */` or `#[doc = "..."]`) most docs in the file already use.
+    pub match_doc_style: bool,
+    /// Write each doc to a markdown file under this directory and include it from the source instead.
+    pub sidecar: Option<PathBuf>,
+    /// Save the original of every patched file in a new run directory under this root, for `undo`.
+    pub backups: Option<PathBuf>,
+}
 
 /// A text edit specifying a range and replacement content.
+#[derive(Clone)]
 pub struct Edit {
     /// Starting index of the edit in the original text (inclusive).
     start: usize,
@@ -83,8 +108,9 @@ pub enum InsertWhere {
 /// - `Some(InsertWhere::Replace(doc_lo, anchor))` if a block of `///` comments is found and `overwrite` is `true`.
 ///
 /// Notes:
-/// - The function traverses the source lines backward from the struct signature to find the first attribute (`#[...` or `#[![...]`).
-/// - It identifies the start of a doc comment block by detecting consecutive `///` lines starting from the line immediately before the attribute.
+/// - The function traverses the source lines backward from the struct signature to find the first attribute (`#[...` or `#[![...]`,
+///   possibly spanning several lines); `#[doc = ...]` attributes are docs, not attributes.
+/// - It identifies the start of a doc comment block by detecting consecutive doc lines (`///`, `/** */` or `#[doc = ...]`) starting from the line immediately before the attribute.
 /// - The `doc_lo` value marks the beginning of the doc block, and `anchor` marks the position of the attribute.
 /// - If the block is found and `overwrite` is false, the function returns `None` to avoid modifying existing documentation.
 ///
@@ -104,16 +130,16 @@ fn doc_slot_above_attrs(
     struct_sig_line0: usize,
     overwrite: bool,
 ) -> Option<InsertWhere> {
-    let lines: Vec<&str> = src.lines().collect();
+    let kinds = classify_lines(src);
+    let is_doc = |i: usize| kinds.get(i).is_some_and(|k| k.is_doc());
     let mut attr_first = struct_sig_line0;
     let mut i = struct_sig_line0.saturating_sub(1);
     let mut saw_attr = false;
-    while i < lines.len() {
+    while i < kinds.len() {
         if i == usize::MAX {
             break;
         }
-        let t = lines[i].trim_start();
-        if t.starts_with("#[") || t.starts_with("#![") {
+        if kinds[i] == LineKind::Attr {
             saw_attr = true;
             attr_first = i;
             if i == 0 {
@@ -122,7 +148,7 @@ fn doc_slot_above_attrs(
             i = i.saturating_sub(1);
             continue;
         }
-        if t.is_empty() && saw_attr {
+        if kinds[i] == LineKind::Blank && saw_attr {
             if i == 0 {
                 break;
             }
@@ -133,9 +159,9 @@ fn doc_slot_above_attrs(
     }
     let anchor = attr_first;
 
-    if anchor > 0 && lines[anchor - 1].trim_start().starts_with("///") {
+    if anchor > 0 && is_doc(anchor - 1) {
         let mut doc_lo = anchor - 1;
-        while doc_lo > 0 && lines[doc_lo - 1].trim_start().starts_with("///") {
+        while doc_lo > 0 && is_doc(doc_lo - 1) {
             doc_lo -= 1;
         }
         if !overwrite {
@@ -159,25 +185,23 @@ fn doc_slot_above_attrs(
 /// - `None` if `overwrite` is `false` and a matching documentation block is found.
 ///
 /// Notes:
-/// - Documentation blocks are identified by lines starting with "///" after trimming leading whitespace.
+/// - Documentation blocks are `///` lines, `/** */` comments and `#[doc = ...]` attributes (see [`classify_lines`]).
 /// - The function finds the start of the block (the first line with "///" before the insertion point) and adjusts the insertion accordingly.
 /// - If the insertion point is 0, it always returns an insertion before the first line.
 /// ```
 fn field_doc_slot(src: &str, insert_line0: usize, overwrite: bool) -> Option<InsertWhere> {
-    let lines: Vec<&str> = src.lines().collect();
+    let kinds = classify_lines(src);
+    let is_doc = |i: usize| kinds.get(i).is_some_and(|k| k.is_doc());
     if insert_line0 == 0 {
         return Some(InsertWhere::Before(0));
     }
     let i = insert_line0 - 1;
-    if lines
-        .get(i)
-        .map_or(false, |l| l.trim_start().starts_with("///"))
-    {
+    if is_doc(i) {
         if !overwrite {
             return None;
         }
         let mut doc_lo = i;
-        while doc_lo > 0 && lines[doc_lo - 1].trim_start().starts_with("///") {
+        while doc_lo > 0 && is_doc(doc_lo - 1) {
             doc_lo -= 1;
         }
         return Some(InsertWhere::Replace(doc_lo, insert_line0));
@@ -188,9 +212,11 @@ fn field_doc_slot(src: &str, insert_line0: usize, overwrite: bool) -> Option<Ins
 /// Finds the insertion range for a doc comment block in a Rust source string, starting from a given line index.
 ///
 /// This function locates the beginning and end of a doc comment block by scanning backward from `start_line_1`.
-/// It identifies the first non-doc comment line (using `///`, `#![doc]`, or `#[doc]` as markers) and the first attribute line (`#[` or `#![`).
+/// A doc block directly above the signature is used as is; otherwise the attribute lines above it are skipped and
+/// the doc block (if any) above them is used, so the new doc keeps sitting above the attributes. Docs in any form
+/// (`///`, `/** */`, `#[doc = ...]`, including multi-line ones) count.
 /// The range is returned as `(lo, hi)`, where `lo` is the start line and `hi` is the end line of the doc block.
-/// If no attributes are found, the end is set to `start_line_1` or the line before an empty line.
+/// If there are neither docs nor attributes, the range is the blank line before the signature, if any.
 ///
 /// # Parameters
 /// - `source`: The Rust source code as a string slice.
@@ -213,58 +239,33 @@ fn field_doc_slot(src: &str, insert_line0: usize, overwrite: bool) -> Option<Ins
 /// assert_eq!(range, (0, 0));
 /// ```
 fn find_doc_insertion_range(source: &str, start_line_1: usize) -> (usize, usize) {
-    let lines: Vec<&str> = source.lines().collect();
+    let kinds = classify_lines(source);
     let sig_idx = start_line_1.saturating_sub(1);
-    let mut lo = sig_idx;
-
-    let mut i = sig_idx.saturating_sub(1);
-    while i < lines.len() {
-        if i == usize::MAX {
-            break;
+    let doc_top = |hi: usize| {
+        let mut lo = hi;
+        while lo > 0 && kinds.get(lo - 1).is_some_and(|k| k.is_doc()) {
+            lo -= 1;
         }
-        let t = lines[i].trim_start();
-        if t.starts_with("///") || t.starts_with("#![doc") || t.starts_with("#[doc") {
-            lo = i;
-            if i == 0 {
-                break;
-            }
-            i = i.saturating_sub(1);
-            continue;
-        }
-        break;
+        lo
+    };
+
+    let lo = doc_top(sig_idx);
+    if lo < sig_idx {
+        return (lo, sig_idx);
     }
 
-    let mut j = sig_idx.saturating_sub(1);
-    let mut saw_attr = false;
-    let mut attr_first_idx = usize::MAX;
-    while j < lines.len() {
-        if j == usize::MAX {
-            break;
-        }
-        let t = lines[j].trim_start();
-        if t.starts_with("#[") || t.starts_with("#![") {
-            saw_attr = true;
-            attr_first_idx = j;
-            lo = lo.min(j);
-            if j == 0 {
-                break;
-            }
-            j = j.saturating_sub(1);
-            continue;
-        }
-        break;
+    let mut attr_first = sig_idx;
+    while attr_first > 0 && kinds.get(attr_first - 1) == Some(&LineKind::Attr) {
+        attr_first -= 1;
+    }
+    if attr_first < sig_idx {
+        return (doc_top(attr_first), attr_first);
     }
 
-    if saw_attr {
-        let hi = attr_first_idx;
-        (lo, hi)
-    } else {
-        let mut lo2 = lo;
-        if sig_idx > 0 && lines[sig_idx - 1].trim().is_empty() {
-            lo2 = lo2.min(sig_idx - 1);
-        }
-        (lo2, sig_idx)
+    if sig_idx > 0 && kinds.get(sig_idx - 1) == Some(&LineKind::Blank) {
+        return (sig_idx - 1, sig_idx);
     }
+    (sig_idx, sig_idx)
 }
 
 /// Applies indentation to a document by preserving the leading whitespace of a target line
@@ -322,6 +323,14 @@ fn indent_like(target_line: &str, doc: &str) -> String {
     out
 }
 
+/// Returns the display width of the leading whitespace of `line`, counting a tab as four columns.
+pub fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
 /// Returns `true` if the line immediately above the specified `insert_line0` is non-blank,
 /// otherwise `false`. If `insert_line0` is zero, the function returns `false` since there
 /// is no line above the first line. The function checks the trimmed version of the line
@@ -373,50 +382,252 @@ fn add_leading_blank_if_needed(source: &str, insert_line0: usize, doc: &str) ->
     }
 }
 
-/// Patches source files by inserting or updating documentation blocks based on LLM-generated results.
-/// For each result, it locates the appropriate insertion point in the file (before or after a function/struct/field signature),
-/// applies the generated doc string with proper indentation, and writes the updated content back to disk.
+/// Removes all whitespace, so signatures compare equal however they were wrapped or indented.
+fn squash_ws(s: &str) -> String {
+    s.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// Returns the nearest line above `line0` that is indented less than it (the `impl`, `mod`, `struct`, … header the
+/// line sits in), or `None` for a line at the top level.
+fn enclosing_header<'a>(lines: &[&'a str], line0: usize) -> Option<&'a str> {
+    let indent = indent_width(lines[line0]);
+    lines[..line0].iter().rev().copied().find(|l| {
+        let t = l.trim_start();
+        !t.is_empty() && !t.starts_with("//") && !t.starts_with("#[") && indent_width(l) < indent
+    })
+}
+
+/// Finds the line an item's signature now starts on, for a file that changed since the item was harvested.
+///
+/// Parameters:
+/// - `src`: The current file contents.
+/// - `signature`: The harvested signature; it may span several lines and is compared with whitespace removed.
+/// - `fqpath`: The item's path, whose parent segment tells apart identical signatures in different `impl`s, `mod`s
+///   or structs.
+///
+/// Returns:
+/// - `Ok(line0)`: The zero-based line the signature starts on.
+/// - `Err(reason)`: `"signature not found"` when no line matches, or `"signature is ambiguous"` when several do
+///   and the enclosing headers do not single one out.
+fn locate_signature(
+    src: &str,
+    signature: &str,
+    fqpath: &str,
+) -> std::result::Result<usize, String> {
+    let wanted = squash_ws(signature);
+    if wanted.is_empty() {
+        return Err("no signature recorded".into());
+    }
+    let lines: Vec<&str> = src.lines().collect();
+
+    let mut hits: Vec<usize> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let first = squash_ws(line);
+        if first.is_empty() || first.starts_with("//") {
+            continue;
+        }
+        let mut joined = first.clone();
+        for next in &lines[i + 1..] {
+            if joined.len() >= first.len() + wanted.len() {
+                break;
+            }
+            joined.push_str(&squash_ws(next));
+        }
+        if first
+            .char_indices()
+            .any(|(at, _)| joined[at..].starts_with(&wanted))
+        {
+            hits.push(i);
+        }
+    }
+
+    if hits.len() > 1 {
+        let parent = fqpath.rsplit("::").nth(1).unwrap_or("");
+        let word = regex::Regex::new(&format!(r"\b{}\b", regex::escape(parent))).ok();
+        let in_parent: Vec<usize> = hits
+            .iter()
+            .copied()
+            .filter(|&h| {
+                enclosing_header(&lines, h)
+                    .zip(word.as_ref())
+                    .is_some_and(|(header, re)| re.is_match(header))
+            })
+            .collect();
+        hits = if in_parent.is_empty() {
+            hits.into_iter()
+                .filter(|&h| enclosing_header(&lines, h).is_none())
+                .collect()
+        } else {
+            in_parent
+        };
+    }
+
+    match hits.as_slice() {
+        [line0] => Ok(*line0),
+        [] => Err("signature not found".into()),
+        _ => Err("signature is ambiguous".into()),
+    }
+}
+
+/// Re-finds a result's item in a file that changed since it was harvested, returning the line its doc slot
+/// starts on (for fields, the top of the field's attributes, as the pipeline records it).
+///
+/// Errors:
+/// - A reason for the refusal when the recorded signature cannot be located exactly once (see
+///   [`locate_signature`]).
+fn relocate(src: &str, r: &LlmDocResult) -> std::result::Result<usize, String> {
+    let mut line0 = locate_signature(src, &r.signature, &r.fqpath)?;
+    if r.kind == "field" {
+        let lines: Vec<&str> = src.lines().collect();
+        while line0 > 0 && lines[line0 - 1].trim_start().starts_with("#[") {
+            line0 -= 1;
+        }
+    }
+    Ok(line0)
+}
+
+/// Why the line heuristics found no slot for an item.
+enum Skip {
+    /// No signature matching the item's kind was found near its recorded line.
+    NoSignature,
+    /// The item is documented and `overwrite` is off.
+    ExistingDoc,
+}
+
+/// Computes an item's doc slot with the line heuristics, for files that do not parse or items the parse does not
+/// see: the signature is re-found with the regexes near `start_line0` and the doc and attribute lines above it
+/// are classified.
+///
+/// Errors:
+/// - [`Skip`] when the item gets no edit.
+fn heuristic_slot(
+    original: &str,
+    kinds: &[LineKind],
+    r: &LlmDocResult,
+    start_line0: usize,
+    overwrite: bool,
+) -> std::result::Result<DocSlot, Skip> {
+    let re_for_kind = match r.kind.as_str() {
+        "struct" => re_struct(),
+        "field" => re_field(),
+        _ => re_fn_sig(),
+    };
+    let sig_line0_opt = if r.kind == "field" {
+        Some(start_line0)
+    } else {
+        find_sig_line_near(original, start_line0, re_for_kind)
+    };
+
+    let (ins_lo, ins_hi, indent_line_idx) = match (r.kind.as_str(), sig_line0_opt) {
+        ("struct", Some(sig_line0)) => match doc_slot_above_attrs(original, sig_line0, overwrite) {
+            Some(InsertWhere::Before(i)) => (i, i, i.min(sig_line0)),
+            Some(InsertWhere::Replace(lo, hi)) => (lo, hi, hi.min(sig_line0)),
+            None => return Err(Skip::ExistingDoc),
+        },
+        ("field", _) => match field_doc_slot(original, start_line0, overwrite) {
+            Some(InsertWhere::Before(i)) => (i, i, i),
+            Some(InsertWhere::Replace(lo, hi)) => (lo, hi, hi),
+            None => return Err(Skip::ExistingDoc),
+        },
+        (_, Some(sig_line0)) => {
+            let (lo, hi) = find_doc_insertion_range(original, sig_line0 + 1);
+            (lo, hi, sig_line0)
+        }
+        _ => return Err(Skip::NoSignature),
+    };
+    let lo = ins_lo.min(kinds.len());
+    let hi = ins_hi.min(kinds.len());
+
+    // Docs of this item outside the slot (say, between its attributes) would otherwise stay stacked on top of the
+    // new block, so they count as existing docs and are removed when replacing.
+    let item0 = (hi..kinds.len())
+        .find(|&k| kinds[k] == LineKind::Code)
+        .unwrap_or(hi);
+    let mut top = item0;
+    while top > 0 && matches!(kinds[top - 1], LineKind::Attr | LineKind::Doc(_)) {
+        top -= 1;
+    }
+    let mut strays: Vec<(usize, usize)> = Vec::new();
+    for (k, kind) in kinds.iter().enumerate().take(item0).skip(top.min(lo)) {
+        if !kind.is_doc() || (lo..hi).contains(&k) {
+            continue;
+        }
+        match strays.last_mut() {
+            Some((_, end)) if *end == k => *end = k + 1,
+            _ => strays.push((k, k + 1)),
+        }
+    }
+
+    Ok(DocSlot {
+        lo: ins_lo,
+        hi: ins_hi,
+        item_line0: indent_line_idx,
+        extra_docs: strays,
+    })
+}
+
+/// The doc edits planned for one source file.
+#[derive(Debug, Clone)]
+pub struct FilePatch {
+    /// Path of the source file, as harvested.
+    pub file: String,
+    /// The file contents before patching.
+    pub original: String,
+    /// The file contents with every planned doc block applied.
+    pub patched: String,
+    /// Fqpaths of the items whose doc block is written, in file order.
+    pub items: Vec<String>,
+}
+
+/// Computes the doc edits for every file without touching the working tree.
+/// For each result, it locates the appropriate insertion point in the file (before or after a function/struct/field signature)
+/// and applies the generated doc string with proper indentation to an in-memory copy of the file.
 /// If `overwrite` is `false`, it skips existing doc blocks.
 ///
 /// Parameters:
 /// - `results`: A slice of [`LlmDocResult`] containing the generated documentation and metadata (e.g., file path, start line, kind, and doc content).
-/// - `overwrite`: A boolean indicating whether to overwrite existing documentation blocks. If `false`, skips any file with existing doc blocks.
+/// - `opts`: [`PatchOptions`]; `overwrite` replaces existing doc blocks (otherwise documented items are skipped), `merge`
+///   replaces them only for items whose doc was merged, and `wrap` reflows each block to the given width after accounting for the indentation it is inserted at.
 ///
 /// Returns:
-/// - `Result<()>`: `Ok(())` on successful patching of all files, `Err` if any I/O or parsing error occurs.
+/// - `Result<Vec<FilePatch>>`: One [`FilePatch`] per file that receives at least one edit, `Err` if a file cannot be read.
 ///
 /// Errors:
-/// - Returns `Error::Io` with path and source if reading/writing files fails.
+/// - Returns `Error::Io` with path and source if reading files fails.
 /// - Returns `Error` if parsing or matching fails during doc insertion (e.g., no signature found, invalid line structure).
 ///
 /// Notes:
 /// - The function processes files in a grouped manner by file path, ensuring efficient batch operations.
 /// - For fields, insertion happens at the field's line; for functions/structs, it inserts above attributes or at the signature line.
-/// - Edits are applied only if no existing doc block is present (or if `overwrite` is true).
+/// - Edits are applied only if no existing doc block is present (or if `opts.overwrite` is true).
 /// - Line numbering is based on byte offsets, with line starts tracked for accurate insertion.
+/// - A file that parsed before patching is parsed again afterwards; if it no longer parses or declares different
+///   items, it gets no [`FilePatch`] and a warning names the offending items.
 ///
 /// Examples:
 /// ```no_run
 /// let results = vec![
 ///     LlmDocResult {
-///         file: "src/lib.rs",
+///         file: "src/lib.rs".into(),
 ///         start_line: Some(10),
 ///         kind: "fn".into(),
-///         llm_doc: "Returns the current time.".into(),
+///         llm_doc: "/// Returns the current time.".into(),
+///         ..Default::default()
 ///     },
 /// ];
 ///
-/// patch_files_with_docs(&results, false).await?;
+/// let plans = plan_patches(&results, &PatchOptions::default())?;
 /// ```
 #[instrument(level = "info", skip(results))]
-pub fn patch_files_with_docs(results: &[LlmDocResult], overwrite: bool) -> Result<()> {
+pub fn plan_patches(results: &[LlmDocResult], opts: &PatchOptions) -> Result<Vec<FilePatch>> {
+    let mut plans: Vec<FilePatch> = Vec::new();
     let mut by_file: BTreeMap<&str, Vec<&LlmDocResult>> = BTreeMap::new();
 
     for r in results {
         by_file.entry(&r.file).or_default().push(r);
     }
 
-    for (file, mut items) in by_file {
+    for (file, items) in by_file {
         let original = fs::read_to_string(file).map_err(|e| Error::Io {
             path: Some(PathBuf::from(file)),
             source: e,
@@ -430,108 +641,321 @@ pub fn patch_files_with_docs(results: &[LlmDocResult], overwrite: bool) -> Resul
         }
         line_starts.push(original.len());
 
-        let mut edits: Vec<Edit> = Vec::new();
+        // each edit with the index (in `patched`) of the item it belongs to
+        let mut edits: Vec<(usize, Edit)> = Vec::new();
+        let mut patched: Vec<String> = Vec::new();
         let mut skipped_no_sig = 0usize;
         let mut skipped_existing_doc = 0usize;
+        let mut skipped_drift = 0usize;
+        // (fqpath, markdown file, its new contents, whether the source already includes it)
+        let mut sidecars: Vec<(String, PathBuf, String, bool)> = Vec::new();
 
-        items.sort_by_key(|r| r.start_line.unwrap_or(0));
-
+        // Results carry the hash of the file they were generated from; when the file moved on since, each
+        // item's line is found again by its signature instead of trusting the recorded one.
+        let current_hash = stable_hash(&original);
+        let kinds = classify_lines(&original);
+        let lines: Vec<&str> = original.lines().collect();
+        // Slots come from the parse when the file parses; the line heuristics are the fallback.
+        let parsed = parse_items(&original);
+        if parsed.is_none() {
+            debug!(file, "file does not parse; using line heuristics");
+        }
+        let form = if opts.match_doc_style {
+            dominant_form(&kinds)
+        } else {
+            DocForm::Line
+        };
+        let mut located: Vec<(&LlmDocResult, usize)> = Vec::new();
         for r in items {
             let Some(start_line_1) = r.start_line else {
                 continue;
             };
-            let start_line0 = start_line_1.saturating_sub(1) as usize;
+            let recorded0 = start_line_1.saturating_sub(1) as usize;
+            let drifted = r.file_hash.as_deref().is_some_and(|h| h != current_hash);
+            if !drifted {
+                located.push((r, recorded0));
+                continue;
+            }
+            match relocate(&original, r) {
+                Ok(line0) => {
+                    debug!(item = %r.fqpath, from = recorded0 + 1, to = line0 + 1, "re-located drifted item");
+                    located.push((r, line0));
+                }
+                Err(reason) => {
+                    warn!(
+                        "{file} changed since its docs were generated and `{}` could not be re-located by its \
+                         signature ({reason}); not patching it. Regenerate its doc to pick up the change.",
+                        r.fqpath
+                    );
+                    skipped_drift += 1;
+                }
+            }
+        }
+        located.sort_by_key(|(_, line0)| *line0);
 
-            let re_for_kind = match r.kind.as_str() {
-                "struct" => re_struct(),
-                "field" => re_field(),
-                _ => re_fn_sig(),
-            };
-            let sig_line0_opt = if r.kind == "field" {
-                Some(start_line0)
-            } else {
-                find_sig_line_near(&original, start_line0, re_for_kind)
-            };
+        for (r, start_line0) in located {
+            let overwrite = opts.overwrite || (opts.merge && r.had_existing_doc);
 
-            let (ins_lo, ins_hi, indent_line_idx) = match (r.kind.as_str(), sig_line0_opt) {
-                ("struct", Some(sig_line0)) => {
-                    match doc_slot_above_attrs(&original, sig_line0, overwrite) {
-                        Some(InsertWhere::Before(i)) => (i, i, i.min(sig_line0)),
-                        Some(InsertWhere::Replace(lo, hi)) => (lo, hi, hi.min(sig_line0)),
-                        None => {
-                            skipped_existing_doc += 1;
-                            continue;
-                        }
+            let from_parse = parsed
+                .as_deref()
+                .and_then(|items| find_item(items, &r.kind, &r.fqpath, start_line0))
+                .and_then(|item| item.doc_slot(&lines));
+            if parsed.is_some() && from_parse.is_none() {
+                debug!(item = %r.fqpath, "no usable parsed span; falling back to line heuristics");
+            }
+            let slot = match from_parse {
+                Some(slot) => slot,
+                None => match heuristic_slot(&original, &kinds, r, start_line0, overwrite) {
+                    Ok(slot) => slot,
+                    Err(Skip::NoSignature) => {
+                        skipped_no_sig += 1;
+                        continue;
                     }
-                }
-                ("field", _) => match field_doc_slot(&original, start_line0, overwrite) {
-                    Some(InsertWhere::Before(i)) => (i, i, i),
-                    Some(InsertWhere::Replace(lo, hi)) => (lo, hi, hi),
-                    None => {
+                    Err(Skip::ExistingDoc) => {
                         skipped_existing_doc += 1;
                         continue;
                     }
                 },
-                (_, Some(sig_line0)) => {
-                    let (lo, hi) = find_doc_insertion_range(&original, sig_line0 + 1);
-                    (lo, hi, sig_line0)
-                }
-                _ => {
-                    skipped_no_sig += 1;
-                    continue;
-                }
             };
+            let (ins_lo, ins_hi, indent_line_idx) = (slot.lo, slot.hi, slot.item_line0);
+            let (lo, hi) = (ins_lo.min(lines.len()), ins_hi.min(lines.len()));
 
-            let lines: Vec<&str> = original.lines().collect();
-            let lo = ins_lo.min(lines.len());
-            let hi = ins_hi.min(lines.len());
-
-            let has_doc_block_in_range = (lo..hi).any(|k| {
-                let t = lines[k].trim_start();
-                t.starts_with("///") || t.starts_with("#![doc") || t.starts_with("#[doc")
-            });
+            let has_doc_block_in_range =
+                (lo..hi).any(|k| kinds[k].is_doc()) || !slot.extra_docs.is_empty();
             if !overwrite && has_doc_block_in_range {
                 skipped_existing_doc += 1;
                 continue;
             }
+            // pushed before the doc itself, so a deletion starting where the doc is inserted is applied first
+            for &(a, b) in &slot.extra_docs {
+                let start = line_starts[a];
+                edits.push((
+                    patched.len(),
+                    Edit {
+                        start,
+                        end: *line_starts.get(b).unwrap_or(&start),
+                        text: String::new(),
+                    },
+                ));
+            }
 
             let start_b = *line_starts.get(ins_lo).unwrap_or(&0);
             let end_b = *line_starts.get(ins_hi).unwrap_or(&start_b);
 
             let target_line = original.lines().nth(indent_line_idx).unwrap_or("");
-            let mut repl = indent_like(target_line, &r.llm_doc);
+            let mut repl = match opts.sidecar.as_deref() {
+                Some(dir) => {
+                    let md_path = sidecar_path(dir, &r.fqpath);
+                    let include = include_attr(Path::new(file), &md_path);
+                    let doc = match opts.wrap {
+                        Some(width) => reflow_doc(&r.llm_doc, width),
+                        None => r.llm_doc.clone(),
+                    };
+                    // An item already including its sidecar keeps its source; only the markdown is rewritten.
+                    let in_place =
+                        hi == lo + 1 && slot.extra_docs.is_empty() && lines[lo].trim() == include;
+                    sidecars.push((r.fqpath.clone(), md_path, markdown(&doc), in_place));
+                    if in_place {
+                        continue;
+                    }
+                    let indent = &target_line[..target_line.len() - target_line.trim_start().len()];
+                    format!("{indent}{include}\n")
+                }
+                None => {
+                    let doc = match opts.wrap {
+                        Some(width) => {
+                            reflow_doc(&r.llm_doc, width.saturating_sub(indent_width(target_line)))
+                        }
+                        None => r.llm_doc.clone(),
+                    };
+                    render_doc(&indent_like(target_line, &doc), form)
+                }
+            };
 
             // Add one blank line *before* the doc block when the previous line is non-blank.
-            // Do this only for top-level items (fn/struct), not for fields.
-            if r.kind != "field" {
+            // Do this only for top-level items (fn/struct), not for fields, and not when replacing a doc that sits
+            // below an attribute.
+            let below_attr = ins_lo > 0 && kinds.get(ins_lo - 1) == Some(&LineKind::Attr);
+            if r.kind != "field" && !below_attr {
                 repl = add_leading_blank_if_needed(&original, ins_lo, &repl);
             }
 
-            edits.push(Edit {
-                start: start_b,
-                end: end_b,
-                text: repl,
-            });
+            edits.push((
+                patched.len(),
+                Edit {
+                    start: start_b,
+                    end: end_b,
+                    text: repl,
+                },
+            ));
+            patched.push(r.fqpath.clone());
         }
 
-        if edits.is_empty() {
+        if edits.is_empty() && sidecars.is_empty() {
             eprintln!(
-                "Patched {}: 0 edits (skipped_no_sig={}, skipped_existing_doc={})",
-                file, skipped_no_sig, skipped_existing_doc
+                "Patched {}: 0 edits (skipped_no_sig={}, skipped_existing_doc={}, skipped_drift={})",
+                file, skipped_no_sig, skipped_existing_doc, skipped_drift
             );
             continue;
         }
 
-        let new_text = apply_edits(original, edits);
-        fs::write(file, new_text).map_err(|e| Error::Io {
-            path: Some(PathBuf::from(file)),
+        let new_text = apply_edits(
+            original.clone(),
+            edits.iter().map(|(_, e)| e.clone()).collect(),
+        );
+
+        // A file that parsed before must still parse, with the same items, before it is written.
+        if let Some(before) = parsed.as_deref() {
+            if let Err(problem) = verify_items(before, &new_text) {
+                let alone = |i: usize| {
+                    let own = edits
+                        .iter()
+                        .filter(|(o, _)| *o == i)
+                        .map(|(_, e)| e.clone());
+                    apply_edits(original.clone(), own.collect())
+                };
+                let mut offending: Vec<&str> = (0..patched.len())
+                    .filter(|&i| verify_items(before, &alone(i)).is_err())
+                    .map(|i| patched[i].as_str())
+                    .collect();
+                if offending.is_empty() {
+                    // only the combination breaks the file
+                    offending = patched.iter().map(String::as_str).collect();
+                }
+                warn!(
+                    "{file}: {problem} after patching; rolled back all {} edits to it. Offending items: {}",
+                    patched.len(),
+                    offending.join(", ")
+                );
+                continue;
+            }
+        }
+
+        if !patched.is_empty() {
+            plans.push(FilePatch {
+                file: file.to_string(),
+                original,
+                patched: new_text,
+                items: patched,
+            });
+        }
+        // Sidecars follow their source file, so one that was rolled back leaves them alone too.
+        for (fqpath, md_path, md, in_place) in sidecars {
+            let before = fs::read_to_string(&md_path).unwrap_or_default();
+            if before == md {
+                continue;
+            }
+            plans.push(FilePatch {
+                file: md_path.to_string_lossy().into_owned(),
+                original: before,
+                patched: md,
+                // items with a new include are counted with their source file
+                items: if in_place { vec![fqpath] } else { Vec::new() },
+            });
+        }
+    }
+
+    Ok(plans)
+}
+
+/// Patches source files by inserting or updating documentation blocks based on LLM-generated results.
+/// The edits are planned with [`plan_patches`] and each changed file is replaced atomically (temp file + rename),
+/// after its original is saved to a new backup run when `opts.backups` is set.
+///
+/// Parameters:
+/// - `results`: The generated docs.
+/// - `opts`: [`PatchOptions`], as for [`plan_patches`].
+///
+/// Returns:
+/// - `Result<Vec<String>>`: The fqpaths of the items whose doc was written.
+///
+/// Errors:
+/// - Returns `Error::Io` with path and source if reading, backing up or writing a file fails.
+#[instrument(level = "info", skip(results))]
+pub fn patch_files_with_docs(results: &[LlmDocResult], opts: &PatchOptions) -> Result<Vec<String>> {
+    let mut written: Vec<String> = Vec::new();
+    let mut backup = opts.backups.as_deref().map(Backup::start);
+    for plan in plan_patches(results, opts)? {
+        if let Some(b) = backup.as_mut() {
+            b.save(&plan.file, &plan.original, &plan.patched)?;
+        }
+        let path = Path::new(&plan.file);
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| Error::Io {
+                path: Some(dir.to_path_buf()),
+                source: e,
+            })?;
+        }
+        atomic_write(path, &plan.patched)?;
+        written.extend(plan.items);
+    }
+    if let Some(b) = backup.filter(|_| !written.is_empty()) {
+        info!(
+            run = b.run_id(),
+            "saved originals; `awful_rustdocs undo` restores them"
+        );
+    }
+    Ok(written)
+}
+
+/// Prints planned doc edits as a unified diff and/or saves them as a patch file, without writing any source.
+///
+/// Parameters:
+/// - `results`, `opts`: As for [`plan_patches`].
+/// - `diff`: Print the patch to stdout.
+/// - `patch_out`: Also write the patch to this file.
+///
+/// Errors:
+/// - Returns `Error::Io` if a source file cannot be read or the patch file cannot be written.
+pub fn preview_patches(
+    results: &[LlmDocResult],
+    opts: &PatchOptions,
+    diff: bool,
+    patch_out: Option<&Path>,
+) -> Result<()> {
+    let plans = plan_patches(results, opts)?;
+    let patch = render_patch(&plans);
+    if diff {
+        print!("{patch}");
+    }
+    if let Some(path) = patch_out {
+        fs::write(path, &patch).map_err(|e| Error::Io {
+            path: Some(path.to_path_buf()),
             source: e,
         })?;
+        info!(file=%path.display(), files = plans.len(), "wrote patch");
     }
-
     Ok(())
 }
 
+/// Renders planned patches as one `git apply`-compatible patch, a [`unified_diff`] per file in path order.
+///
+/// Absolute paths under the current directory are made relative to it, so the patch applies from there.
+pub fn render_patch(plans: &[FilePatch]) -> String {
+    let cwd = std::env::current_dir().ok();
+    plans
+        .iter()
+        .map(|p| {
+            let path = cwd
+                .as_deref()
+                .and_then(|c| Path::new(&p.file).strip_prefix(c).ok())
+                .map(|r| r.to_string_lossy().into_owned())
+                .unwrap_or_else(|| p.file.clone());
+            let diff = unified_diff(&path, &p.original, &p.patched, 3);
+            if p.original.is_empty() && !Path::new(&p.file).exists() {
+                // a new sidecar: `git apply` creates it
+                let path = path.trim_start_matches("./");
+                return diff.replacen(
+                    &format!("--- a/{path}\n"),
+                    "new file mode 100644\n--- /dev/null\n",
+                    1,
+                );
+            }
+            diff
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -877,4 +1301,325 @@ pub fn foo() {}
             got
         );
     }
+
+    // ---------- patch_files_with_docs ----------
+
+    #[test]
+    fn test_patch_files_with_docs_wraps_to_width_including_indent() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("lib.rs");
+        let src =
+            "impl Counter {\n    pub fn bump(&mut self) {\n        self.count += 1;\n    }\n}\n";
+        std::fs::write(&file, src).unwrap();
+
+        let result = LlmDocResult {
+            kind: "fn".into(),
+            fqpath: "crate::Counter::bump".into(),
+            file: file.to_string_lossy().into_owned(),
+            start_line: Some(2),
+            end_line: Some(4),
+            signature: "pub fn bump(&mut self)".into(),
+            llm_doc: "/// Increments the counter by one, saturating never, because the count is unbounded here.".into(),
+            ..Default::default()
+        };
+        let opts = PatchOptions {
+            wrap: Some(40),
+            ..PatchOptions::default()
+        };
+        let written = patch_files_with_docs(&[result], &opts).expect("patch succeeds");
+        assert_eq!(written, vec!["crate::Counter::bump".to_string()]);
+
+        let got = std::fs::read_to_string(&file).unwrap();
+        let doc_lines: Vec<&str> = got.lines().filter(|l| l.contains("///")).collect();
+        assert!(
+            doc_lines.len() > 1,
+            "Expected the doc to wrap.\nGOT:\n{}",
+            numbered(&got)
+        );
+        assert!(
+            doc_lines
+                .iter()
+                .all(|l| l.starts_with("    /// ") && l.chars().count() <= 40),
+            "Every doc line must be indented and fit in 40 columns.\nGOT:\n{}",
+            numbered(&got)
+        );
+    }
+
+    #[test]
+    fn test_render_patch_previews_without_touching_the_file() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("lib.rs");
+        let src = "use std::fmt;
+
+pub fn answer() -> u8 {
+    42
+}
+";
+        std::fs::write(&file, src).unwrap();
+
+        let result = LlmDocResult {
+            kind: "fn".into(),
+            fqpath: "crate::answer".into(),
+            file: file.to_string_lossy().into_owned(),
+            start_line: Some(3),
+            end_line: Some(5),
+            signature: "pub fn answer() -> u8".into(),
+            llm_doc: "/// Returns the answer.".into(),
+            ..Default::default()
+        };
+        let plans = plan_patches(&[result], &PatchOptions::default()).expect("plan succeeds");
+        assert_eq!(plans.len(), 1);
+        assert_eq!(plans[0].items, vec!["crate::answer".to_string()]);
+
+        let patch = render_patch(&plans);
+        assert!(
+            patch.contains("@@ -1,5 +1,6 @@\n use std::fmt;\n \n+/// Returns the answer.\n pub fn answer() -> u8 {\n"),
+            "PATCH:\n{patch}"
+        );
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            src,
+            "planning must not write the file"
+        );
+    }
+
+    // ---------- drift ----------
+
+    #[test]
+    fn test_locate_signature_disambiguates_by_enclosing_impl() {
+        let src = "impl A {\n    pub fn new() -> Self {\n        A\n    }\n}\n\nimpl B {\n    pub fn new()\n        -> Self {\n        B\n    }\n}\n";
+        assert_eq!(
+            locate_signature(src, "pub fn new() -> Self", "crate::B::new"),
+            Ok(7),
+            "the wrapped `new` inside `impl B` is meant"
+        );
+        assert_eq!(
+            locate_signature(src, "pub fn new() -> Self", "crate::A::new"),
+            Ok(1)
+        );
+        assert!(locate_signature(src, "pub fn new() -> Self", "crate::C::new").is_err());
+        assert_eq!(
+            locate_signature(src, "pub fn gone()", "crate::A::gone"),
+            Err("signature not found".to_string())
+        );
+    }
+
+    #[test]
+    fn test_plan_patches_relocates_drifted_items_and_refuses_changed_ones() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("lib.rs");
+        let harvested = "pub fn one() {}\n\npub fn two(x: u8) {}\n";
+        let mk = |fqpath: &str, line: u32, signature: &str| LlmDocResult {
+            kind: "fn".into(),
+            fqpath: fqpath.into(),
+            file: file.to_string_lossy().into_owned(),
+            start_line: Some(line),
+            end_line: Some(line),
+            signature: signature.into(),
+            llm_doc: format!("/// Doc of {fqpath}."),
+            file_hash: Some(stable_hash(harvested)),
+            ..Default::default()
+        };
+        let results = [
+            mk("crate::one", 1, "pub fn one()"),
+            mk("crate::two", 3, "pub fn two(x: u8)"),
+        ];
+
+        // Two lines were added on top and `two` changed its signature after the docs were generated.
+        std::fs::write(
+            &file,
+            "use std::fmt;\n\npub fn one() {}\n\npub fn two(x: u16) {}\n",
+        )
+        .unwrap();
+        let plans = plan_patches(&results, &PatchOptions::default()).expect("plan succeeds");
+        assert_eq!(
+            plans[0].items,
+            vec!["crate::one".to_string()],
+            "the changed item is refused"
+        );
+        assert_eq!(
+            plans[0].patched,
+            "use std::fmt;\n\n/// Doc of crate::one.\npub fn one() {}\n\npub fn two(x: u16) {}\n",
+            "GOT:\n{}",
+            numbered(&plans[0].patched)
+        );
+
+        // Unchanged files are patched at the recorded lines without searching.
+        std::fs::write(&file, harvested).unwrap();
+        let plans = plan_patches(&results, &PatchOptions::default()).expect("plan succeeds");
+        assert_eq!(plans[0].items.len(), 2);
+    }
+
+    // ---------- doc forms ----------
+
+    #[test]
+    fn test_plan_patches_replaces_block_and_attr_docs_without_stacking() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("lib.rs");
+        let src = "/**\n * Old block doc.\n */\n#[inline]\n/// Stray.\npub fn one() {}\n\n#[derive(Debug)]\n#[doc = r\"Old\nattr doc.\"]\npub struct Two {\n    /** Old field doc. */\n    pub x: u8,\n}\n";
+        std::fs::write(&file, src).unwrap();
+        let mk = |kind: &str, fqpath: &str, line: u32| LlmDocResult {
+            kind: kind.into(),
+            fqpath: fqpath.into(),
+            file: file.to_string_lossy().into_owned(),
+            start_line: Some(line),
+            llm_doc: format!("/// New {kind} doc."),
+            had_existing_doc: true,
+            ..Default::default()
+        };
+        let results = [
+            mk("fn", "crate::one", 6),
+            mk("struct", "crate::Two", 11),
+            mk("field", "crate::Two::x", 12),
+        ];
+
+        let kept = plan_patches(&results, &PatchOptions::default()).expect("plan succeeds");
+        assert!(kept.is_empty(), "every item already has a doc: {kept:#?}");
+
+        let opts = PatchOptions {
+            overwrite: true,
+            match_doc_style: true,
+            ..PatchOptions::default()
+        };
+        let plans = plan_patches(&results, &opts).expect("plan succeeds");
+        assert_eq!(
+            plans[0].patched,
+            "#[inline]\n/**\n * New fn doc.\n */\npub fn one() {}\n\n#[derive(Debug)]\n/**\n * New struct doc.\n */\npub struct Two {\n    /**\n     * New field doc.\n     */\n    pub x: u8,\n}\n",
+            "old docs in every form are replaced once, in the file's block style.\nGOT:\n{}",
+            numbered(&plans[0].patched)
+        );
+    }
+
+    // ---------- parsed spans ----------
+
+    #[test]
+    fn test_plan_patches_uses_parsed_spans_and_falls_back_when_unparsable() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("lib.rs");
+        let mk = |fqpath: &str, line: u32| LlmDocResult {
+            kind: "fn".into(),
+            fqpath: fqpath.into(),
+            file: file.to_string_lossy().into_owned(),
+            start_line: Some(line),
+            llm_doc: format!("/// Doc of {fqpath}."),
+            ..Default::default()
+        };
+
+        std::fs::write(
+            &file,
+            "pub(in crate) const\nfn spread(\n    x: u8,\n) {}\nfn next() {}\n",
+        )
+        .unwrap();
+        let plans = plan_patches(
+            &[mk("crate::spread", 1), mk("crate::next", 5)],
+            &PatchOptions::default(),
+        )
+        .expect("plan succeeds");
+        assert_eq!(
+            plans[0].patched,
+            "/// Doc of crate::spread.\npub(in crate) const\nfn spread(\n    x: u8,\n) {}\n\n/// Doc of crate::next.\nfn next() {}\n",
+            "GOT:\n{}",
+            numbered(&plans[0].patched)
+        );
+
+        std::fs::write(&file, "fn broken() {\n").unwrap();
+        let plans = plan_patches(&[mk("crate::broken", 1)], &PatchOptions::default())
+            .expect("plan succeeds");
+        assert_eq!(
+            plans[0].patched, "/// Doc of crate::broken.\nfn broken() {\n",
+            "a file that does not parse is patched with the line heuristics"
+        );
+    }
+
+    // ---------- re-validation ----------
+
+    #[test]
+    fn test_plan_patches_rolls_back_files_that_no_longer_parse() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let file = dir.path().join("lib.rs");
+        let src =
+            "pub struct S {\n    pub a: u8,\n}\n\nfn total() -> u8 {\n    1\n        + 2\n}\n";
+        std::fs::write(&file, src).unwrap();
+        let mk = |kind: &str, fqpath: &str, line: u32| LlmDocResult {
+            kind: kind.into(),
+            fqpath: fqpath.into(),
+            file: file.to_string_lossy().into_owned(),
+            start_line: Some(line),
+            llm_doc: "/// A doc.".into(),
+            ..Default::default()
+        };
+        // A field the parse does not know, recorded on a line inside an expression: the heuristics put its doc
+        // in the middle of `1 + 2`.
+        let results = [
+            mk("struct", "crate::S", 1),
+            mk("field", "crate::S::gone", 7),
+        ];
+        let plans = plan_patches(&results, &PatchOptions::default()).expect("plan succeeds");
+        assert!(
+            plans.is_empty(),
+            "the broken file is rolled back as a whole: {plans:#?}"
+        );
+
+        let plans = plan_patches(&results[..1], &PatchOptions::default()).expect("plan succeeds");
+        assert_eq!(plans[0].items, vec!["crate::S".to_string()]);
+    }
+
+    // ---------- sidecars ----------
+
+    #[test]
+    fn test_sidecar_mode_writes_markdown_and_updates_it_in_place() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        let file = dir.path().join("src").join("a.rs");
+        std::fs::write(
+            &file,
+            "use x;
+
+    pub fn f() {}
+",
+        )
+        .unwrap();
+        let mk = |doc: &str| LlmDocResult {
+            kind: "fn".into(),
+            fqpath: "crate::a::f".into(),
+            file: file.to_string_lossy().into_owned(),
+            start_line: Some(3),
+            signature: "pub fn f()".into(),
+            llm_doc: doc.into(),
+            ..Default::default()
+        };
+        let md = dir.path().join("docs").join("crate.a.f.md");
+        let mut opts = PatchOptions {
+            sidecar: Some(dir.path().join("docs")),
+            ..Default::default()
+        };
+
+        let first = [mk("/// Does f.\n///\n/// More.")];
+        let patch = render_patch(&plan_patches(&first, &opts).unwrap());
+        assert!(
+            patch.contains("new file mode 100644\n--- /dev/null\n"),
+            "a new sidecar is created by the patch: {patch}"
+        );
+
+        let written = patch_files_with_docs(&first, &opts).unwrap();
+        assert_eq!(written, vec!["crate::a::f".to_string()]);
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            "use x;\n\n    #[doc = include_str!(\"../docs/crate.a.f.md\")]\n    pub fn f() {}\n",
+            "the include replaces the `///` block, indented like the item"
+        );
+        assert_eq!(std::fs::read_to_string(&md).unwrap(), "Does f.\n\nMore.\n");
+
+        let rerun = [mk("/// Does f, better.")];
+        assert!(
+            plan_patches(&rerun, &opts).unwrap().is_empty(),
+            "an included sidecar is an existing doc"
+        );
+        opts.overwrite = true;
+        let plans = plan_patches(&rerun, &opts).unwrap();
+        assert_eq!(plans.len(), 1, "only the markdown changes: {plans:#?}");
+        assert_eq!(plans[0].file, md.to_string_lossy());
+        assert_eq!(plans[0].items, vec!["crate::a::f".to_string()]);
+        assert_eq!(plans[0].patched, "Does f, better.\n");
+    }
 }
@@ -1,43 +1,125 @@
+use crate::cli::GenerateOpts;
+use crate::doccomment::to_line_comments;
 use crate::error::{Error, Result};
-use crate::grep::{calls_in_function_span, qualified_paths_in_span};
-use crate::model::{LlmDocResult, Row, StructDocResponse};
+use crate::grep::{
+    CallSite, Hazard, calls_in_function_span, hazards_in_span, qualified_paths_in_span,
+};
+use crate::links::SymbolIndex;
+use crate::llm::{LlmClient, LlmRequest};
+use crate::model::{FieldDocOut, LlmDocResult, Row, StructDocResponse};
 use crate::model::{collect_symbol_refs, referencing_functions};
-use crate::prompt::{build_markdown_question, build_struct_request_with_refs};
+use crate::patch::indent_width;
+use crate::prompt::{
+    Prompts, build_critique_request, build_merge_request, build_missing_sections_followup,
+    build_unknown_refs_followup, render_fn_prompt, render_struct_prompt,
+};
 use crate::regexes::re_word;
-use crate::sanitize::sanitize_llm_doc;
+use crate::runner::ToolRunner;
+use crate::sanitize::{CritiqueVerdict, parse_critique_verdict, reflow_doc, sanitize_llm_doc};
+use crate::sidecar::row_doc;
+use crate::style::{StyleProfile, merge_docs, merge_docs_with, parse_doc};
+use crate::transcript::{Exchange, Recorder};
+use crate::util::fqpath_to_filename;
+use crate::validate::{
+    code_identifiers, missing_sections, ref_warnings, required_sections, section_warnings,
+    unknown_refs,
+};
 
-use awful_aj::api;
-use awful_aj::config::AwfulJadeConfig;
 use awful_aj::template::ChatTemplate;
 use tracing::{debug, error, info, info_span, instrument, warn};
 
 use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
 use std::time::Instant;
 
 /// Context container for the generation pipeline, holding configuration, templates, and generation options.
 /// Used across the pipeline to maintain state and enable consistent message formatting and behavior.
 pub struct Ctx {
-    /// Configuration settings for the Awful Jade system.
-    pub cfg: AwfulJadeConfig,
+    /// Backend that answers LLM requests (Awful Jade by default, canned fixtures for offline runs).
+    pub client: Box<dyn LlmClient>,
     /// Function to render chat messages using a template (for user messages).
     /// Must be callable during generation to format prompts.
     pub tpl_fn: ChatTemplate,
     /// Function to render structured data using a template (for structured outputs).
     /// Used when generating structured responses like JSON or tables.
     pub tpl_struct: ChatTemplate,
+    /// Template for the optional review pass; `Some` only when `--critique` is set.
+    pub tpl_critique: Option<ChatTemplate>,
+    /// Prompt body templates for functions and structs.
+    pub prompts: Prompts,
+    /// House style applied to every sanitized doc; `None` keeps the sanitizer's headings.
+    pub style: Option<StyleProfile>,
+    /// Collects prompts and raw answers when `--record` is set.
+    pub recorder: Option<Recorder>,
     /// Command-line options used to control generation behavior (e.g., max tokens, temperature).
     /// Passed from CLI to influence output parameters.
-    pub opts: crate::cli::GenerateOpts,
+    pub opts: GenerateOpts,
+}
+
+impl Ctx {
+    /// Applies the house style, if one is configured, to a sanitized doc block.
+    fn styled(&self, doc: String) -> String {
+        match &self.style {
+            Some(profile) => profile.apply(&doc),
+            None => doc,
+        }
+    }
+
+    /// Reflows a doc block to `--wrap` the way the patcher will write it: to the full width in a sidecar,
+    /// otherwise to what the indentation of `target_line` (the source line the doc goes above) leaves. Doing it
+    /// here keeps `docs.json`, the reports and `--review` showing the text that is written.
+    fn wrapped(&self, doc: String, target_line: &str) -> String {
+        match self.opts.wrap {
+            Some(width) if self.opts.sidecar.is_some() => reflow_doc(&doc, width),
+            Some(width) => reflow_doc(&doc, width.saturating_sub(indent_width(target_line))),
+            None => doc,
+        }
+    }
+}
+
+/// Returns the canonical names of the sections an existing doc already has (for `--merge` prompts).
+fn section_names(doc: &str) -> Vec<String> {
+    parse_doc(&to_line_comments(doc))
+        .sections
+        .into_iter()
+        .map(|s| s.title)
+        .collect()
+}
+
+/// Context gathered for a function before prompting (see [`Pipeline::fn_context`]).
+pub struct FnContext {
+    /// Harvested symbols and qualified paths referenced in the body.
+    pub referenced_symbols: Vec<String>,
+    /// Call sites inside the function span.
+    pub calls_in_span: Vec<CallSite>,
+    /// Panicking operations and `unsafe` blocks inside the function span.
+    pub hazards: Vec<Hazard>,
+}
+
+/// Context gathered for a struct before prompting (see [`Pipeline::struct_context`]).
+pub struct StructContext {
+    /// Full text of the struct's source file.
+    pub file_src: String,
+    /// 0-based line of the body's opening brace.
+    pub body_lo: usize,
+    /// 0-based line of the body's closing brace.
+    pub body_hi: usize,
+    /// The body block, verbatim.
+    pub body_text: String,
+    /// Fully-qualified paths of functions that reference the struct.
+    pub refs: Vec<String>,
 }
 
 /// A pipeline that processes data through stages, maintaining context and state across rows and symbols.
 pub struct Pipeline<'a> {
-    /// Context reference for the pipeline execution.
-    pub ctx: &'a Ctx,
+    /// Command-line options controlling which items are selected.
+    pub opts: &'a GenerateOpts,
     /// Rows of data processed in the current pipeline stage.
     pub rows: Vec<Row>,
     /// Set of all unique symbol names encountered during processing.
     pub all_symbols: BTreeSet<String>,
+    /// Set of all fully-qualified paths of harvested items.
+    pub all_fqpaths: BTreeSet<String>,
     /// Function rows (e.g., generated or transformed rows) for functional processing.
     pub fn_rows: Vec<Row>,
 }
@@ -50,7 +132,7 @@ impl<'a> Pipeline<'a> {
     /// context, raw rows, collected symbols, and function rows for later processing.
     ///
     /// # Parameters
-    /// - `ctx`: A reference to the execution context containing configuration and state.
+    /// - `opts`: The generation options (used for `--only` filtering).
     /// - `rows`: A vector of [`Row`] entries representing harvested data, each with a `name` and `kind`.
     ///
     /// # Returns
@@ -58,6 +140,7 @@ impl<'a> Pipeline<'a> {
     ///
     /// # Notes
     /// - The `all_symbols` field collects non-empty `name` fields from all rows.
+    /// - The `all_fqpaths` field collects non-empty `fqpath` fields from all rows.
     /// - The `fn_rows` field collects only rows where `kind` is `"fn"`, preserving their original data.
     /// - This function does not perform any I/O or side effects beyond data aggregation.
     ///
@@ -67,35 +150,47 @@ impl<'a> Pipeline<'a> {
     /// use crate::Ctx;
     /// use crate::Row;
     ///
-    /// let ctx = Ctx::default();
+    /// let opts = GenerateOpts::default();
     /// let rows = vec![
     ///     Row { name: "x".into(), kind: "var".into() },
     ///     Row { name: "y".into(), kind: "fn".into() },
     ///     Row { name: "".into(), kind: "var".into() },
     /// ];
     ///
-    /// Pipeline::from_harvest(&ctx, rows)
+    /// Pipeline::from_harvest(&opts, rows)
     /// ```
-    pub fn from_harvest(ctx: &'a Ctx, rows: Vec<Row>) -> Self {
+    pub fn from_harvest(opts: &'a GenerateOpts, rows: Vec<Row>) -> Self {
         let all_symbols = rows
             .iter()
             .map(|r| r.name.clone())
             .filter(|s| !s.is_empty())
             .collect();
+        let all_fqpaths = rows
+            .iter()
+            .map(|r| r.fqpath.clone())
+            .filter(|s| !s.is_empty())
+            .collect();
         let fn_rows = rows.iter().filter(|r| r.kind == "fn").cloned().collect();
         Self {
-            ctx,
+            opts,
             rows,
             all_symbols,
+            all_fqpaths,
             fn_rows,
         }
     }
 
+    /// Returns the identifiers a generated doc may reference without being flagged: every harvested symbol
+    /// name plus every fully-qualified path.
+    pub fn known_refs(&self) -> BTreeSet<String> {
+        self.all_symbols.union(&self.all_fqpaths).cloned().collect()
+    }
+
     /// Returns an iterator over rows that match the specified criteria: either have a kind of "fn" or "struct", and optionally match a name or full qualified path in the `only` list.
     /// If `only` is empty, all rows with the specified kinds are included.
     ///
     /// Parameters:
-    /// - `self`: The `Pipeline<'a>` instance containing the rows and context options.
+    /// - `self`: The `Pipeline<'a>` instance containing the rows and options.
     ///
     /// Returns:
     /// - An iterator over references to `Row` that match the filtering conditions.
@@ -104,6 +199,7 @@ impl<'a> Pipeline<'a> {
     /// - The filtering is based on the `kind` field of the row, which must be either "fn" or "struct".
     /// - If `only` is provided, the row's `name` or `fqpath` must match one of the strings in `only`.
     /// - The `only` list is checked for exact matches using `&r.name` or `&r.fqpath`.
+    /// - When `opts.selection` is set (e.g. by `--stale`), the row's `fqpath` must also be in it.
     ///
     /// Examples:
     /// ```rust
@@ -115,24 +211,293 @@ impl<'a> Pipeline<'a> {
     ///     Row { kind: "struct", name: "baz", fqpath: "baz::qux" },
     /// ];
     ///
-    /// let ctx = crate::context::Context { opts: crate::context::Opts { only: vec!["foo".into()] }, ..Default::default() };
-    /// let pipeline = Pipeline { rows, ctx };
+    /// let opts = GenerateOpts { only: vec!["foo".into()], ..Default::default() };
+    /// let pipeline = Pipeline::from_harvest(&opts, rows);
     /// let filtered = pipeline.wanted();
     ///
     /// assert!(filtered.any());
     /// assert!(!filtered.any()); // if only contains "bar", then no match
     /// ```
     pub fn wanted<'b>(&'b self) -> impl Iterator<Item = &'b Row> {
-        let only = &self.ctx.opts.only;
+        let only = &self.opts.only;
+        let selection = &self.opts.selection;
         self.rows.iter().filter(move |r| {
             (r.kind == "fn" || r.kind == "struct")
                 && (only.is_empty() || only.iter().any(|s| s == &r.name || s == &r.fqpath))
+                && selection.as_ref().is_none_or(|s| s.contains(&r.fqpath))
         })
     }
+
+    /// Groups the wanted rows by file, each file's items sorted by start line then fqpath.
+    ///
+    /// This is the traversal order used for generation and prompt export, so `--limit` selects the same
+    /// items in both.
+    pub fn wanted_by_file(&self) -> BTreeMap<String, Vec<Row>> {
+        let mut per_file: BTreeMap<String, Vec<Row>> = BTreeMap::new();
+        for r in self.wanted() {
+            per_file.entry(r.file.clone()).or_default().push(r.clone());
+        }
+        for v in per_file.values_mut() {
+            v.sort_by_key(|r| (r.span.start_line.unwrap_or(0), r.fqpath.clone()));
+        }
+        if per_file.is_empty() && !self.opts.only.is_empty() {
+            warn!(only = %self.opts.only.join(", "), "no items matched --only filter");
+        }
+        per_file
+    }
+
+    /// Collects the body-level context for a function prompt.
+    ///
+    /// # Parameters
+    /// - `runner`: Tool runner used for the `ast-grep` queries.
+    /// - `item`: The function row.
+    ///
+    /// # Returns
+    /// The referenced symbols (harvested names found in the body plus qualified paths unless `--no-paths`)
+    /// the call sites in the function span (unless `--no-calls`), and the panicking operations and `unsafe` blocks
+    /// in it (unless `--no-hazards`).
+    ///
+    /// # Notes
+    /// - `ast-grep` failures are ignored and yield empty lists, as before.
+    pub fn fn_context(&self, runner: &dyn ToolRunner, item: &Row) -> FnContext {
+        let mut referenced_symbols = collect_symbol_refs(
+            item.body_text.as_deref().unwrap_or(""),
+            &self.all_symbols,
+            re_word(),
+        );
+
+        let (start_b, end_b) = item.span_bytes();
+
+        if !self.opts.no_paths {
+            let qpaths =
+                qualified_paths_in_span(runner, &item.file, start_b, end_b).unwrap_or_default();
+            referenced_symbols.extend(qpaths);
+        }
+
+        let calls_in_span = if self.opts.no_calls {
+            vec![]
+        } else {
+            calls_in_function_span(runner, &item.file, start_b, end_b).unwrap_or_default()
+        };
+
+        let hazards = if self.opts.no_hazards {
+            vec![]
+        } else {
+            hazards_in_span(runner, &item.file, start_b, end_b).unwrap_or_default()
+        };
+
+        FnContext {
+            referenced_symbols,
+            calls_in_span,
+            hazards,
+        }
+    }
+
+    /// Loads the struct's source file, locates its body block and finds the functions referencing it.
+    ///
+    /// # Parameters
+    /// - `item`: The struct row.
+    ///
+    /// # Returns
+    /// `Some(StructContext)` on success, or `None` (with a warning logged) when the struct signature or body
+    /// cannot be located near the harvested span.
+    ///
+    /// # Errors
+    /// - `Error::Io` if the source file cannot be read.
+    pub fn struct_context(&self, item: &Row) -> Result<Option<StructContext>> {
+        let file_src = std::fs::read_to_string(&item.file).map_err(|e| Error::Io {
+            path: Some(std::path::PathBuf::from(&item.file)),
+            source: e,
+        })?;
+
+        let approx_line0 = item.span.start_line.unwrap_or(1).saturating_sub(1) as usize;
+        let Some(struct_sig0) = crate::regexes::find_sig_line_near(
+            &file_src,
+            approx_line0,
+            crate::regexes::re_struct(),
+        ) else {
+            warn!("could not locate struct sig");
+            return Ok(None);
+        };
+        let Some((body_lo, body_hi)) = crate::util::find_struct_body_block(&file_src, struct_sig0)
+        else {
+            warn!("could not locate struct body");
+            return Ok(None);
+        };
+        let body_text = crate::util::extract_lines(&file_src, body_lo, body_hi);
+
+        let fn_rows_refs: Vec<&Row> = self.fn_rows.iter().collect();
+        let refs = referencing_functions(&item.name, &item.fqpath, &fn_rows_refs);
+
+        Ok(Some(StructContext {
+            file_src,
+            body_lo,
+            body_hi,
+            body_text,
+            refs,
+        }))
+    }
+}
+
+/// Sends a single question to the LLM and returns the raw answer together with the request latency.
+///
+/// # Parameters
+/// - `ctx`: The generation context holding the LLM client.
+/// - `question`: The fully rendered prompt.
+/// - `template_name`: The name the template was loaded under (e.g. `rustdoc_fn`).
+/// - `tpl`: The chat template to send the question with.
+/// - `fqpath`: The item being documented, used for error context.
+///
+/// # Returns
+/// A tuple of the raw answer and the elapsed time in milliseconds.
+///
+/// # Notes
+/// - With `--record`, the exchange is also handed to `ctx.recorder`.
+///
+/// # Errors
+/// - Whatever the configured [`LlmClient`] reports, typically `Error::External`.
+async fn ask(
+    ctx: &Ctx,
+    question: String,
+    template_name: &str,
+    tpl: &ChatTemplate,
+    fqpath: &str,
+) -> Result<(String, u128)> {
+    let t_llm = Instant::now();
+    let req = LlmRequest {
+        fqpath,
+        template_name,
+        template: tpl,
+        question,
+    };
+    let answer = ctx.client.ask(&req).await.map_err(|e| {
+        error!(error = %e, fqpath = %fqpath, "LLM ask() failed");
+        e
+    })?;
+    let llm_ms = t_llm.elapsed().as_millis();
+    if let Some(rec) = &ctx.recorder {
+        rec.record(
+            fqpath,
+            Exchange {
+                template: template_name.to_string(),
+                prompt: req.question,
+                response: answer.clone(),
+                elapsed_ms: llm_ms as u64,
+            },
+        );
+    }
+    Ok((answer, llm_ms))
+}
+
+/// Result warnings for a critique verdict: an `"empty"` one means the doc was never reviewed.
+fn critique_warnings(critique: Option<&str>) -> Vec<String> {
+    match critique {
+        Some("empty") => {
+            vec!["critique returned no verdict: the draft was kept unreviewed".to_string()]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Sends a draft doc through the critique pass and returns the doc to keep together with the verdict.
+///
+/// # Parameters
+/// - `ctx`: The generation context; the pass is skipped when `ctx.tpl_critique` is `None`.
+/// - `item`: The harvested item the draft was written for.
+/// - `body_text`: The item body shown to the reviewer.
+/// - `draft`: The sanitized draft rustdoc block.
+/// - `known`: Identifiers the doc may reference (see [`Pipeline::known_refs`]).
+///
+/// # Returns
+/// The doc to keep and the verdict: `None` without `--critique`, otherwise `"approved"`, `"revised"`,
+/// `"discarded"` when `--strict-refs` rejects a revision that introduces unknown references, or `"empty"` when
+/// the reviewer answered with nothing (the draft is kept; see [`critique_warnings`]).
+///
+/// # Errors
+/// - `Error::External` if the critique request fails.
+async fn critique_draft(
+    ctx: &Ctx,
+    item: &Row,
+    body_text: &str,
+    draft: String,
+    known: &BTreeSet<String>,
+) -> Result<(String, Option<String>)> {
+    let Some(tpl) = &ctx.tpl_critique else {
+        return Ok((draft, None));
+    };
+    let question = build_critique_request(item, body_text, &draft);
+    debug!(
+        question_len = question.len(),
+        "sending LLM request (critique)"
+    );
+    let (answer, llm_ms) = ask(
+        ctx,
+        question,
+        &ctx.opts.critique_template,
+        tpl,
+        &item.fqpath,
+    )
+    .await?;
+
+    match parse_critique_verdict(&answer) {
+        CritiqueVerdict::Approved => {
+            info!(llm_ms, "critique approved draft");
+            Ok((draft, Some("approved".into())))
+        }
+        CritiqueVerdict::Empty => {
+            warn!(
+                llm_ms,
+                "critique returned an empty reply; keeping the draft"
+            );
+            Ok((draft, Some("empty".into())))
+        }
+        CritiqueVerdict::Revised(revised) => {
+            if ctx.opts.strict_refs {
+                let unknown = unknown_refs(&revised, known);
+                if !unknown.is_empty() {
+                    warn!(
+                        unknown = %unknown.join(", "),
+                        "discarding critique revision: it references unknown identifiers"
+                    );
+                    return Ok((draft, Some("discarded".into())));
+                }
+            }
+            info!(
+                llm_ms,
+                doc_lines = revised.lines().count(),
+                "critique revised draft"
+            );
+            Ok((revised, Some("revised".into())))
+        }
+    }
+}
+
+/// Parses the structured struct response, falling back to treating the whole payload as the struct doc.
+///
+/// # Parameters
+/// - `raw`: The raw LLM answer for a struct request.
+///
+/// # Returns
+/// The (unsanitized) struct doc and the per-field docs; the field list is empty when parsing fails.
+fn parse_struct_answer(raw: String) -> (String, Vec<FieldDocOut>) {
+    let parsed: Result<StructDocResponse> = serde_json::from_str(&raw).map_err(|e| Error::Json {
+        context: "struct JSON parse",
+        source: e,
+    });
+    match parsed {
+        Ok(v) => {
+            info!(fields = v.fields.len(), "parsed struct JSON");
+            (v.struct_doc, v.fields)
+        }
+        Err(err) => {
+            warn!(error = %err, "struct JSON parse failed; using raw payload");
+            (raw, vec![])
+        }
+    }
 }
 
 /// Runs the generation of Rust documentation for symbols (functions and structs) based on provided rows of code metadata.
-/// For each symbol, it extracts relevant context, builds a question using references and call chains, and sends it to the LLM via `api::ask`.
+/// For each symbol, it extracts relevant context, builds a question using references and call chains, and sends it to the configured `LlmClient`.
 /// The results are sanitized and stored in `LlmDocResult` format, grouped by file and processed in order of line position.
 /// If a symbol already has documentation and `--overwrite` is not specified, it is skipped unless it's a struct.
 /// Function execution includes timing and logging for performance and debugging.
@@ -149,44 +514,92 @@ impl<'a> Pipeline<'a> {
 /// - Returns `Error::Json` when parsing LLM-generated JSON response fails.
 /// - Returns `Error::External` when the LLM API call fails.
 /// - Returns `Error::External` if no struct signature or body is found in the source file.
+/// - Returns `Error::Io` or `Error::Json` if transcripts cannot be written with `--record`. Transcripts are
+///   written when generation fails too, so the exchanges leading up to the failure can be inspected or replayed.
 ///
 /// # Notes
 /// - Processing stops early if `--limit` is reached.
 /// - Existing documentation is skipped for non-struct symbols unless `--overwrite` is enabled.
 /// - Structs require parsing of the source file to locate their signature and body block.
 /// - Symbol references and function calls are collected using regex and span analysis.
-/// - All LLM requests use the configured template (function or struct) and are passed through the `api::ask` layer.
+/// - All LLM requests use the configured template (function or struct) and are sent through `ctx.client`.
+/// - Backticked references in each doc are checked against the harvest; unknown ones are recorded as warnings,
+///   and with `--strict-refs` the item is re-prompted once and rejected if they persist.
+/// - With `--critique`, each fn and struct doc gets a second request using the critique template; the verdict is
+///   stored in `LlmDocResult::critique`. Field docs are not reviewed.
 #[instrument(level = "info", skip(ctx, rows))]
 pub async fn run_generation<'a>(ctx: &'a Ctx, rows: Vec<Row>) -> Result<Vec<LlmDocResult>> {
+    let mut all_results: Vec<LlmDocResult> = Vec::new();
+    let outcome = generate_into(ctx, rows, &mut all_results).await;
+    if let Some(rec) = &ctx.recorder {
+        // written on failure too: the exchanges that led up to it are what a failed run is debugged with
+        let written = rec.write(&all_results);
+        match (&outcome, written) {
+            (Err(_), Err(e)) => warn!(error = %e, "failed to write transcripts of the failed run"),
+            (Err(e), Ok(n)) => {
+                warn!(error = %e, items = n, "generation failed; wrote transcripts so far")
+            }
+            (Ok(_), written) => {
+                written?;
+            }
+        }
+    }
+    outcome.map(|()| all_results)
+}
+
+/// The body of [`run_generation`]: generates docs into `all_results`, which keeps what was produced before an
+/// error.
+async fn generate_into(
+    ctx: &Ctx,
+    rows: Vec<Row>,
+    all_results: &mut Vec<LlmDocResult>,
+) -> Result<()> {
     debug!(rows = rows.len(), "generation started");
 
-    let pipe = Pipeline::from_harvest(ctx, rows);
+    let pipe = Pipeline::from_harvest(&ctx.opts, rows);
 
     // group by file for stable traversal
-    let mut per_file: BTreeMap<String, Vec<Row>> = BTreeMap::new();
-
-    for r in pipe.wanted() {
-        per_file.entry(r.file.clone()).or_default().push(r.clone());
-    }
-
-    for v in per_file.values_mut() {
-        v.sort_by_key(|r| (r.span.start_line.unwrap_or(0), r.fqpath.clone()));
-    }
-
-    if per_file.is_empty() && !ctx.opts.only.is_empty() {
-        warn!(only = %ctx.opts.only.join(", "), "no items matched --only filter");
-    }
+    let per_file = pipe.wanted_by_file();
 
-    let fn_rows_refs: Vec<&Row> = pipe.fn_rows.iter().collect();
+    let base_known = pipe.known_refs();
     let runner = crate::runner::ProcRunner;
 
-    let mut all_results: Vec<LlmDocResult> = Vec::new();
+    // post-processing applied to every accepted doc: intra-doc links, house style, then the line width; with
+    // `--merge` (`merge_into` is the hand-written doc) only to the generated parts
+    let links = ctx
+        .opts
+        .intra_links
+        .then(|| SymbolIndex::from_rows(&pipe.rows));
+    let finish = |doc: String, item: &Row, target_line: &str, merge_into: Option<&str>| -> String {
+        let polish = |doc: &str| {
+            let doc = match &links {
+                Some(index) => index.link_mentions(doc, item),
+                None => doc.to_string(),
+            };
+            ctx.wrapped(ctx.styled(doc), target_line)
+        };
+        match merge_into {
+            Some(existing) => merge_docs_with(existing, &doc, polish),
+            None => polish(&doc),
+        }
+    };
+
     let mut processed = 0usize;
 
     'files: for (file, items) in per_file.iter() {
         let _file_span = info_span!("file", file = %file).entered();
         debug!(items = items.len(), "begin file");
-
+        // only read for the indentation `--wrap` accounts for
+        let source = match ctx.opts.wrap {
+            Some(_) => std::fs::read_to_string(file).unwrap_or_default(),
+            None => String::new(),
+        };
+        let item_line = |item: &Row| -> &str {
+            item.span
+                .start_line
+                .and_then(|l| source.lines().nth((l as usize).saturating_sub(1)))
+                .unwrap_or("")
+        };
         for item in items {
             let _sym_span = info_span!(
                 "symbol",
@@ -210,7 +623,17 @@ pub async fn run_generation<'a>(ctx: &'a Ctx, rows: Vec<Row>) -> Result<Vec<LlmD
             processed += 1;
 
             let had_existing_doc = item.had_doc();
-            if had_existing_doc && !ctx.opts.overwrite {
+            let merging = had_existing_doc && ctx.opts.merge;
+            // `/** */` and `#[doc = ...]` docs are merged like `///` ones
+            let existing = to_line_comments(&row_doc(item));
+            let merged = |doc: String| {
+                if merging {
+                    merge_docs(&existing, &doc)
+                } else {
+                    doc
+                }
+            };
+            if had_existing_doc && !ctx.opts.overwrite && !ctx.opts.merge {
                 if item.kind != "struct" {
                     let elapsed_ms = t_symbol.elapsed().as_millis();
                     info!(
@@ -226,48 +649,118 @@ pub async fn run_generation<'a>(ctx: &'a Ctx, rows: Vec<Row>) -> Result<Vec<LlmD
                 "fn" => {
                     info!("generating docs for function");
 
-                    let mut referenced_symbols = collect_symbol_refs(
-                        item.body_text.as_deref().unwrap_or(""),
-                        &pipe.all_symbols,
-                        re_word(),
-                    );
-
-                    let (start_b, end_b) = item.span_bytes();
-
-                    if !ctx.opts.no_paths {
-                        let qpaths = qualified_paths_in_span(&runner, &item.file, start_b, end_b)
-                            .unwrap_or_default();
-                        referenced_symbols.extend(qpaths.into_iter());
-                    }
+                    let FnContext {
+                        referenced_symbols,
+                        calls_in_span,
+                        hazards,
+                    } = pipe.fn_context(&runner, item);
 
-                    let calls_in_span = if ctx.opts.no_calls {
-                        vec![]
+                    let question = render_fn_prompt(
+                        &ctx.prompts.fn_prompt,
+                        item,
+                        &referenced_symbols,
+                        &calls_in_span,
+                        &hazards,
+                    );
+                    let question = if merging {
+                        build_merge_request(&question, &section_names(&existing))
                     } else {
-                        calls_in_function_span(&runner, &item.file, start_b, end_b)
-                            .unwrap_or_default()
+                        question
                     };
-
-                    let question =
-                        build_markdown_question(item, &referenced_symbols, &calls_in_span);
                     debug!(question_len = question.len(), "sending LLM request (fn)");
 
-                    let t_llm = Instant::now();
-                    let answer = api::ask(&ctx.cfg, question, &ctx.tpl_fn, None, None)
-                        .await
-                        .map_err(|e| {
-                            error!(error = %e, fqpath = %item.fqpath, "LLM ask() failed");
-                            Error::External {
-                                context: "LLM ask() failed",
-                                message: format!("{}: {}", item.fqpath, e),
-                            }
-                        })?;
-                    let llm_ms = t_llm.elapsed().as_millis();
+                    let (answer, llm_ms) = ask(
+                        ctx,
+                        question.clone(),
+                        &ctx.opts.fn_template,
+                        &ctx.tpl_fn,
+                        &item.fqpath,
+                    )
+                    .await?;
 
                     debug!(
                         answer_len = answer.len(),
                         llm_ms, "received LLM response (fn)"
                     );
-                    let llm_doc_block = sanitize_llm_doc(&answer);
+                    let mut llm_doc_block = sanitize_llm_doc(&answer);
+
+                    let mut known = base_known.clone();
+                    known.extend(code_identifiers(
+                        &item.signature,
+                        item.body_text.as_deref().unwrap_or(""),
+                    ));
+                    known.extend(referenced_symbols.iter().cloned());
+                    known.extend(calls_in_span.iter().map(|c| c.callee.clone()));
+
+                    let mut unknown = unknown_refs(&llm_doc_block, &known);
+                    if ctx.opts.strict_refs && !unknown.is_empty() {
+                        warn!(unknown = %unknown.join(", "), "unknown references in draft; re-prompting");
+                        let retry = build_unknown_refs_followup(&question, &unknown);
+                        let (answer, _) =
+                            ask(ctx, retry, &ctx.opts.fn_template, &ctx.tpl_fn, &item.fqpath)
+                                .await?;
+                        llm_doc_block = sanitize_llm_doc(&answer);
+                        unknown = unknown_refs(&llm_doc_block, &known);
+                        if !unknown.is_empty() {
+                            warn!(
+                                unknown = %unknown.join(", "),
+                                "rejecting doc: unknown references remain after re-prompt"
+                            );
+                            continue;
+                        }
+                    } else if !unknown.is_empty() {
+                        warn!(unknown = %unknown.join(", "), "generated doc references unknown identifiers");
+                    }
+
+                    llm_doc_block = merged(llm_doc_block);
+                    let required = required_sections(&item.signature, &hazards);
+                    let mut missing = missing_sections(&llm_doc_block, &required);
+                    if ctx.opts.strict_sections && !missing.is_empty() {
+                        warn!(missing = ?missing, "required sections missing in draft; re-prompting");
+                        let retry = build_missing_sections_followup(&question, &missing);
+                        let (answer, _) =
+                            ask(ctx, retry, &ctx.opts.fn_template, &ctx.tpl_fn, &item.fqpath)
+                                .await?;
+                        let fresh = sanitize_llm_doc(&answer);
+                        unknown = unknown_refs(&fresh, &known);
+                        if ctx.opts.strict_refs && !unknown.is_empty() {
+                            warn!(
+                                unknown = %unknown.join(", "),
+                                "rejecting doc: re-prompted doc references unknown identifiers"
+                            );
+                            continue;
+                        } else if !unknown.is_empty() {
+                            warn!(unknown = %unknown.join(", "), "re-prompted doc references unknown identifiers");
+                        }
+                        llm_doc_block = merged(fresh);
+                        missing = missing_sections(&llm_doc_block, &required);
+                        if !missing.is_empty() {
+                            warn!(
+                                missing = ?missing,
+                                "rejecting doc: required sections still missing after re-prompt"
+                            );
+                            continue;
+                        }
+                    } else if !missing.is_empty() {
+                        warn!(missing = ?missing, "generated doc lacks required sections");
+                    }
+
+                    let (llm_doc_block, critique) = critique_draft(
+                        ctx,
+                        item,
+                        item.body_text.as_deref().unwrap_or(""),
+                        llm_doc_block,
+                        &known,
+                    )
+                    .await?;
+                    let llm_doc_block = merged(llm_doc_block);
+                    if critique.as_deref() == Some("revised") {
+                        unknown = unknown_refs(&llm_doc_block, &known);
+                        missing = missing_sections(&llm_doc_block, &required);
+                    }
+                    let mut warnings = ref_warnings(&unknown);
+                    warnings.extend(section_warnings(&missing));
+                    warnings.extend(critique_warnings(critique.as_deref()));
                     info!(
                         doc_lines = llm_doc_block.lines().count(),
                         elapsed_ms = t_symbol.elapsed().as_millis(),
@@ -284,88 +777,113 @@ pub async fn run_generation<'a>(ctx: &'a Ctx, rows: Vec<Row>) -> Result<Vec<LlmD
                         signature: item.signature.clone(),
                         callers: item.callers.clone().unwrap_or_default(),
                         referenced_symbols,
-                        llm_doc: llm_doc_block,
+                        llm_doc: finish(
+                            llm_doc_block,
+                            item,
+                            item_line(item),
+                            merging.then_some(existing.as_str()),
+                        ),
                         had_existing_doc,
+                        existing_doc: had_existing_doc.then(|| existing.clone()),
+                        warnings,
+                        critique,
+                        file_hash: item.file_hash.clone(),
                     });
                 }
 
                 "struct" => {
                     info!("generating docs for struct and its fields");
 
-                    // load file + find struct body
-                    let file_src = std::fs::read_to_string(&item.file).map_err(|e| Error::Io {
-                        path: Some(std::path::PathBuf::from(&item.file)),
-                        source: e,
-                    })?;
-
-                    let approx_line0 = item.span.start_line.unwrap_or(1).saturating_sub(1) as usize;
-                    let struct_sig0 = match crate::regexes::find_sig_line_near(
-                        &file_src,
-                        approx_line0,
-                        crate::regexes::re_struct(),
-                    ) {
-                        Some(l) => l,
-                        None => {
-                            warn!("could not locate struct sig");
-                            continue;
-                        }
+                    // load file + find struct body + references
+                    let Some(StructContext {
+                        file_src,
+                        body_lo,
+                        body_hi,
+                        body_text,
+                        refs,
+                    }) = pipe.struct_context(item)?
+                    else {
+                        continue;
                     };
-                    let (body_lo, body_hi) =
-                        match crate::util::find_struct_body_block(&file_src, struct_sig0) {
-                            Some(p) => p,
-                            None => {
-                                warn!("could not locate struct body");
-                                continue;
-                            }
-                        };
-                    let body_text = crate::util::extract_lines(&file_src, body_lo, body_hi);
-
-                    // references
-                    let refs = referencing_functions(&item.name, &item.fqpath, &fn_rows_refs);
 
                     // ask / parse
-                    let question = build_struct_request_with_refs(item, &body_text, &refs);
+                    let question =
+                        render_struct_prompt(&ctx.prompts.struct_prompt, item, &body_text, &refs);
+                    let question = if merging {
+                        build_merge_request(&question, &section_names(&existing))
+                    } else {
+                        question
+                    };
                     debug!(
                         question_len = question.len(),
                         refs = refs.len(),
                         "sending LLM request (struct)"
                     );
 
-                    let t_llm = Instant::now();
-                    let raw = api::ask(&ctx.cfg, question, &ctx.tpl_struct, None, None)
-                        .await
-                        .map_err(|e| {
-                            error!(error = %e, fqpath = %item.fqpath, "LLM ask() failed");
-                            Error::External {
-                                context: "LLM ask() failed",
-                                message: format!("{}: {}", item.fqpath, e),
-                            }
-                        })?;
-                    let llm_ms = t_llm.elapsed().as_millis();
+                    let (raw, llm_ms) = ask(
+                        ctx,
+                        question.clone(),
+                        &ctx.opts.struct_template,
+                        &ctx.tpl_struct,
+                        &item.fqpath,
+                    )
+                    .await?;
 
                     debug!(
                         answer_len = raw.len(),
                         llm_ms, "received LLM response (struct)"
                     );
 
-                    let parsed: Result<StructDocResponse> =
-                        serde_json::from_str(&raw).map_err(|e| Error::Json {
-                            context: "struct JSON parse",
-                            source: e,
-                        });
-
-                    let (struct_doc, field_docs) = match parsed {
-                        Ok(v) => {
-                            info!(fields = v.fields.len(), "parsed struct JSON");
-                            (v.struct_doc, v.fields)
-                        }
-                        Err(err) => {
-                            warn!(error = %err, "struct JSON parse failed; using raw payload");
-                            (raw, vec![])
+                    let mut known = base_known.clone();
+                    known.extend(code_identifiers(&item.signature, &body_text));
+                    known.extend(refs.iter().cloned());
+                    let unknown_in = |struct_doc: &str, field_docs: &[FieldDocOut]| {
+                        let mut all = unknown_refs(&sanitize_llm_doc(struct_doc), &known);
+                        for fd in field_docs {
+                            all.extend(unknown_refs(&sanitize_llm_doc(&fd.doc), &known));
                         }
+                        all.sort();
+                        all.dedup();
+                        all
                     };
 
-                    let struct_llm_doc = sanitize_llm_doc(&struct_doc);
+                    let (mut struct_doc, mut field_docs) = parse_struct_answer(raw);
+                    let unknown = unknown_in(&struct_doc, &field_docs);
+                    if ctx.opts.strict_refs && !unknown.is_empty() {
+                        warn!(unknown = %unknown.join(", "), "unknown references in draft; re-prompting");
+                        let retry = build_unknown_refs_followup(&question, &unknown);
+                        let (raw, _) = ask(
+                            ctx,
+                            retry,
+                            &ctx.opts.struct_template,
+                            &ctx.tpl_struct,
+                            &item.fqpath,
+                        )
+                        .await?;
+                        (struct_doc, field_docs) = parse_struct_answer(raw);
+                        let unknown = unknown_in(&struct_doc, &field_docs);
+                        if !unknown.is_empty() {
+                            warn!(
+                                unknown = %unknown.join(", "),
+                                "rejecting doc: unknown references remain after re-prompt"
+                            );
+                            continue;
+                        }
+                    }
+
+                    let (struct_llm_doc, critique) = critique_draft(
+                        ctx,
+                        item,
+                        &body_text,
+                        sanitize_llm_doc(&struct_doc),
+                        &known,
+                    )
+                    .await?;
+                    let struct_unknown = unknown_refs(&struct_llm_doc, &known);
+                    let struct_llm_doc = merged(struct_llm_doc);
+                    if !struct_unknown.is_empty() {
+                        warn!(unknown = %struct_unknown.join(", "), "generated doc references unknown identifiers");
+                    }
 
                     // map fields
                     let fields_in_file = crate::util::extract_struct_fields_in_file(
@@ -388,15 +906,29 @@ pub async fn run_generation<'a>(ctx: &'a Ctx, rows: Vec<Row>) -> Result<Vec<LlmD
                         signature: item.signature.clone(),
                         callers: item.callers.clone().unwrap_or_default(),
                         referenced_symbols: vec![],
-                        llm_doc: struct_llm_doc,
+                        llm_doc: finish(
+                            struct_llm_doc,
+                            item,
+                            item_line(item),
+                            merging.then_some(existing.as_str()),
+                        ),
                         had_existing_doc,
+                        existing_doc: had_existing_doc.then(|| existing.clone()),
+                        warnings: ref_warnings(&struct_unknown)
+                            .into_iter()
+                            .chain(critique_warnings(critique.as_deref()))
+                            .collect(),
+                        critique,
+                        file_hash: item.file_hash.clone(),
                     });
 
                     for fd in field_docs {
                         if let Some((insert0, field_line_text)) = field_index.get(&fd.name).cloned()
                         {
                             let doc_block = sanitize_llm_doc(&fd.doc);
+                            let field_unknown = unknown_refs(&doc_block, &known);
                             debug!(field = %fd.name, insert_line = insert0 + 1, "prepared field doc");
+                            let llm_doc = finish(doc_block, item, &field_line_text, None);
                             all_results.push(LlmDocResult {
                                 kind: "field".into(),
                                 fqpath: format!("{}::{}", item.fqpath, fd.name),
@@ -406,8 +938,12 @@ pub async fn run_generation<'a>(ctx: &'a Ctx, rows: Vec<Row>) -> Result<Vec<LlmD
                                 signature: field_line_text,
                                 callers: vec![],
                                 referenced_symbols: vec![],
-                                llm_doc: doc_block,
+                                llm_doc,
                                 had_existing_doc: false,
+                                existing_doc: None,
+                                warnings: ref_warnings(&field_unknown),
+                                critique: None,
+                                file_hash: item.file_hash.clone(),
                             });
                         } else {
                             warn!(field = %fd.name, "field not found in struct body; skipping doc");
@@ -436,5 +972,623 @@ pub async fn run_generation<'a>(ctx: &'a Ctx, rows: Vec<Row>) -> Result<Vec<LlmD
     }
 
     info!(generated = all_results.len(), "generation finished");
-    Ok(all_results)
+    Ok(())
+}
+
+/// Writes the exact prompts a generation run would send, one file per item, without contacting the model.
+///
+/// Items are selected and ordered exactly like [`run_generation`] (`--only`, `--limit`, and skipping
+/// functions that already have docs unless `--overwrite`), and enriched with the same ast-grep context.
+///
+/// # Parameters
+/// - `opts`: The generation options.
+/// - `prompts`: The prompt templates to render.
+/// - `rows`: The harvested rows.
+/// - `dir`: Output directory; each prompt is written to `<dir>/<fqpath>.md` (see `util::fqpath_to_filename`).
+///
+/// # Returns
+/// The number of prompt files written.
+///
+/// # Errors
+/// - `Error::Io` if a struct's source file cannot be read, or the directory or a prompt file cannot be written.
+#[instrument(level = "info", skip(opts, prompts, rows))]
+pub fn emit_prompts(
+    opts: &GenerateOpts,
+    prompts: &Prompts,
+    rows: Vec<Row>,
+    dir: &Path,
+) -> Result<usize> {
+    let pipe = Pipeline::from_harvest(opts, rows);
+    let runner = crate::runner::ProcRunner;
+
+    std::fs::create_dir_all(dir).map_err(|e| Error::Io {
+        path: Some(dir.to_path_buf()),
+        source: e,
+    })?;
+
+    let mut written = 0usize;
+    let mut total_chars = 0usize;
+    let mut processed = 0usize;
+
+    'files: for items in pipe.wanted_by_file().values() {
+        for item in items {
+            if let Some(limit) = opts.limit {
+                if processed >= limit {
+                    info!(limit, "limit reached, stopping prompt export");
+                    break 'files;
+                }
+            }
+            processed += 1;
+
+            if item.had_doc() && !opts.overwrite && !opts.merge && item.kind != "struct" {
+                debug!(symbol = %item.fqpath, "skipping: existing rustdoc present");
+                continue;
+            }
+
+            let question = match item.kind.as_str() {
+                "fn" => {
+                    let cx = pipe.fn_context(&runner, item);
+                    render_fn_prompt(
+                        &prompts.fn_prompt,
+                        item,
+                        &cx.referenced_symbols,
+                        &cx.calls_in_span,
+                        &cx.hazards,
+                    )
+                }
+                "struct" => match pipe.struct_context(item)? {
+                    Some(cx) => {
+                        render_struct_prompt(&prompts.struct_prompt, item, &cx.body_text, &cx.refs)
+                    }
+                    None => continue,
+                },
+                _ => continue,
+            };
+            let question = if item.had_doc() && opts.merge {
+                build_merge_request(&question, &section_names(&to_line_comments(&row_doc(item))))
+            } else {
+                question
+            };
+
+            let path = dir.join(format!("{}.md", fqpath_to_filename(&item.fqpath)));
+            std::fs::write(&path, &question).map_err(|e| Error::Io {
+                path: Some(path.clone()),
+                source: e,
+            })?;
+            debug!(symbol = %item.fqpath, file = %path.display(), "wrote prompt");
+            total_chars += question.chars().count();
+            written += 1;
+        }
+    }
+
+    info!(
+        prompts = written,
+        total_chars,
+        approx_tokens = total_chars / 4,
+        dir = %dir.display(),
+        "prompt export finished"
+    );
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{Cli, Command};
+    use crate::harvest::stamp_file_hashes;
+    use crate::llm::FixtureClient;
+    use crate::model::Span;
+    use crate::util::stable_hash;
+
+    use clap::Parser;
+
+    // ---------- helpers ----------
+
+    const SRC: &str = "pub fn add_one(x: i32) -> i32 {\n    x + 1\n}\n\npub struct Counter {\n    pub count: usize,\n}\n";
+
+    fn mk_opts(extra: &[&str]) -> GenerateOpts {
+        let mut args = vec![
+            "awful_rustdocs",
+            "run",
+            "--no-calls",
+            "--no-paths",
+            "--no-hazards",
+        ];
+        args.extend_from_slice(extra);
+        match Cli::try_parse_from(args).expect("valid args").cmd {
+            Command::Run(opts) => *opts,
+            other => panic!("expected run command, got {other:?}"),
+        }
+    }
+
+    fn mk_template() -> ChatTemplate {
+        ChatTemplate {
+            system_prompt: String::new(),
+            messages: vec![],
+            response_format: None,
+            pre_user_message_content: None,
+            post_user_message_content: None,
+        }
+    }
+
+    fn mk_ctx(fixtures: &Path, critique: bool, extra: &[&str]) -> Ctx {
+        Ctx {
+            client: Box::new(FixtureClient {
+                dir: fixtures.to_path_buf(),
+            }),
+            tpl_fn: mk_template(),
+            tpl_struct: mk_template(),
+            tpl_critique: critique.then(mk_template),
+            prompts: Prompts::default(),
+            style: None,
+            recorder: None,
+            opts: mk_opts(extra),
+        }
+    }
+
+    fn mk_row(
+        kind: &str,
+        name: &str,
+        file: &Path,
+        lines: (u32, u32),
+        sig: &str,
+        body: &str,
+    ) -> Row {
+        Row {
+            kind: kind.into(),
+            name: name.into(),
+            crate_name: Some("demo".into()),
+            module_path: Some(vec![]),
+            fqpath: format!("crate::{name}"),
+            visibility: "pub".into(),
+            file: file.to_string_lossy().into_owned(),
+            span: Span {
+                start_line: Some(lines.0),
+                end_line: Some(lines.1),
+                start_byte: None,
+                end_byte: None,
+            },
+            signature: sig.into(),
+            has_body: true,
+            doc: None,
+            body_text: Some(body.into()),
+            callers: None,
+            file_hash: None,
+        }
+    }
+
+    /// Writes `SRC` plus the fn/struct fixtures into fresh temp dirs and returns the harvested rows.
+    fn setup(fn_answer: &str) -> (tempfile::TempDir, tempfile::TempDir, Vec<Row>) {
+        let src_dir = tempfile::tempdir().expect("tempdir");
+        let fixtures = tempfile::tempdir().expect("tempdir");
+        let file = src_dir.path().join("lib.rs");
+        std::fs::write(&file, SRC).unwrap();
+
+        std::fs::write(
+            fixtures.path().join("crate.add_one.rustdoc_fn.txt"),
+            fn_answer,
+        )
+        .unwrap();
+        std::fs::write(
+            fixtures.path().join("crate.Counter.rustdoc_struct.txt"),
+            r#"{"struct_doc": "/// A simple counter.", "fields": [{"name": "count", "doc": "/// Current count."}]}"#,
+        )
+        .unwrap();
+
+        let mut rows = vec![
+            mk_row(
+                "fn",
+                "add_one",
+                &file,
+                (1, 3),
+                "pub fn add_one(x: i32) -> i32",
+                "{\n    x + 1\n}",
+            ),
+            mk_row(
+                "struct",
+                "Counter",
+                &file,
+                (5, 7),
+                "pub struct Counter",
+                "{\n    pub count: usize,\n}",
+            ),
+        ];
+        stamp_file_hashes(&mut rows);
+        (src_dir, fixtures, rows)
+    }
+
+    // ---------- run_generation (offline, fixture backend) ----------
+
+    #[tokio::test]
+    async fn test_run_generation_end_to_end_with_fixtures() {
+        let (_src, fixtures, rows) =
+            setup("ANSWER: /// Adds one to `x`.\n///\n/// # Returns\n/// `x + 1`.");
+        let ctx = mk_ctx(fixtures.path(), false, &[]);
+        // edited between harvest and generation: the results must still carry the harvested file's hash
+        std::fs::write(&rows[0].file, format!("{SRC}// edited\n")).unwrap();
+
+        let results = run_generation(&ctx, rows)
+            .await
+            .expect("generation succeeds");
+        let summary: Vec<(&str, &str)> = results
+            .iter()
+            .map(|r| (r.kind.as_str(), r.fqpath.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("fn", "crate::add_one"),
+                ("struct", "crate::Counter"),
+                ("field", "crate::Counter::count"),
+            ]
+        );
+
+        assert!(
+            results[0].llm_doc.starts_with("/// Adds one to `x`."),
+            "FULL DOC:\n{}",
+            results[0].llm_doc
+        );
+        assert_eq!(results[1].llm_doc, "/// A simple counter.");
+        assert_eq!(results[2].llm_doc, "/// Current count.");
+        assert_eq!(
+            results[2].start_line,
+            Some(6),
+            "field doc goes above line 6"
+        );
+        assert!(
+            results
+                .iter()
+                .all(|r| r.warnings.is_empty() && r.critique.is_none()),
+            "Unexpected warnings/critique: {results:#?}"
+        );
+        assert!(
+            results
+                .iter()
+                .all(|r| r.file_hash.as_deref() == Some(stable_hash(SRC).as_str())),
+            "every result records the hash of the file as harvested"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_records_unknown_reference_warnings() {
+        let (_src, fixtures, rows) = setup("/// Adds one to `x`, like `Widget::bump`.");
+        let ctx = mk_ctx(fixtures.path(), false, &["--only", "add_one"]);
+
+        let results = run_generation(&ctx, rows)
+            .await
+            .expect("generation succeeds");
+        assert_eq!(results.len(), 1, "only add_one should be generated");
+        assert_eq!(
+            results[0].warnings,
+            vec!["unknown reference `Widget::bump`"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_strict_refs_rejects_persistent_hallucinations() {
+        let (_src, fixtures, rows) = setup("/// Adds one to `x`, like `Widget::bump`.");
+        let ctx = mk_ctx(
+            fixtures.path(),
+            false,
+            &["--only", "add_one", "--strict-refs"],
+        );
+
+        let results = run_generation(&ctx, rows)
+            .await
+            .expect("generation succeeds");
+        assert!(
+            results.is_empty(),
+            "Expected item to be rejected: {results:#?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_reflows_docs_to_the_width_they_are_written_at() {
+        let (_src, fixtures, rows) = setup(
+            "/// Adds one to `x`, without checking for overflow, which wraps in release builds.",
+        );
+        let ctx = mk_ctx(fixtures.path(), false, &["--wrap", "40"]);
+
+        let results = run_generation(&ctx, rows)
+            .await
+            .expect("generation succeeds");
+        assert_eq!(
+            results[0].llm_doc,
+            "/// Adds one to `x`, without checking\n/// for overflow, which wraps in release\n/// builds.",
+            "docs.json holds the doc as it will be written"
+        );
+        assert_eq!(
+            results[2].llm_doc, "/// Current count.",
+            "{:#?}",
+            results[2]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_critique_records_verdicts() {
+        let (_src, fixtures, rows) = setup("/// Adds one to `x`.");
+        std::fs::write(
+            fixtures.path().join("crate.add_one.rustdoc_critique.txt"),
+            "APPROVED",
+        )
+        .unwrap();
+        std::fs::write(
+            fixtures.path().join("crate.Counter.rustdoc_critique.txt"),
+            "/// A counter that only goes up.",
+        )
+        .unwrap();
+        let ctx = mk_ctx(fixtures.path(), true, &["--critique"]);
+
+        let results = run_generation(&ctx, rows)
+            .await
+            .expect("generation succeeds");
+        assert_eq!(results[0].critique.as_deref(), Some("approved"));
+        assert_eq!(results[0].llm_doc, "/// Adds one to `x`.");
+        assert_eq!(results[1].critique.as_deref(), Some("revised"));
+        assert_eq!(results[1].llm_doc, "/// A counter that only goes up.");
+        assert_eq!(results[2].critique, None, "field docs are not reviewed");
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_keeps_draft_when_critique_reply_is_empty() {
+        let (_src, fixtures, rows) = setup("/// Adds one to `x`.");
+        std::fs::write(
+            fixtures.path().join("crate.add_one.rustdoc_critique.txt"),
+            "  \n",
+        )
+        .unwrap();
+        let ctx = mk_ctx(fixtures.path(), true, &["--critique", "--only", "add_one"]);
+
+        let results = run_generation(&ctx, rows)
+            .await
+            .expect("generation succeeds");
+        assert_eq!(
+            results[0].llm_doc, "/// Adds one to `x`.",
+            "the draft is kept"
+        );
+        assert_eq!(results[0].critique.as_deref(), Some("empty"));
+        assert_eq!(
+            results[0].warnings,
+            vec!["critique returned no verdict: the draft was kept unreviewed"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_record_then_replay_reproduces_results() {
+        let (_src, fixtures, rows) = setup("ANSWER: /// Adds one to `x`.");
+        let rec_dir = tempfile::tempdir().expect("tempdir");
+
+        let mut ctx = mk_ctx(fixtures.path(), false, &[]);
+        ctx.recorder = Some(Recorder::new(rec_dir.path().to_path_buf()));
+        let recorded = run_generation(&ctx, rows.clone())
+            .await
+            .expect("record run");
+
+        let transcript =
+            std::fs::read_to_string(rec_dir.path().join("crate.add_one.json")).expect("transcript");
+        assert!(
+            transcript.contains("\"template\": \"rustdoc_fn\"")
+                && transcript.contains("ANSWER: /// Adds one to `x`.")
+                && transcript.contains("\"llm_doc\": \"/// Adds one to `x`.\""),
+            "Transcript should hold template, raw response and sanitized doc:\n{transcript}"
+        );
+
+        // Replay with an empty fixture dir: every answer must come from the recording.
+        let ctx = Ctx {
+            client: Box::new(
+                crate::transcript::ReplayClient::from_dir(rec_dir.path()).expect("load"),
+            ),
+            ..mk_ctx(tempfile::tempdir().unwrap().path(), false, &[])
+        };
+        let replayed = run_generation(&ctx, rows).await.expect("replay run");
+
+        let docs = |rs: &[LlmDocResult]| -> Vec<(String, String)> {
+            rs.iter()
+                .map(|r| (r.fqpath.clone(), r.llm_doc.clone()))
+                .collect()
+        };
+        assert_eq!(docs(&replayed), docs(&recorded));
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_records_transcripts_when_generation_fails() {
+        let (_src, fixtures, rows) = setup("ANSWER: /// Adds one to `x`.");
+        std::fs::remove_file(fixtures.path().join("crate.Counter.rustdoc_struct.txt")).unwrap();
+        let rec_dir = tempfile::tempdir().expect("tempdir");
+
+        let mut ctx = mk_ctx(fixtures.path(), false, &[]);
+        ctx.recorder = Some(Recorder::new(rec_dir.path().to_path_buf()));
+        let err = run_generation(&ctx, rows).await;
+        assert!(err.is_err(), "the struct has no fixture");
+        assert!(
+            rec_dir.path().join("crate.add_one.json").is_file(),
+            "the fn answered before the failure is still recorded"
+        );
+    }
+
+    /// Answers every request with the next of a fixed list of answers.
+    struct Scripted(std::cell::RefCell<Vec<&'static str>>);
+
+    #[async_trait::async_trait(?Send)]
+    impl LlmClient for Scripted {
+        async fn ask(&self, _req: &LlmRequest<'_>) -> Result<String> {
+            Ok(self.0.borrow_mut().remove(0).to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_checks_refs_of_the_section_retry() {
+        let (_src, fixtures, mut rows) = setup("");
+        rows[0].signature = "pub unsafe fn add_one(x: i32) -> i32".into();
+        let answers = || {
+            Box::new(Scripted(std::cell::RefCell::new(vec![
+                "/// Adds one to `x`.",
+                "/// Adds one via `Frobnicator`.\n///\n/// # Safety\n/// `x` must not be `i32::MAX`.",
+            ])))
+        };
+
+        let flags = ["--only", "add_one", "--strict-sections"];
+        let ctx = Ctx {
+            client: answers(),
+            ..mk_ctx(fixtures.path(), false, &flags)
+        };
+        let results = run_generation(&ctx, rows.clone())
+            .await
+            .expect("generation succeeds");
+        assert_eq!(
+            results[0].warnings,
+            vec!["unknown reference `Frobnicator`"],
+            "the retried doc is checked like the first draft"
+        );
+
+        let ctx = Ctx {
+            client: answers(),
+            ..mk_ctx(
+                fixtures.path(),
+                false,
+                &[&flags[..], &["--strict-refs"]].concat(),
+            )
+        };
+        let results = run_generation(&ctx, rows)
+            .await
+            .expect("generation succeeds");
+        assert!(
+            results.is_empty(),
+            "with --strict-refs the retried doc is rejected: {results:#?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_requires_safety_section_for_unsafe_fn() {
+        let (_src, fixtures, mut rows) = setup("/// Adds one to `x`.");
+        rows[0].signature = "pub unsafe fn add_one(x: i32) -> i32".into();
+
+        let ctx = mk_ctx(fixtures.path(), false, &["--only", "add_one"]);
+        let results = run_generation(&ctx, rows.clone())
+            .await
+            .expect("generation succeeds");
+        assert_eq!(
+            results[0].warnings,
+            vec!["missing `# Safety` section: the function is `unsafe`"]
+        );
+
+        let ctx = mk_ctx(
+            fixtures.path(),
+            false,
+            &["--only", "add_one", "--strict-sections"],
+        );
+        let results = run_generation(&ctx, rows.clone())
+            .await
+            .expect("generation succeeds");
+        assert!(
+            results.is_empty(),
+            "Expected item to be rejected: {results:#?}"
+        );
+
+        std::fs::write(
+            fixtures.path().join("crate.add_one.rustdoc_fn.txt"),
+            "/// Adds one to `x`.\n///\n/// Safety:\n/// - `x` must not be `i32::MAX`.",
+        )
+        .unwrap();
+        let results = run_generation(&ctx, rows)
+            .await
+            .expect("generation succeeds");
+        assert!(results[0].warnings.is_empty(), "{results:#?}");
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_links_symbol_mentions() {
+        let (_src, fixtures, rows) = setup("/// Adds one, e.g. to a `Counter`.");
+        let ctx = mk_ctx(
+            fixtures.path(),
+            false,
+            &["--only", "add_one", "--intra-links"],
+        );
+
+        let results = run_generation(&ctx, rows)
+            .await
+            .expect("generation succeeds");
+        assert_eq!(results[0].llm_doc, "/// Adds one, e.g. to a [`Counter`].");
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_merge_keeps_hand_written_sections() {
+        let (_src, fixtures, mut rows) = setup(
+            "/// Increments a number.\n///\n/// Returns:\n/// `x + 1`.\n///\n/// Examples:\n/// Generated example.",
+        );
+        rows[0].doc =
+            Some("/// Adds one to `x`.\n///\n/// # Examples\n/// Hand-written example.".into());
+
+        let ctx = mk_ctx(fixtures.path(), false, &["--only", "add_one"]);
+        let results = run_generation(&ctx, rows.clone())
+            .await
+            .expect("generation succeeds");
+        assert!(
+            results.is_empty(),
+            "documented items are skipped by default"
+        );
+
+        let ctx = mk_ctx(fixtures.path(), false, &["--only", "add_one", "--merge"]);
+        let results = run_generation(&ctx, rows)
+            .await
+            .expect("generation succeeds");
+        assert_eq!(
+            results[0].llm_doc,
+            "/// Adds one to `x`.\n///\n/// # Returns\n/// `x + 1`.\n///\n/// # Examples\n/// Hand-written example.",
+            "FULL DOC:\n{}",
+            results[0].llm_doc
+        );
+        assert!(results[0].had_existing_doc);
+        assert_eq!(
+            results[0].existing_doc.as_deref(),
+            Some("/// Adds one to `x`.\n///\n/// # Examples\n/// Hand-written example."),
+            "the doc it replaces is kept for the reports"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_run_generation_applies_house_style() {
+        let (_src, fixtures, rows) = setup(
+            "/// Adds one to `x`.\n///\n/// Returns:\n/// `x + 1`.\n///\n/// Parameters:\n/// - `x`: The input.",
+        );
+        let mut ctx = mk_ctx(fixtures.path(), false, &["--only", "add_one"]);
+        ctx.style = Some(crate::style::StyleProfile::rustdoc_std());
+
+        let results = run_generation(&ctx, rows)
+            .await
+            .expect("generation succeeds");
+        assert_eq!(
+            results[0].llm_doc, "/// Adds one to `x`.\n///\n/// # Returns\n/// `x + 1`.",
+            "FULL DOC:\n{}",
+            results[0].llm_doc
+        );
+    }
+
+    // ---------- emit_prompts ----------
+
+    #[test]
+    fn test_emit_prompts_writes_one_file_per_item_without_llm() {
+        let (_src, _fixtures, rows) = setup("");
+        let out = tempfile::tempdir().expect("tempdir");
+        let opts = mk_opts(&[]);
+
+        let n = emit_prompts(&opts, &Prompts::default(), rows.clone(), out.path())
+            .expect("export succeeds");
+        assert_eq!(n, 2);
+
+        let fn_prompt = std::fs::read_to_string(out.path().join("crate.add_one.md")).unwrap();
+        let expected =
+            crate::prompt::render_fn_prompt(&Prompts::default().fn_prompt, &rows[0], &[], &[], &[]);
+        assert_eq!(
+            fn_prompt, expected,
+            "fn prompt must match the generation prompt"
+        );
+
+        let struct_prompt = std::fs::read_to_string(out.path().join("crate.Counter.md")).unwrap();
+        assert!(
+            struct_prompt.contains("# Rust Struct Documentation Task")
+                && struct_prompt.contains("pub count: usize,"),
+            "FULL PROMPT:\n{struct_prompt}"
+        );
+    }
 }
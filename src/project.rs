@@ -0,0 +1,83 @@
+use crate::error::{Error, Result};
+use crate::style::StyleSetting;
+
+use serde::Deserialize;
+use tracing::debug;
+
+use std::path::Path;
+
+/// File name of the per-project settings, looked up in the directory `awful_rustdocs` is run from.
+pub const PROJECT_FILE: &str = ".awful_rustdocs.yaml";
+
+/// Per-project settings read from [`PROJECT_FILE`].
+///
+/// Every field is optional; command-line flags take precedence over values set here.
+///
+/// ```yaml
+/// style: rustdoc-std
+/// wrap: 100
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// House style applied to generated docs (see [`crate::style::StyleProfile`]).
+    pub style: Option<StyleSetting>,
+    /// Line width generated docs are reflowed to when patching (see [`crate::sanitize::reflow_doc`]).
+    pub wrap: Option<usize>,
+}
+
+impl ProjectConfig {
+    /// Loads `<dir>/.awful_rustdocs.yaml`, or the default settings if the file does not exist.
+    ///
+    /// Errors:
+    /// - `Error::Io` if the file exists but cannot be read.
+    /// - `Error::External` if the file is not valid YAML or contains unknown keys.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(PROJECT_FILE);
+        if !path.is_file() {
+            debug!(file = %path.display(), "no project file; using defaults");
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(&path).map_err(|e| Error::Io {
+            path: Some(path.clone()),
+            source: e,
+        })?;
+        let cfg = serde_yaml::from_str(&text).map_err(|e| Error::External {
+            context: "invalid project file",
+            message: format!("{}: {}", path.display(), e),
+        })?;
+        debug!(file = %path.display(), ?cfg, "loaded project file");
+        Ok(cfg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---------- ProjectConfig::load ----------
+
+    #[test]
+    fn test_load_missing_file_yields_defaults() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let cfg = ProjectConfig::load(dir.path()).unwrap();
+        assert!(cfg.style.is_none());
+    }
+
+    #[test]
+    fn test_load_reads_style_and_rejects_unknown_keys() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::write(dir.path().join(PROJECT_FILE), "style: legacy\nwrap: 100\n").unwrap();
+        let cfg = ProjectConfig::load(dir.path()).unwrap();
+        assert_eq!(cfg.wrap, Some(100));
+        let profile = cfg.style.expect("style set").resolve().unwrap();
+        assert_eq!(profile.name, "legacy");
+
+        std::fs::write(dir.path().join(PROJECT_FILE), "stlye: legacy\n").unwrap();
+        let err = ProjectConfig::load(dir.path()).expect_err("typo must be reported");
+        assert!(
+            matches!(err, Error::External { .. }),
+            "Expected Error::External, got {err:?}"
+        );
+    }
+}
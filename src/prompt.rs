@@ -1,5 +1,12 @@
-use crate::grep::CallSite;
+use crate::defaults::{DEFAULT_FN_PROMPT_MD, DEFAULT_STRUCT_PROMPT_MD};
+use crate::error::{Error, Result};
+use crate::grep::{CallSite, Hazard};
 use crate::model::Row;
+use crate::util::extract_struct_fields_in_file;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::OnceLock;
 
 /// Truncates a string to fit within a specified number of characters and lines, preserving line breaks and adding a truncation indicator if necessary.
 ///
@@ -27,214 +34,510 @@ pub fn truncate_for_context(s: &str, max_chars: usize, max_lines: usize) -> Stri
     out
 }
 
-/// Builds a markdown-formatted question from a function's metadata, including its path, signature, and referenced symbols.
-///
-/// This function constructs a structured markdown representation of a function's context, useful for documentation or introspection.
-/// It includes details such as the fully-qualified path, signature, visibility, referenced symbols, and function calls within its span.
-/// The output is a formatted string suitable for rendering in documentation systems or developer tools.
-///
-/// Parameters:
-/// - `f`: A reference to a `Row` containing function metadata (path, signature, visibility, etc.).
-/// - `referenced_symbols`: A slice of symbol names referenced within the function body.
-/// - `calls_in_span`: A slice of `CallSite` entries representing function calls within the span.
-///
-/// Returns:
-/// - A `String` containing the formatted markdown question.
-///
-/// Notes:
-/// - The function truncates the function body to 400 characters for display, using a context limit of 8000.
-/// - Only the first 50 calls in the span are included to avoid excessive output.
-/// - If no existing documentation is present, it will indicate "_No existing rustdoc found._"
-///
-/// Examples:
-/// ```no_run
-/// let row = Row {
-///     fqpath: "crate::example::function",
-///     signature: "fn hello() -> String",
-///     visibility: "pub",
-///     doc: None,
-///     body_text: Some("return format!("Hello, world!");"),
-///     referenced_symbols: &["format!"],
-///     calls_in_span: &[CallSite { kind: "call", callee: "format!", qual: Some("format!") }],
-/// };
-///
-/// let question = build_markdown_question(&row, &["format!"], &[CallSite { kind: "call", callee: "format!", qual: Some("format!") }]);
+/// Placeholders available in function prompt templates.
+pub const FN_PROMPT_PLACEHOLDERS: &[&str] = &[
+    "fqpath",
+    "name",
+    "signature",
+    "visibility",
+    "existing_doc",
+    "referenced_symbols",
+    "calls",
+    "hazards",
+    "body",
+    "callers",
+];
+
+/// Placeholders available in struct prompt templates.
+pub const STRUCT_PROMPT_PLACEHOLDERS: &[&str] = &[
+    "fqpath",
+    "name",
+    "signature",
+    "visibility",
+    "existing_doc",
+    "body",
+    "callers",
+    "fields",
+];
+
+/// A piece of a parsed prompt template.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Literal text copied verbatim.
+    Text(String),
+    /// `{{name}}`: replaced by the value.
+    Var(String),
+    /// `{{#name}}` (rendered when the value is non-empty) or `{{^name}}` (rendered when it is empty).
+    Open { inverted: bool, name: String },
+    /// `{{/name}}`: closes the section opened with the same name.
+    Close(String),
+}
+
+/// A user-editable prompt body with `{{placeholder}}` substitutions.
 ///
-/// println!("{}", question);
-/// ```
-pub fn build_markdown_question(
-    f: &Row,
-    referenced_symbols: &[String],
-    calls_in_span: &[CallSite],
-) -> String {
-    use std::fmt::Write;
-    let mut s = String::new();
+/// Besides plain `{{name}}` placeholders, a template may contain sections: `{{#name}}…{{/name}}` is only
+/// rendered when the value of `name` is non-empty, `{{^name}}…{{/name}}` only when it is empty. This is how the
+/// defaults print `_No existing rustdoc found._` or skip the calls section entirely.
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    /// Where the template came from (a file path or `built-in <kind>`), used in error messages.
+    pub name: String,
+    /// The parsed template.
+    tokens: Vec<Token>,
+}
 
-    writeln!(s, "# Rust Function Documentation Task").ok();
-    writeln!(s, "You are given context about a single Rust function.").ok();
-    writeln!(s).ok();
-
-    writeln!(s, "## Function Identity").ok();
-    writeln!(s, "- **Fully-qualified path**: `{}`", f.fqpath).ok();
-    writeln!(s, "- **Signature**: `{}`", f.signature).ok();
-    writeln!(s, "- **Visibility**: `{}`", f.visibility).ok();
-
-    writeln!(s, "\n## Existing Documentation").ok();
-    match &f.doc {
-        Some(doc) if !doc.trim().is_empty() => {
-            writeln!(
-                s,
-                "The function already has Rustdoc. Improve and rewrite it if necessary:"
-            )
-            .ok();
-            writeln!(s, "```rust\n{}\n```", doc.trim()).ok();
+impl PromptTemplate {
+    /// Parses and validates a template against the placeholders allowed for its kind.
+    ///
+    /// Parameters:
+    /// - `name`: A label for error messages (usually the file path).
+    /// - `text`: The template source.
+    /// - `allowed`: The placeholder names accepted for this kind (see [`FN_PROMPT_PLACEHOLDERS`] and
+    ///   [`STRUCT_PROMPT_PLACEHOLDERS`]).
+    ///
+    /// Returns:
+    /// - The parsed template.
+    ///
+    /// Errors:
+    /// - `Error::PromptTemplate` listing every unknown placeholder, unterminated `{{`, and unbalanced section.
+    pub fn parse(name: &str, text: &str, allowed: &[&str]) -> Result<Self> {
+        let mut tokens = Vec::new();
+        let mut problems = Vec::new();
+        let mut open: Vec<String> = Vec::new();
+        let mut rest = text;
+
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                tokens.push(Token::Text(rest[..start].to_string()));
+            }
+            let after = &rest[start + 2..];
+            let Some(end) = after.find("}}") else {
+                problems.push("unterminated `{{`".to_string());
+                rest = "";
+                break;
+            };
+            let inner = after[..end].trim();
+            let (token, key) = match inner.chars().next() {
+                Some('#') | Some('^') => {
+                    let key = inner[1..].trim().to_string();
+                    open.push(key.clone());
+                    (
+                        Token::Open {
+                            inverted: inner.starts_with('^'),
+                            name: key.clone(),
+                        },
+                        key,
+                    )
+                }
+                Some('/') => {
+                    let key = inner[1..].trim().to_string();
+                    if open.last() == Some(&key) {
+                        open.pop();
+                    } else {
+                        problems.push(format!("unexpected `{{{{/{key}}}}}`"));
+                    }
+                    (Token::Close(key.clone()), key)
+                }
+                _ => (Token::Var(inner.to_string()), inner.to_string()),
+            };
+            if !allowed.contains(&key.as_str()) {
+                problems.push(format!("unknown placeholder `{{{{{key}}}}}`"));
+            }
+            tokens.push(token);
+            rest = &after[end + 2..];
+        }
+        if !rest.is_empty() {
+            tokens.push(Token::Text(rest.to_string()));
         }
-        _ => {
-            writeln!(s, "_No existing rustdoc found._").ok();
+        for key in open {
+            problems.push(format!("section `{key}` is never closed"));
         }
-    };
-
-    writeln!(s, "\n## Referenced Symbols (body-level)").ok();
-    if referenced_symbols.is_empty() {
-        writeln!(s, "_No symbol references detected._").ok();
-    } else {
-        for sym in referenced_symbols {
-            writeln!(s, "- `{}`", sym).ok();
+        problems.dedup();
+
+        if problems.is_empty() {
+            Ok(Self {
+                name: name.to_string(),
+                tokens,
+            })
+        } else {
+            Err(Error::PromptTemplate {
+                name: name.to_string(),
+                problems,
+            })
         }
     }
 
-    if !calls_in_span.is_empty() {
-        writeln!(s, "\n## Function Calls Inside This Function").ok();
-        for c in calls_in_span.iter().take(50) {
-            match &c.qual {
-                Some(q) => {
-                    writeln!(s, "- **{}** call → `{}` on `{}`", c.kind, c.callee, q).ok();
+    /// Renders the template with the given values; missing values render as empty strings.
+    pub fn render(&self, values: &BTreeMap<&str, String>) -> String {
+        let mut out = String::new();
+        let mut active = true;
+        let mut stack: Vec<bool> = Vec::new();
+        let non_empty = |key: &str| values.get(key).is_some_and(|v| !v.is_empty());
+
+        for token in &self.tokens {
+            match token {
+                Token::Text(t) if active => out.push_str(t),
+                Token::Var(key) if active => {
+                    out.push_str(values.get(key.as_str()).map(String::as_str).unwrap_or(""))
                 }
-                None => {
-                    writeln!(s, "- **{}** call → `{}`", c.kind, c.callee).ok();
+                Token::Open { inverted, name } => {
+                    stack.push(active);
+                    active = active && (non_empty(name) != *inverted);
                 }
-            };
+                Token::Close(_) => active = stack.pop().unwrap_or(true),
+                _ => {}
+            }
         }
+        out
     }
+}
 
-    if let Some(body) = &f.body_text {
-        writeln!(s, "\n## Function Body (Truncated)").ok();
-        let trimmed = truncate_for_context(body, 8000, 400);
-        writeln!(s, "```rust\n{}\n```", trimmed).ok();
+/// The built-in function and struct prompt templates, or the user's replacements.
+#[derive(Debug, Clone)]
+pub struct Prompts {
+    /// Template used for `fn` items.
+    pub fn_prompt: PromptTemplate,
+    /// Template used for `struct` items.
+    pub struct_prompt: PromptTemplate,
+}
+
+impl Default for Prompts {
+    fn default() -> Self {
+        Self {
+            fn_prompt: default_fn_prompt().clone(),
+            struct_prompt: default_struct_prompt().clone(),
+        }
     }
+}
 
-    writeln!(s, "\n---\n## Output Requirements\n\
-        Return **ONLY** a Rustdoc block composed of lines starting with `///`.\n\
-        - No JSON, no backticks, no XML, no surrounding prose.\n\
-        - Include a clear 1–2 sentence summary.\n\
-        - If relevant, add sections titled exactly: `Parameters:`, `Returns:`, `Errors:`, `Notes:`, `Examples:`.\n\
-        - Only include a `Safety:` section if the function is unsafe.
-        - Use concise bullet points; examples should be doc-test friendly (no fenced code).\n\
-        - Every line MUST start with `///` (or be a blank `///`)."
-    ).ok();
+impl Prompts {
+    /// Loads the prompt templates used for a run and validates them.
+    ///
+    /// For each kind the first available source wins: the explicit path (`--fn-prompt` / `--struct-prompt`),
+    /// then `<prompt_dir>/rustdoc_fn.md` / `<prompt_dir>/rustdoc_struct.md`, then the built-in default.
+    ///
+    /// Parameters:
+    /// - `fn_path`: Explicit function template file, if any.
+    /// - `struct_path`: Explicit struct template file, if any.
+    /// - `prompt_dir`: Directory with user templates (normally `<config_root>/prompts`), if known.
+    ///
+    /// Returns:
+    /// - The loaded templates.
+    ///
+    /// Errors:
+    /// - `Error::Io` if an explicit template file cannot be read.
+    /// - `Error::PromptTemplate` if a template uses unknown placeholders or has unbalanced sections.
+    pub fn load(
+        fn_path: Option<&Path>,
+        struct_path: Option<&Path>,
+        prompt_dir: Option<&Path>,
+    ) -> Result<Self> {
+        let load_one = |explicit: Option<&Path>,
+                        file_name: &str,
+                        allowed: &[&str],
+                        default: &PromptTemplate|
+         -> Result<PromptTemplate> {
+            let path = match explicit {
+                Some(p) => p.to_path_buf(),
+                None => match prompt_dir.map(|d| d.join(file_name)) {
+                    Some(p) if p.is_file() => p,
+                    _ => return Ok(default.clone()),
+                },
+            };
+            let text = std::fs::read_to_string(&path).map_err(|e| Error::Io {
+                path: Some(path.clone()),
+                source: e,
+            })?;
+            PromptTemplate::parse(&path.display().to_string(), &text, allowed)
+        };
+
+        Ok(Self {
+            fn_prompt: load_one(
+                fn_path,
+                "rustdoc_fn.md",
+                FN_PROMPT_PLACEHOLDERS,
+                default_fn_prompt(),
+            )?,
+            struct_prompt: load_one(
+                struct_path,
+                "rustdoc_struct.md",
+                STRUCT_PROMPT_PLACEHOLDERS,
+                default_struct_prompt(),
+            )?,
+        })
+    }
+}
 
-    s
+/// Returns the parsed built-in function prompt template.
+fn default_fn_prompt() -> &'static PromptTemplate {
+    static TPL: OnceLock<PromptTemplate> = OnceLock::new();
+    TPL.get_or_init(|| {
+        PromptTemplate::parse("built-in fn", DEFAULT_FN_PROMPT_MD, FN_PROMPT_PLACEHOLDERS).unwrap()
+    })
 }
 
-/// Builds a structured request string for generating Rustdoc for a given struct, including its metadata, existing documentation, body, and referencing functions.
-///
-/// The function constructs a detailed prompt that includes the struct's fully-qualified path, signature, visibility, existing Rustdoc (if any), struct body (verbatim), and up to 100 referencing function paths. It then specifies the expected output format: a JSON object with a `struct_doc` field (a concise 1–2 sentence summary) and a list of `fields`, each containing a `doc` entry for a named field in the struct body.
+/// Returns the parsed built-in struct prompt template.
+fn default_struct_prompt() -> &'static PromptTemplate {
+    static TPL: OnceLock<PromptTemplate> = OnceLock::new();
+    TPL.get_or_init(|| {
+        PromptTemplate::parse(
+            "built-in struct",
+            DEFAULT_STRUCT_PROMPT_MD,
+            STRUCT_PROMPT_PLACEHOLDERS,
+        )
+        .unwrap()
+    })
+}
+
+/// Renders a list of names as markdown bullets with inline code (`` - `name` ``), one per line.
+fn bullet_list<'s>(items: impl IntoIterator<Item = &'s String>) -> String {
+    items
+        .into_iter()
+        .map(|s| format!("- `{}`", s))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns the trimmed existing doc of a row, or an empty string when it has none.
+fn existing_doc(row: &Row) -> String {
+    row.doc.as_deref().map(str::trim).unwrap_or("").to_string()
+}
+
+/// Renders a function prompt from a template.
 ///
 /// Parameters:
-/// - `srow`: A reference to a `Row` containing struct metadata including fully-qualified path, signature, and visibility.
-/// - `body_text`: The raw Rust struct body text as a string slice.
-/// - `referencing_fns`: A slice of strings representing the fully-qualified paths of functions that reference this struct.
+/// - `tpl`: The function prompt template.
+/// - `f`: The function row.
+/// - `referenced_symbols`: Symbols referenced in the body (`{{referenced_symbols}}`).
+/// - `calls_in_span`: Call sites in the function (`{{calls}}`, first 50 only).
+/// - `hazards`: Panicking operations and `unsafe` blocks in the body (`{{hazards}}`).
 ///
 /// Returns:
-/// - A `String` containing the formatted prompt ready to be used in a model or LLM for generating Rustdoc.
-///
-/// Notes:
-/// - The function limits the number of referencing functions to 100 to prevent excessive prompt length.
-/// - If no existing documentation is present, it explicitly notes "_No existing rustdoc found._".
-/// - The output is structured to guide an AI model to produce valid, concise, and accurate Rustdoc comments.
-///
-/// Examples:
-/// ```no_run
-/// use crate::prompt::Row;
-///
-/// let srow = Row {
-///     fqpath: "crate::my_struct".into(),
-///     signature: "pub struct MyStruct { pub field: i32 }".into(),
-///     visibility: "pub".into(),
-///     doc: None,
-///     body_text: "pub struct MyStruct { pub field: i32 }".into(),
-/// };
+/// - The rendered prompt. `{{body}}` is the body truncated to 400 lines / 8000 chars, `{{callers}}` the
+///   harvested callers; list placeholders are markdown bullets and empty when there is nothing to list.
+pub fn render_fn_prompt(
+    tpl: &PromptTemplate,
+    f: &Row,
+    referenced_symbols: &[String],
+    calls_in_span: &[CallSite],
+    hazards: &[Hazard],
+) -> String {
+    let calls = calls_in_span
+        .iter()
+        .take(50)
+        .map(|c| match &c.qual {
+            Some(q) => format!("- **{}** call → `{}` on `{}`", c.kind, c.callee, q),
+            None => format!("- **{}** call → `{}`", c.kind, c.callee),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let values = BTreeMap::from([
+        ("fqpath", f.fqpath.clone()),
+        ("name", f.name.clone()),
+        ("signature", f.signature.clone()),
+        ("visibility", f.visibility.clone()),
+        ("existing_doc", existing_doc(f)),
+        ("referenced_symbols", bullet_list(referenced_symbols)),
+        ("calls", calls),
+        (
+            "hazards",
+            hazards
+                .iter()
+                .map(|h| format!("- {}", h.describe()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ),
+        (
+            "body",
+            f.body_text
+                .as_deref()
+                .map(|b| truncate_for_context(b, 8000, 400))
+                .unwrap_or_default(),
+        ),
+        ("callers", bullet_list(f.callers.iter().flatten())),
+    ]);
+    tpl.render(&values)
+}
+
+/// Renders a struct prompt from a template.
 ///
-/// let referencing_fns = &["crate::util::process", "crate::core::handle"];
-/// let prompt = build_struct_request_with_refs(&srow, "pub struct MyStruct { pub field: i32 }", referencing_fns);
+/// Parameters:
+/// - `tpl`: The struct prompt template.
+/// - `srow`: The struct row.
+/// - `body_text`: The struct body, verbatim (`{{body}}`).
+/// - `referencing_fns`: Functions referencing the struct (`{{callers}}`, first 100 only).
 ///
-/// println!("{}", prompt);
-/// ```
-pub fn build_struct_request_with_refs(
+/// Returns:
+/// - The rendered prompt. `{{fields}}` lists the named fields found in `body_text`.
+pub fn render_struct_prompt(
+    tpl: &PromptTemplate,
     srow: &Row,
     body_text: &str,
     referencing_fns: &[String],
 ) -> String {
+    let last_line0 = body_text.lines().count().saturating_sub(1);
+    let fields: Vec<String> = extract_struct_fields_in_file(body_text, 0, last_line0, &srow.fqpath)
+        .into_iter()
+        .map(|f| f.name)
+        .collect();
+
+    let values = BTreeMap::from([
+        ("fqpath", srow.fqpath.clone()),
+        ("name", srow.name.clone()),
+        ("signature", srow.signature.clone()),
+        ("visibility", srow.visibility.clone()),
+        ("existing_doc", existing_doc(srow)),
+        ("body", body_text.to_string()),
+        ("callers", bullet_list(referencing_fns.iter().take(100))),
+        ("fields", bullet_list(&fields)),
+    ]);
+    tpl.render(&values)
+}
+
+/// Extends a previously sent question with a correction note listing references the model invented.
+///
+/// Used by `--strict-refs` to re-prompt once after the validator found backticked names that do not resolve
+/// to any harvested item, parameter or local binding.
+///
+/// Parameters:
+/// - `question`: The original prompt that produced the rejected draft.
+/// - `unknown`: The unresolved references reported by `validate::unknown_refs`.
+///
+/// Returns:
+/// - The original question followed by a `## Unknown References` section.
+pub fn build_unknown_refs_followup(question: &str, unknown: &[String]) -> String {
     use std::fmt::Write;
-    let mut s = String::new();
+    let mut s = question.to_string();
 
-    writeln!(s, "# Rust Struct Documentation Task").ok();
-    writeln!(s, "You are given the source of a single Rust struct and a list of functions that reference it.").ok();
-
-    writeln!(s, "\n## Struct Identity").ok();
-    writeln!(s, "- **Fully-qualified path**: `{}`", srow.fqpath).ok();
-    writeln!(s, "- **Signature**: `{}`", srow.signature).ok();
-    writeln!(s, "- **Visibility**: `{}`", srow.visibility).ok();
-
-    writeln!(s, "\n## Existing Documentation").ok();
-    match &srow.doc {
-        Some(doc) if !doc.trim().is_empty() => {
-            writeln!(
-                s,
-                "The struct already has Rustdoc. If needed, rewrite it to be concise:"
-            )
-            .ok();
-            writeln!(s, "```rust\n{}\n```", doc.trim()).ok();
-        }
-        _ => {
-            writeln!(s, "_No existing rustdoc found._").ok();
-        }
-    };
+    writeln!(s, "\n## Unknown References").ok();
+    writeln!(
+        s,
+        "A previous answer mentioned identifiers that do not exist in this crate:"
+    )
+    .ok();
+    for r in unknown {
+        writeln!(s, "- `{}`", r).ok();
+    }
+    writeln!(
+        s,
+        "Rewrite the documentation without them. Only reference items listed above or present in the code."
+    )
+    .ok();
 
-    writeln!(s, "\n## Struct Body (verbatim)").ok();
-    writeln!(s, "```rust\n{}\n```", body_text).ok();
+    s
+}
 
-    writeln!(s, "\n## Referencing Functions (FQ paths)").ok();
-    if referencing_fns.is_empty() {
-        writeln!(s, "_No referencing functions detected in the crate._").ok();
-    } else {
-        for f in referencing_fns.iter().take(100) {
-            writeln!(s, "- `{}`", f).ok();
-        }
+/// Extends a previously sent question with a note listing the sections the answer left out.
+///
+/// Used by `--strict-sections` to re-prompt once when a doc lacks a `Panics` or `Safety` section that the
+/// body scan (see `validate::required_sections`) calls for.
+///
+/// Parameters:
+/// - `question`: The original prompt that produced the rejected draft.
+/// - `missing`: `(section, reason)` pairs from `validate::missing_sections`.
+///
+/// Returns:
+/// - The original question followed by a `## Missing Sections` section.
+pub fn build_missing_sections_followup(question: &str, missing: &[(&str, String)]) -> String {
+    use std::fmt::Write;
+    let mut s = question.to_string();
+
+    writeln!(s, "\n## Missing Sections").ok();
+    writeln!(
+        s,
+        "A previous answer left out sections this function needs:"
+    )
+    .ok();
+    for (name, why) in missing {
+        writeln!(s, "- `{name}:` because of {why}").ok();
     }
+    writeln!(
+        s,
+        "Rewrite the documentation and include these sections, describing exactly when it panics or what callers must guarantee."
+    )
+    .ok();
+
+    s
+}
 
-    writeln!(s, "\n---\n## Output Requirements").ok();
+/// Extends a function or struct prompt with merge instructions for an item that already has docs.
+///
+/// Used by `--merge`: the hand-written summary and sections are kept verbatim, so the model is asked only
+/// for the standard sections the existing doc lacks.
+///
+/// Parameters:
+/// - `question`: The rendered prompt (which already shows the existing doc).
+/// - `existing_sections`: Canonical names of the sections the existing doc has.
+///
+/// Returns:
+/// - The question followed by a `## Merge Mode` section.
+pub fn build_merge_request(question: &str, existing_sections: &[String]) -> String {
+    use std::fmt::Write;
+    let mut s = question.to_string();
+
+    writeln!(s, "\n## Merge Mode").ok();
     writeln!(
         s,
-        "Respond in **structured JSON** (no prose) with this shape:"
+        "The existing summary{} will be kept exactly as written.",
+        if existing_sections.is_empty() {
+            String::new()
+        } else {
+            format!(" and the sections {}", existing_sections.join(", "))
+        }
     )
     .ok();
     writeln!(
         s,
-        r#"{{
-  "struct_doc": "/// short summary...\n/// ...",
-  "fields": [
-    {{ "name": "field_name", "doc": "/// one-line or short doc...\n/// ..." }}
-  ]
-}}"#
+        "Return only the sections that are missing and relevant (for example `Errors:`, `Panics:` or `Examples:`), each under its own heading. Do not repeat the summary or any existing section."
     )
     .ok();
+
+    s
+}
+
+/// Builds the review prompt for the critique pass: the item's identity, its code and the draft doc to check.
+///
+/// The model is asked to either answer with the single word `APPROVED` or return a corrected Rustdoc block;
+/// the answer is interpreted by `sanitize::parse_critique_verdict`.
+///
+/// Parameters:
+/// - `row`: The harvested item the draft was written for.
+/// - `body_text`: The item body (function body or struct body); truncated like the generation prompt.
+/// - `draft`: The sanitized draft rustdoc block.
+///
+/// Returns:
+/// - A `String` containing the formatted review prompt.
+pub fn build_critique_request(row: &Row, body_text: &str, draft: &str) -> String {
+    use std::fmt::Write;
+    let mut s = String::new();
+
+    writeln!(s, "# Rust Documentation Review Task").ok();
     writeln!(
         s,
-        "- `struct_doc`: A short 1–2 sentence rustdoc for the struct (above attributes)."
+        "You are given a Rust item and a draft Rustdoc comment written for it."
     )
     .ok();
-    writeln!(s, "- `fields`: One entry **per named field** appearing in the struct body; the `doc` value must be a ready-to-insert `///` block for that field (keep it short, include units/invariants if relevant).").ok();
+
+    writeln!(s, "\n## Item Identity").ok();
+    writeln!(s, "- **Kind**: `{}`", row.kind).ok();
+    writeln!(s, "- **Fully-qualified path**: `{}`", row.fqpath).ok();
+    writeln!(s, "- **Signature**: `{}`", row.signature).ok();
+
+    if !body_text.trim().is_empty() {
+        writeln!(s, "\n## Body (Truncated)").ok();
+        let trimmed = truncate_for_context(body_text, 8000, 400);
+        writeln!(s, "```rust\n{}\n```", trimmed).ok();
+    }
+
+    writeln!(s, "\n## Draft Documentation").ok();
+    writeln!(s, "```rust\n{}\n```", draft.trim()).ok();
+
+    writeln!(s, "\n---\n## Output Requirements\n\
+        - If the draft is accurate and complete, reply with the single word `APPROVED`.\n\
+        - Otherwise return **ONLY** the corrected Rustdoc block; every line MUST start with `///`.\n\
+        - Do not mention parameters, fields or items that are not in the code above."
+    ).ok();
 
     s
 }
@@ -271,6 +574,7 @@ mod tests {
             doc: doc.map(|s| s.to_string()),
             body_text: body.map(|s| s.to_string()),
             callers: Some(vec!["crate::main::run".into()]),
+            file_hash: None,
         }
     }
 
@@ -289,6 +593,7 @@ mod tests {
             doc: doc.map(|s| s.to_string()),
             body_text: None,
             callers: None,
+            file_hash: None,
         }
     }
 
@@ -317,10 +622,10 @@ mod tests {
         assert_eq!(out, s, "FULL OUTPUT:\n{out}");
     }
 
-    // ---------- build_markdown_question (no existing doc) ----------
+    // ---------- render_fn_prompt (built-in template) ----------
 
     #[test]
-    fn test_build_markdown_question_no_existing_doc_includes_core_sections() {
+    fn test_render_fn_prompt_no_existing_doc_includes_core_sections() {
         let row = mk_row_fn(None, Some("fn hello(){ let _x = 1; }"));
         let refs = vec!["Foo".to_string(), "Bar".to_string()];
         let calls = vec![
@@ -336,7 +641,7 @@ mod tests {
             },
         ];
 
-        let out = build_markdown_question(&row, &refs, &calls);
+        let out = render_fn_prompt(default_fn_prompt(), &row, &refs, &calls, &[]);
 
         // identity
         assert!(out.contains("## Function Identity"), "FULL OUTPUT:\n{out}");
@@ -401,7 +706,7 @@ mod tests {
     }
 
     #[test]
-    fn test_build_markdown_question_includes_only_first_50_calls() {
+    fn test_render_fn_prompt_includes_only_first_50_calls() {
         let row = mk_row_fn(None, None);
         let refs: Vec<String> = vec![];
         // 60 calls -> should only list 50
@@ -413,7 +718,7 @@ mod tests {
             })
             .collect();
 
-        let out = build_markdown_question(&row, &refs, &calls);
+        let out = render_fn_prompt(default_fn_prompt(), &row, &refs, &calls, &[]);
         let count = out.matches("- **plain** call → `").count();
         assert_eq!(
             count, 50,
@@ -427,9 +732,9 @@ mod tests {
     }
 
     #[test]
-    fn test_build_markdown_question_with_existing_doc_embeds_code_block() {
+    fn test_render_fn_prompt_with_existing_doc_embeds_code_block() {
         let row = mk_row_fn(Some("Existing doc\nMore lines"), Some("fn body() {}"));
-        let out = build_markdown_question(&row, &[], &[]);
+        let out = render_fn_prompt(default_fn_prompt(), &row, &[], &[], &[]);
         // Should embed the trimmed doc in a rust code block
         assert!(
             out.contains("The function already has Rustdoc."),
@@ -441,13 +746,13 @@ mod tests {
         );
     }
 
-    // ---------- build_struct_request_with_refs ----------
+    // ---------- render_struct_prompt (built-in template) ----------
 
     #[test]
-    fn test_build_struct_request_with_refs_no_existing_doc_and_no_refs() {
+    fn test_render_struct_prompt_no_existing_doc_and_no_refs() {
         let srow = mk_row_struct(None);
         let body = "pub struct Widget { pub w: usize }";
-        let out = build_struct_request_with_refs(&srow, body, &[]);
+        let out = render_struct_prompt(default_struct_prompt(), &srow, body, &[]);
 
         assert!(
             out.contains("# Rust Struct Documentation Task"),
@@ -489,13 +794,13 @@ mod tests {
     }
 
     #[test]
-    fn test_build_struct_request_with_refs_limits_to_100_refs() {
+    fn test_render_struct_prompt_limits_to_100_refs() {
         let srow = mk_row_struct(None);
         let body = "pub struct Widget { pub w: usize }";
 
         let all_refs: Vec<String> = (0..150).map(|i| format!("crate::f::{i}")).collect();
 
-        let out = build_struct_request_with_refs(&srow, body, &all_refs);
+        let out = render_struct_prompt(default_struct_prompt(), &srow, body, &all_refs);
 
         // Count how many "- `...`" lines for refs appear; expect 100
         let rendered = out
@@ -514,10 +819,10 @@ mod tests {
     }
 
     #[test]
-    fn test_build_struct_request_with_refs_shows_existing_doc_when_present() {
+    fn test_render_struct_prompt_shows_existing_doc_when_present() {
         let srow = mk_row_struct(Some("Existing struct doc.\nMore."));
         let body = "pub struct Widget { pub w: usize }";
-        let out = build_struct_request_with_refs(&srow, body, &[]);
+        let out = render_struct_prompt(default_struct_prompt(), &srow, body, &[]);
         assert!(
             out.contains("The struct already has Rustdoc."),
             "FULL OUTPUT:\n{out}"
@@ -527,4 +832,178 @@ mod tests {
             "FULL OUTPUT:\n{out}"
         );
     }
+
+    // ---------- build_unknown_refs_followup ----------
+
+    #[test]
+    fn test_build_unknown_refs_followup_appends_list() {
+        let out = build_unknown_refs_followup("# Task", &["Widget".into(), "frob".into()]);
+        assert!(out.starts_with("# Task"), "FULL OUTPUT:\n{out}");
+        assert!(out.contains("## Unknown References"), "FULL OUTPUT:\n{out}");
+        assert!(out.contains("- `Widget`"), "FULL OUTPUT:\n{out}");
+        assert!(out.contains("- `frob`"), "FULL OUTPUT:\n{out}");
+    }
+
+    // ---------- build_missing_sections_followup ----------
+
+    #[test]
+    fn test_build_missing_sections_followup_lists_sections_with_reasons() {
+        let out = build_missing_sections_followup(
+            "# Task",
+            &[
+                (
+                    "Panics",
+                    "`v[0]` (panics if the index is out of bounds)".into(),
+                ),
+                ("Safety", "the function is `unsafe`".into()),
+            ],
+        );
+        assert!(out.starts_with("# Task"), "FULL OUTPUT:\n{out}");
+        assert!(out.contains("## Missing Sections"), "FULL OUTPUT:\n{out}");
+        assert!(
+            out.contains("- `Panics:` because of `v[0]` (panics if the index is out of bounds)"),
+            "FULL OUTPUT:\n{out}"
+        );
+        assert!(
+            out.contains("- `Safety:` because of the function is `unsafe`"),
+            "FULL OUTPUT:\n{out}"
+        );
+    }
+
+    // ---------- build_merge_request ----------
+
+    #[test]
+    fn test_build_merge_request_names_the_kept_sections() {
+        let out = build_merge_request("# Task", &["Parameters".into(), "Returns".into()]);
+        assert!(out.starts_with("# Task"), "FULL OUTPUT:\n{out}");
+        assert!(out.contains("## Merge Mode"), "FULL OUTPUT:\n{out}");
+        assert!(
+            out.contains("The existing summary and the sections Parameters, Returns will be kept exactly as written."),
+            "FULL OUTPUT:\n{out}"
+        );
+        assert!(
+            out.contains("Do not repeat the summary or any existing section."),
+            "FULL OUTPUT:\n{out}"
+        );
+
+        let out = build_merge_request("# Task", &[]);
+        assert!(
+            out.contains("The existing summary will be kept exactly as written."),
+            "Without sections only the summary is mentioned.\nFULL OUTPUT:\n{out}"
+        );
+    }
+
+    // ---------- build_critique_request ----------
+
+    #[test]
+    fn test_build_critique_request_embeds_draft_signature_and_body() {
+        let row = mk_row_fn(None, Some("fn hello(x: i32) -> i32 { x + 1 }"));
+        let draft = "/// Adds one to `x`.";
+        let out = build_critique_request(&row, row.body_text.as_deref().unwrap_or(""), draft);
+        assert!(
+            out.starts_with("# Rust Documentation Review Task"),
+            "FULL OUTPUT:\n{out}"
+        );
+        assert!(
+            out.contains("- **Signature**: `pub fn hello(x: i32) -> i32`"),
+            "FULL OUTPUT:\n{out}"
+        );
+        assert!(
+            out.contains("```rust\nfn hello(x: i32) -> i32 { x + 1 }\n```"),
+            "Body should be embedded in a rust fence.\nFULL OUTPUT:\n{out}"
+        );
+        assert!(
+            out.contains("```rust\n/// Adds one to `x`.\n```"),
+            "Draft should be embedded verbatim.\nFULL OUTPUT:\n{out}"
+        );
+        assert!(out.contains("APPROVED"), "FULL OUTPUT:\n{out}");
+    }
+
+    // ---------- PromptTemplate ----------
+
+    #[test]
+    fn test_prompt_template_renders_placeholders_and_sections() {
+        let tpl = PromptTemplate::parse(
+            "t",
+            "Item {{ fqpath }}.{{#calls}} Calls:\n{{calls}}{{/calls}}{{^calls}} No calls.{{/calls}}",
+            FN_PROMPT_PLACEHOLDERS,
+        )
+        .expect("valid template");
+
+        let mut values = BTreeMap::from([("fqpath", "crate::f".to_string())]);
+        assert_eq!(tpl.render(&values), "Item crate::f. No calls.");
+
+        values.insert("calls", "- `g`".to_string());
+        assert_eq!(tpl.render(&values), "Item crate::f. Calls:\n- `g`");
+    }
+
+    #[test]
+    fn test_prompt_template_rejects_unknown_and_unbalanced() {
+        let err = PromptTemplate::parse(
+            "custom.md",
+            "{{fqpath}} {{fields}} {{#body}}open {{/calls}}",
+            FN_PROMPT_PLACEHOLDERS,
+        )
+        .expect_err("template must be rejected");
+        let Error::PromptTemplate { name, problems } = err else {
+            panic!("expected Error::PromptTemplate, got {err:?}");
+        };
+        assert_eq!(name, "custom.md");
+        assert!(
+            problems.iter().any(|p| p.contains("`{{fields}}`")),
+            "fields is a struct-only placeholder: {problems:?}"
+        );
+        assert!(
+            problems.iter().any(|p| p.contains("`{{/calls}}`")),
+            "{problems:?}"
+        );
+        assert!(
+            problems
+                .iter()
+                .any(|p| p.contains("section `body` is never closed")),
+            "{problems:?}"
+        );
+    }
+
+    #[test]
+    fn test_render_struct_prompt_lists_fields_and_callers() {
+        let tpl = PromptTemplate::parse(
+            "t",
+            "{{name}}\n{{fields}}\n{{callers}}",
+            STRUCT_PROMPT_PLACEHOLDERS,
+        )
+        .unwrap();
+        let srow = mk_row_struct(None);
+        let body = "{\n    pub w: usize,\n    #[serde(default)]\n    h: u8,\n}";
+        let out = render_struct_prompt(&tpl, &srow, body, &["crate::f".into()]);
+        assert_eq!(out, "Widget\n- `w`\n- `h`\n- `crate::f`");
+    }
+
+    #[test]
+    fn test_prompts_load_prefers_explicit_then_dir_then_builtin() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::write(dir.path().join("rustdoc_fn.md"), "dir {{fqpath}}").unwrap();
+        let explicit = dir.path().join("mine.md");
+        std::fs::write(&explicit, "explicit {{name}}").unwrap();
+        let row = mk_row_fn(None, None);
+
+        let p = Prompts::load(None, None, Some(dir.path())).unwrap();
+        assert_eq!(
+            render_fn_prompt(&p.fn_prompt, &row, &[], &[], &[]),
+            "dir crate::moda::modb::hello"
+        );
+        assert_eq!(p.struct_prompt.name, "built-in struct");
+
+        let p = Prompts::load(Some(&explicit), None, Some(dir.path())).unwrap();
+        assert_eq!(
+            render_fn_prompt(&p.fn_prompt, &row, &[], &[], &[]),
+            "explicit hello"
+        );
+
+        std::fs::write(&explicit, "{{nope}}").unwrap();
+        assert!(
+            Prompts::load(Some(&explicit), None, None).is_err(),
+            "unknown placeholders must fail at load time"
+        );
+    }
 }
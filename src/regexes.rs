@@ -135,6 +135,75 @@ pub fn re_attr() -> &'static Regex {
     RE.get_or_init(|| Regex::new(r#"^\s*#\["#).unwrap())
 }
 
+/// Returns a static, compiled regular expression that matches inline code spans delimited by single backticks.
+///
+/// Capture group 1 holds the text between the backticks. Spans never cross a line break, so the regex is meant
+/// to be applied to one doc line at a time (after fenced code blocks have been skipped by the caller).
+///
+/// # Returns
+/// - A `&'static Regex` matching `` `...` `` spans.
+///
+/// # Notes
+/// - Double-backtick spans (`` `` x `` ``) are not treated specially; they yield empty or partial captures
+///   which callers filter out with [`re_ident_path`].
+/// - The `OnceLock` ensures thread-safety and avoids redundant compilation.
+///
+/// # Examples
+/// ```rust
+/// let re = crate::regexes::re_code_span();
+/// let caps = re.captures("Returns a `Row` value").unwrap();
+///
+/// assert_eq!(&caps[1], "Row");
+/// ```
+pub fn re_code_span() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"`([^`\n]+)`").unwrap())
+}
+
+/// Returns a static, compiled regular expression that matches a bare Rust identifier or a `::`-separated path,
+/// optionally followed by `()` as models often write for functions and methods.
+///
+/// # Returns
+/// - A `&'static Regex` that matches the whole input (it is anchored at both ends).
+///
+/// # Notes
+/// - Generic arguments, references, macros (`name!`) and expressions such as `a.b` do not match; callers use this
+///   to decide whether a code span names an item at all.
+/// - Capture group 1 holds the path without the trailing `()`.
+///
+/// # Examples
+/// ```rust
+/// let re = crate::regexes::re_ident_path();
+///
+/// assert!(re.is_match("Pipeline::from_harvest()"));
+/// assert!(re.is_match("crate::model::Row"));
+/// assert!(!re.is_match("Vec<String>"));
+/// ```
+pub fn re_ident_path() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"^([A-Za-z_][A-Za-z0-9_]*(?:::[A-Za-z_][A-Za-z0-9_]*)*)(?:\(\))?$").unwrap()
+    })
+}
+
+/// Returns a statically allocated regular expression that matches a unified diff hunk header.
+///
+/// # Notes
+/// - Capture group 1 is the first line of the new side and group 2 its line count, which `git` omits when it
+///   is 1 (`@@ -12 +12 @@`).
+///
+/// # Examples
+/// ```rust
+/// let caps = crate::regexes::re_hunk_header().captures("@@ -40,3 +39,0 @@ impl Parser {").unwrap();
+///
+/// assert_eq!(&caps[1], "39");
+/// assert_eq!(&caps[2], "0");
+/// ```
+pub fn re_hunk_header() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^@@ -\d+(?:,\d+)? \+(\d+)(?:,(\d+))? @@").unwrap())
+}
+
 /// Searches for a line matching a given regular expression near a specified starting line in a string source.
 ///
 /// The function scans forward from `start_line0` up to 20 lines ahead, then backward from 5 lines before `start_line0`
@@ -0,0 +1,323 @@
+use crate::error::{Error, Result};
+use crate::model::LlmDocResult;
+use crate::sidecar::markdown;
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Default directory `run` writes its artifacts to.
+pub const OUT_DIR: &str = "target/llm_rustdocs";
+
+/// Format of the generated-docs artifact written by `run`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    /// Every result as one pretty-printed JSON array (`docs.json`), the input of `apply`.
+    Json,
+    /// One result per line (`docs.jsonl`), for tools that stream them.
+    Jsonl,
+    /// Signature, current doc and generated doc of every item, grouped by file (`docs.md`).
+    Markdown,
+    /// The markdown report as a standalone page (`docs.html`).
+    Html,
+}
+
+impl ReportFormat {
+    /// The file name the artifact is written to in the output directory.
+    pub fn file_name(self) -> &'static str {
+        match self {
+            Self::Json => "docs.json",
+            Self::Jsonl => "docs.jsonl",
+            Self::Markdown => "docs.md",
+            Self::Html => "docs.html",
+        }
+    }
+}
+
+/// Results grouped by file, each file's items in line order.
+fn by_file(results: &[LlmDocResult]) -> BTreeMap<&str, Vec<&LlmDocResult>> {
+    let mut files: BTreeMap<&str, Vec<&LlmDocResult>> = BTreeMap::new();
+    for r in results {
+        files.entry(&r.file).or_default().push(r);
+    }
+    for items in files.values_mut() {
+        items.sort_by_key(|r| r.start_line.unwrap_or(0));
+    }
+    files
+}
+
+/// A code fence longer than any backtick run in `text`, so docs containing fences can be shown verbatim.
+fn fence_for(text: &str) -> String {
+    let longest = text.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    "`".repeat(longest.max(2) + 1)
+}
+
+/// Renders the review report in markdown: per file, each item's signature, then its current and generated doc
+/// in a two-column table (GitHub renders the markdown inside the cells).
+pub fn render_markdown(results: &[LlmDocResult]) -> String {
+    let files = by_file(results);
+    let mut out = String::from("# Generated docs\n\n");
+    let _ = writeln!(out, "{} item(s) in {} file(s).", results.len(), files.len());
+    for (file, items) in files {
+        let _ = write!(out, "\n## `{file}`\n");
+        for r in items {
+            let line = r
+                .start_line
+                .map(|l| format!(", line {l}"))
+                .unwrap_or_default();
+            let _ = write!(out, "\n### `{}` ({}{line})\n\n", r.fqpath, r.kind);
+            let sig_fence = fence_for(&r.signature);
+            let _ = write!(
+                out,
+                "{sig_fence}rust\n{}\n{sig_fence}\n\n",
+                r.signature.trim_end()
+            );
+
+            out.push_str("<table>\n<tr><th>Current doc</th><th>Generated doc</th></tr>\n<tr>\n");
+            for doc in [r.existing_doc.as_deref(), Some(r.llm_doc.as_str())] {
+                match doc.map(markdown).filter(|md| !md.trim().is_empty()) {
+                    Some(md) => {
+                        let fence = fence_for(&md);
+                        let _ = write!(out, "<td>\n\n{fence}markdown\n{md}{fence}\n\n</td>\n");
+                    }
+                    None => out.push_str("<td><em>none</em></td>\n"),
+                }
+            }
+            out.push_str("</tr>\n</table>\n");
+
+            for w in &r.warnings {
+                let _ = writeln!(out, "\n> ⚠️ {w}");
+            }
+        }
+    }
+    out
+}
+
+/// Escapes text for use in HTML element content and attribute values.
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Renders the review report as a standalone HTML page: per file, a table with one row per item holding its
+/// signature, current doc and generated doc side by side.
+pub fn render_html(results: &[LlmDocResult]) -> String {
+    let files = by_file(results);
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Generated docs</title>\n<style>\n\
+         body { font-family: sans-serif; margin: 2em; }\n\
+         table { border-collapse: collapse; width: 100%; margin-bottom: 2em; }\n\
+         th, td { border: 1px solid #ccc; padding: 0.5em; vertical-align: top; text-align: left; }\n\
+         pre { margin: 0; white-space: pre-wrap; }\n\
+         .none { color: #888; }\n\
+         .warning { color: #a60; }\n\
+         </style>\n</head>\n<body>\n<h1>Generated docs</h1>\n",
+    );
+    let _ = writeln!(
+        out,
+        "<p>{} item(s) in {} file(s).</p>",
+        results.len(),
+        files.len()
+    );
+    for (file, items) in files {
+        let _ = writeln!(out, "<h2><code>{}</code></h2>", escape_html(file));
+        out.push_str(
+            "<table>\n<tr><th>Item</th><th>Signature</th><th>Current doc</th><th>Generated doc</th></tr>\n",
+        );
+        for r in items {
+            let line = r
+                .start_line
+                .map(|l| format!(", line {l}"))
+                .unwrap_or_default();
+            let _ = write!(
+                out,
+                "<tr>\n<td><code>{}</code><br>{}{line}",
+                escape_html(&r.fqpath),
+                escape_html(&r.kind)
+            );
+            for w in &r.warnings {
+                let _ = write!(out, "<p class=\"warning\">{}</p>", escape_html(w));
+            }
+            let _ = writeln!(
+                out,
+                "</td>\n<td><pre><code>{}</code></pre></td>",
+                escape_html(r.signature.trim_end())
+            );
+            for doc in [r.existing_doc.as_deref(), Some(r.llm_doc.as_str())] {
+                match doc.map(markdown).filter(|md| !md.trim().is_empty()) {
+                    Some(md) => {
+                        let _ = writeln!(out, "<td><pre>{}</pre></td>", escape_html(md.trim_end()));
+                    }
+                    None => out.push_str("<td class=\"none\">none</td>\n"),
+                }
+            }
+            out.push_str("</tr>\n");
+        }
+        out.push_str("</table>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Renders `results` in `format`.
+///
+/// Errors:
+/// - `Error::Json` if a result cannot be serialized.
+pub fn render(results: &[LlmDocResult], format: ReportFormat) -> Result<String> {
+    let json_err = |e| Error::Json {
+        context: "serialize generated docs",
+        source: e,
+    };
+    Ok(match format {
+        ReportFormat::Json => serde_json::to_string_pretty(results).map_err(json_err)?,
+        ReportFormat::Jsonl => {
+            let mut out = String::new();
+            for r in results {
+                out.push_str(&serde_json::to_string(r).map_err(json_err)?);
+                out.push('\n');
+            }
+            out
+        }
+        ReportFormat::Markdown => render_markdown(results),
+        ReportFormat::Html => render_html(results),
+    })
+}
+
+/// Writes one artifact per format to `out_dir`, creating it if needed.
+///
+/// Returns:
+/// - The paths written, in `formats` order.
+///
+/// Errors:
+/// - `Error::Io` if the directory or a file cannot be written; `Error::Json` as for [`render`].
+pub fn write_reports(
+    out_dir: &Path,
+    results: &[LlmDocResult],
+    formats: &[ReportFormat],
+) -> Result<Vec<PathBuf>> {
+    fs::create_dir_all(out_dir).map_err(|e| Error::Io {
+        path: Some(out_dir.to_path_buf()),
+        source: e,
+    })?;
+    let mut written = Vec::new();
+    for &format in formats {
+        let path = out_dir.join(format.file_name());
+        fs::write(&path, render(results, format)?).map_err(|e| Error::Io {
+            path: Some(path.clone()),
+            source: e,
+        })?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Reads results written by `run`: a JSON array, or one JSON object per line when the file ends in `.jsonl`.
+///
+/// Errors:
+/// - `Error::Io` if the file cannot be read; `Error::Json` if it (or, for JSONL, any non-blank line) does not
+///   parse.
+pub fn load_results(path: &Path) -> Result<Vec<LlmDocResult>> {
+    let text = fs::read_to_string(path).map_err(|e| Error::Io {
+        path: Some(path.to_path_buf()),
+        source: e,
+    })?;
+    let json_err = |e| Error::Json {
+        context: "parse generated docs",
+        source: e,
+    };
+    if path.extension().is_some_and(|e| e == "jsonl") {
+        text.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| serde_json::from_str(l).map_err(json_err))
+            .collect()
+    } else {
+        serde_json::from_str(&text).map_err(json_err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mk(file: &str, fqpath: &str, line: u32, existing: Option<&str>, doc: &str) -> LlmDocResult {
+        LlmDocResult {
+            kind: "fn".into(),
+            fqpath: fqpath.into(),
+            file: file.into(),
+            start_line: Some(line),
+            signature: format!("pub fn {}()", fqpath.rsplit("::").next().unwrap()),
+            llm_doc: doc.into(),
+            had_existing_doc: existing.is_some(),
+            existing_doc: existing.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    // ---------- markdown / html ----------
+
+    #[test]
+    fn test_reports_group_by_file_and_show_both_docs() {
+        let results = [
+            mk("src/b.rs", "crate::b::late", 9, None, "/// Late."),
+            mk(
+                "src/a.rs",
+                "crate::a::f",
+                3,
+                Some("/// Old <f>."),
+                "/// New.\n///\n/// ```\n/// f();\n/// ```",
+            ),
+            mk("src/b.rs", "crate::b::early", 2, None, "/// Early."),
+        ];
+
+        let md = render_markdown(&results);
+        let a = md.find("## `src/a.rs`").expect("a section per file");
+        let early = md
+            .find("### `crate::b::early` (fn, line 2)")
+            .expect("item heading");
+        let late = md.find("### `crate::b::late`").expect("item heading");
+        assert!(
+            a < early && early < late,
+            "files in path order, items in line order:\n{md}"
+        );
+        assert!(
+            md.contains("````markdown\nNew.\n\n```\nf();\n```\n````"),
+            "a doc with a code fence gets a longer fence:\n{md}"
+        );
+        assert!(md.contains("Old <f>."), "the current doc is shown");
+        assert_eq!(md.matches("<em>none</em>").count(), 2, "undocumented items");
+
+        let html = render_html(&results);
+        assert!(html.contains("<td><pre>Old &lt;f&gt;.</pre></td>\n<td><pre>New."));
+        assert_eq!(html.matches("<table>").count(), 2, "one table per file");
+    }
+
+    // ---------- jsonl ----------
+
+    #[test]
+    fn test_jsonl_round_trips_through_load_results() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let results = [
+            mk("src/a.rs", "crate::a::f", 3, Some("/// Old."), "/// New."),
+            mk("src/a.rs", "crate::a::g", 7, None, "/// G."),
+        ];
+        let written = write_reports(
+            &dir.path().join("out"),
+            &results,
+            &[ReportFormat::Jsonl, ReportFormat::Json],
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(&written[0]).unwrap().lines().count(),
+            2,
+            "one result per line"
+        );
+        for path in &written {
+            let loaded = load_results(path).unwrap();
+            assert_eq!(loaded.len(), 2, "{}", path.display());
+            assert_eq!(loaded[0].existing_doc.as_deref(), Some("/// Old."));
+        }
+    }
+}
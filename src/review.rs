@@ -0,0 +1,436 @@
+use crate::error::{Error, Result};
+use crate::lock::doc_fingerprint;
+use crate::model::LlmDocResult;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::process::Command as ProcCommand;
+
+/// The file review decisions are kept in between runs, under the output directory.
+pub const REVIEW_LOG: &str = "review.json";
+
+/// A decision that is remembered across runs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    /// Apply the proposed (or edited) doc.
+    Accepted,
+    /// Never apply this proposed doc.
+    Rejected,
+}
+
+/// The remembered decision for one item.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recorded {
+    /// [`doc_fingerprint`] of the proposed doc the decision was made on; a different proposal is asked again.
+    pub proposed: String,
+    /// What was decided.
+    pub verdict: Verdict,
+    /// The doc as edited in `$EDITOR`, applied instead of the proposal.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited: Option<String>,
+}
+
+/// Review decisions keyed by fqpath.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReviewLog {
+    /// Decisions keyed by fqpath.
+    pub decisions: BTreeMap<String, Recorded>,
+}
+
+impl ReviewLog {
+    /// Reads the log, or returns an empty one if the file does not exist.
+    ///
+    /// Errors:
+    /// - `Error::Io` if the file exists but cannot be read.
+    /// - `Error::Json` if it is not a review log.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let text = std::fs::read_to_string(path).map_err(|e| Error::Io {
+            path: Some(path.to_path_buf()),
+            source: e,
+        })?;
+        serde_json::from_str(&text).map_err(|e| Error::Json {
+            context: "parse review log",
+            source: e,
+        })
+    }
+
+    /// Writes the log as pretty-printed JSON, creating its directory if needed.
+    ///
+    /// Errors:
+    /// - `Error::Json` if serialization fails.
+    /// - `Error::Io` if the file cannot be written.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let io_err = |e| Error::Io {
+            path: Some(path.to_path_buf()),
+            source: e,
+        };
+        if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir).map_err(io_err)?;
+        }
+        let bytes = serde_json::to_vec_pretty(self).map_err(|e| Error::Json {
+            context: "serialize review log",
+            source: e,
+        })?;
+        std::fs::write(path, bytes).map_err(io_err)
+    }
+}
+
+/// The terminal a review runs on: answers are read from `input`, prompts written to `output`, and `editor`
+/// turns a doc into its edited version.
+pub struct Console<'a> {
+    /// Answer source (stdin in the CLI).
+    pub input: &'a mut dyn BufRead,
+    /// Prompt sink (stderr in the CLI, so `--diff` output on stdout stays clean).
+    pub output: &'a mut dyn Write,
+    /// Opens a doc for editing and returns the result (see [`edit_in_editor`]).
+    pub editor: &'a dyn Fn(&str) -> Result<String>,
+}
+
+/// What the reviewer answered for one item.
+enum Answer {
+    Accept,
+    Reject,
+    Edit,
+    SkipFile,
+    Quit,
+}
+
+impl Console<'_> {
+    fn say(&mut self, text: &str) -> Result<()> {
+        self.output
+            .write_all(text.as_bytes())
+            .and_then(|_| self.output.flush())
+            .map_err(|e| Error::Io {
+                path: None,
+                source: e,
+            })
+    }
+
+    /// Asks until a known answer is given; end of input counts as quitting.
+    fn ask(&mut self) -> Result<Answer> {
+        loop {
+            self.say("[a]ccept, [r]eject, [e]dit, [s]kip file, [q]uit? ")?;
+            let mut line = String::new();
+            let read = self.input.read_line(&mut line).map_err(|e| Error::Io {
+                path: None,
+                source: e,
+            })?;
+            if read == 0 {
+                self.say("\n")?;
+                return Ok(Answer::Quit);
+            }
+            match line.trim().to_ascii_lowercase().as_str() {
+                "a" | "accept" | "y" => return Ok(Answer::Accept),
+                "r" | "reject" | "n" => return Ok(Answer::Reject),
+                "e" | "edit" => return Ok(Answer::Edit),
+                "s" | "skip" => return Ok(Answer::SkipFile),
+                "q" | "quit" => return Ok(Answer::Quit),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Puts `///` in front of edited lines that lost it, so a doc edited as plain text still patches cleanly.
+///
+/// Only indentation in front of an existing `///` is dropped (the patcher re-indents the block); everything else
+/// keeps its whitespace, so indented code and nested list items survive the edit.
+fn as_doc_lines(text: &str) -> String {
+    text.trim_end()
+        .lines()
+        .map(|l| {
+            let t = l.trim_start();
+            if t.starts_with("///") {
+                t.to_string()
+            } else if t.is_empty() {
+                "///".to_string()
+            } else {
+                format!("/// {l}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Writes a doc to a temporary file, opens it in `$VISUAL` / `$EDITOR` (default `vi`) and reads it back.
+///
+/// Errors:
+/// - `Error::Io` if the temporary file cannot be written or read.
+/// - `Error::ToolSpawn` / `Error::ToolStatus` if the editor cannot be started or exits with an error.
+pub fn edit_in_editor(doc: &str) -> Result<String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".into());
+    let path =
+        std::env::temp_dir().join(format!("awful_rustdocs-review-{}.md", std::process::id()));
+    let io_err = |e| Error::Io {
+        path: Some(path.clone()),
+        source: e,
+    };
+    std::fs::write(&path, format!("{doc}\n")).map_err(io_err)?;
+
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = ProcCommand::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .map_err(|e| Error::ToolSpawn {
+            tool: "$EDITOR",
+            source: e,
+        })?;
+    if !status.success() {
+        return Err(Error::ToolStatus {
+            tool: "$EDITOR",
+            code: status.code(),
+            stderr_hint: Some(format!("{editor} exited with an error")),
+        });
+    }
+    let edited = std::fs::read_to_string(&path).map_err(io_err)?;
+    let _ = std::fs::remove_file(&path);
+    Ok(edited)
+}
+
+/// Asks, item by item, which pending doc edits to apply.
+///
+/// For every result in `pending` the console shows the item's signature, its current doc and the proposed
+/// doc. Decisions made on the same proposal in an earlier run are reused without asking.
+///
+/// Parameters:
+/// - `results`: Every generated doc, in patch order.
+/// - `pending`: Fqpaths the patcher would actually write; other results pass through untouched.
+/// - `current`: The items' docs as harvested, keyed by fqpath.
+/// - `log`: Remembered decisions; accepts, rejects and edits made here are added to it.
+/// - `console`: Where to ask.
+///
+/// Returns:
+/// - The results to patch: accepted items (with their edited doc, if any) and the non-pending results. Rejected
+///   items, items in skipped files and everything after quitting are dropped.
+///
+/// Errors:
+/// - `Error::Io` if the console cannot be read or written, and any error from the editor.
+pub fn review_results(
+    results: Vec<LlmDocResult>,
+    pending: &BTreeSet<String>,
+    current: &BTreeMap<String, String>,
+    log: &mut ReviewLog,
+    console: &mut Console<'_>,
+) -> Result<Vec<LlmDocResult>> {
+    let mut kept = Vec::with_capacity(results.len());
+    let mut skipped_files: BTreeSet<String> = BTreeSet::new();
+    let mut quit = false;
+
+    for mut r in results {
+        if !pending.contains(&r.fqpath) {
+            kept.push(r);
+            continue;
+        }
+        if quit || skipped_files.contains(&r.file) {
+            continue;
+        }
+
+        let proposed = doc_fingerprint(&r.llm_doc);
+        if let Some(rec) = log
+            .decisions
+            .get(&r.fqpath)
+            .filter(|d| d.proposed == proposed)
+        {
+            debug!(fqpath=%r.fqpath, verdict=?rec.verdict, "reusing review decision");
+            if rec.verdict == Verdict::Accepted {
+                if let Some(doc) = &rec.edited {
+                    r.llm_doc = doc.clone();
+                }
+                kept.push(r);
+            }
+            continue;
+        }
+
+        let location = match r.start_line {
+            Some(l) => format!("{}:{l}", r.file),
+            None => r.file.clone(),
+        };
+        let current_doc = current
+            .get(&r.fqpath)
+            .map(String::as_str)
+            .filter(|d| !d.trim().is_empty())
+            .unwrap_or("(none)");
+        console.say(&format!(
+            "\n── {} ({location})\n{}\n\ncurrent doc:\n{current_doc}\n\nproposed doc:\n{}\n\n",
+            r.fqpath, r.signature, r.llm_doc
+        ))?;
+
+        let (verdict, edited) = match console.ask()? {
+            Answer::Accept => (Verdict::Accepted, None),
+            Answer::Reject => (Verdict::Rejected, None),
+            Answer::Edit => {
+                let doc = as_doc_lines(&(console.editor)(&r.llm_doc)?);
+                console.say(&format!("edited doc:\n{doc}\n"))?;
+                (Verdict::Accepted, Some(doc))
+            }
+            Answer::SkipFile => {
+                skipped_files.insert(r.file.clone());
+                continue;
+            }
+            Answer::Quit => {
+                quit = true;
+                continue;
+            }
+        };
+        log.decisions.insert(
+            r.fqpath.clone(),
+            Recorded {
+                proposed,
+                verdict,
+                edited: edited.clone(),
+            },
+        );
+        if verdict == Verdict::Accepted {
+            if let Some(doc) = edited {
+                r.llm_doc = doc;
+            }
+            kept.push(r);
+        }
+    }
+
+    info!(
+        kept = kept.len(),
+        skipped_files = skipped_files.len(),
+        quit,
+        "review finished"
+    );
+    Ok(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---------- helpers ----------
+
+    fn mk_result(fqpath: &str, file: &str, doc: &str) -> LlmDocResult {
+        LlmDocResult {
+            kind: "fn".into(),
+            fqpath: fqpath.into(),
+            file: file.into(),
+            start_line: Some(1),
+            signature: format!("pub fn {}()", fqpath.rsplit("::").next().unwrap()),
+            llm_doc: doc.into(),
+            ..Default::default()
+        }
+    }
+
+    fn run(
+        results: Vec<LlmDocResult>,
+        answers: &str,
+        log: &mut ReviewLog,
+    ) -> (Vec<(String, String)>, String) {
+        let pending: BTreeSet<String> = results
+            .iter()
+            .filter(|r| r.fqpath != "crate::untouched")
+            .map(|r| r.fqpath.clone())
+            .collect();
+        let current = BTreeMap::from([("crate::a".to_string(), "/// Old a.".to_string())]);
+        let mut input = answers.as_bytes();
+        let mut output: Vec<u8> = Vec::new();
+        let editor = |_: &str| Ok("Edited b.\n\nMore.".to_string());
+        let mut console = Console {
+            input: &mut input,
+            output: &mut output,
+            editor: &editor,
+        };
+        let kept = review_results(results, &pending, &current, log, &mut console).unwrap();
+        (
+            kept.into_iter().map(|r| (r.fqpath, r.llm_doc)).collect(),
+            String::from_utf8(output).unwrap(),
+        )
+    }
+
+    // ---------- review_results ----------
+
+    #[test]
+    fn test_review_applies_answers_and_remembers_them() {
+        let results = || {
+            vec![
+                mk_result("crate::untouched", "src/lib.rs", "/// Kept."),
+                mk_result("crate::a", "src/lib.rs", "/// New a."),
+                mk_result("crate::b", "src/lib.rs", "/// New b."),
+                mk_result("crate::c", "src/lib.rs", "/// New c."),
+                mk_result("crate::d", "src/other.rs", "/// New d."),
+                mk_result("crate::e", "src/other.rs", "/// New e."),
+                mk_result("crate::f", "src/third.rs", "/// New f."),
+            ]
+        };
+        let mut log = ReviewLog::default();
+        let (kept, shown) = run(results(), "a\ne\nwhat\nr\ns\n", &mut log);
+        assert_eq!(
+            kept,
+            vec![
+                ("crate::untouched".to_string(), "/// Kept.".to_string()),
+                ("crate::a".to_string(), "/// New a.".to_string()),
+                (
+                    "crate::b".to_string(),
+                    "/// Edited b.\n///\n/// More.".to_string()
+                ),
+            ],
+            "rejected, skipped and unanswered items are dropped"
+        );
+        assert!(
+            shown.contains("pub fn a()\n\ncurrent doc:\n/// Old a.\n\nproposed doc:\n/// New a."),
+            "SHOWN:\n{shown}"
+        );
+        assert_eq!(log.decisions.len(), 3, "skip and quit are not remembered");
+        assert_eq!(log.decisions["crate::c"].verdict, Verdict::Rejected);
+
+        let (kept, shown) = run(results(), "q\n", &mut log);
+        assert_eq!(kept.len(), 3, "remembered decisions are reused: {kept:?}");
+        assert!(
+            !shown.contains("crate::a ") && shown.contains("crate::d "),
+            "only undecided items are asked again:\n{shown}"
+        );
+    }
+
+    // ---------- as_doc_lines ----------
+
+    #[test]
+    fn test_as_doc_lines_keeps_indentation_after_the_marker() {
+        let edited =
+            "Sums.\n\n- outer\n  - nested\n\n```\nfn f() {\n    g();\n}\n```\n    ///   Kept.\n";
+        assert_eq!(
+            as_doc_lines(edited),
+            "/// Sums.\n///\n/// - outer\n///   - nested\n///\n/// ```\n/// fn f() {\n///     g();\n/// }\n/// ```\n///   Kept.",
+            "only the indentation before an existing `///` is dropped"
+        );
+    }
+
+    #[test]
+    fn test_review_log_round_trip() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let path = dir.path().join("nested").join("review.json");
+        let mut log = ReviewLog::load(&path).unwrap();
+        log.decisions.insert(
+            "crate::a".into(),
+            Recorded {
+                proposed: doc_fingerprint("/// A."),
+                verdict: Verdict::Accepted,
+                edited: None,
+            },
+        );
+        log.save(&path).unwrap();
+        let loaded = ReviewLog::load(&path).unwrap();
+        assert_eq!(loaded.decisions, log.decisions);
+        assert!(
+            std::fs::read_to_string(&path)
+                .unwrap()
+                .contains("\"accepted\""),
+            "verdicts are stored in lowercase"
+        );
+    }
+}
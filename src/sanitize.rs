@@ -24,6 +24,181 @@ pub fn sanitize_llm_doc(raw: &str) -> String {
     strip_leading_empty_doc_lines(&s)
 }
 
+/// What the critique pass answered, as read by [`parse_critique_verdict`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum CritiqueVerdict {
+    /// The draft is accurate and is kept.
+    Approved,
+    /// The sanitized, corrected rustdoc block.
+    Revised(String),
+    /// The answer was empty: the draft was neither approved nor corrected.
+    Empty,
+}
+
+/// Interprets the answer of the critique pass as an approval, a corrected doc, or no verdict at all.
+///
+/// The answer is cleaned with [`sanitize_llm_doc`] first, so `<think>` blocks, `ANSWER:` markers and code
+/// fences are handled the same way as for drafts. If what remains is just the word `approved` (in any case,
+/// optionally wrapped in backticks, asterisks or followed by punctuation) the draft is kept.
+///
+/// Parameters:
+/// - `raw`: The raw LLM answer to the critique prompt.
+///
+/// Returns:
+/// - `CritiqueVerdict::Approved` when the draft was approved.
+/// - `CritiqueVerdict::Empty` when nothing but whitespace (or markup) is left; the caller keeps the draft.
+/// - `CritiqueVerdict::Revised(doc)` with the sanitized, corrected rustdoc block otherwise.
+pub fn parse_critique_verdict(raw: &str) -> CritiqueVerdict {
+    let doc = sanitize_llm_doc(raw);
+    let text: String = doc
+        .lines()
+        .map(|l| l.trim_start().trim_start_matches('/').trim())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let verdict = text
+        .trim_matches(|c: char| !c.is_alphanumeric())
+        .to_ascii_lowercase();
+    if verdict.is_empty() {
+        CritiqueVerdict::Empty
+    } else if verdict == "approved" {
+        CritiqueVerdict::Approved
+    } else {
+        CritiqueVerdict::Revised(doc)
+    }
+}
+
+/// Reflows the prose of a `///` block so no line is wider than `width` columns (including the `/// ` marker).
+///
+/// Paragraphs and list items are re-filled greedily word by word. Everything whose layout carries meaning is
+/// kept verbatim: fenced code blocks, tables (`|` rows), headings, indented code, and lines that are not `///`
+/// comments at all. List items keep their marker, and their continuation lines are indented to the item text.
+/// Words are never split, so long links and inline code spans that do not fit simply overflow on their own line.
+///
+/// Parameters:
+/// - `doc`: A sanitized rustdoc block (one `///` line per line, no indentation).
+/// - `width`: Maximum line width in columns, *excluding* any indentation the patcher adds in front of the block.
+///
+/// Returns:
+/// - The reflowed block.
+///
+/// Notes:
+/// - Widths below 24 are raised to 24 so deeply nested items still get a readable block.
+/// - Backtick spans containing spaces are treated as a single word.
+pub fn reflow_doc(doc: &str, width: usize) -> String {
+    let text_width = width.max(24) - 4;
+    let mut out: Vec<String> = Vec::new();
+    // (indent of the first line, indent of continuation lines, words)
+    let mut para: Option<(String, String, Vec<String>)> = None;
+    let mut in_fence = false;
+
+    let flush = |para: &mut Option<(String, String, Vec<String>)>, out: &mut Vec<String>| {
+        if let Some((first, rest, words)) = para.take() {
+            out.extend(fill_words(&words, &first, &rest, text_width));
+        }
+    };
+
+    for raw in doc.lines() {
+        let Some(body) = raw.trim_start().strip_prefix("///") else {
+            flush(&mut para, &mut out);
+            out.push(raw.to_string());
+            continue;
+        };
+        let content = body.strip_prefix(' ').unwrap_or(body);
+        let trimmed = content.trim_start();
+        let lead = content.len() - trimmed.len();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            flush(&mut para, &mut out);
+            in_fence = !in_fence;
+            out.push(raw.to_string());
+            continue;
+        }
+        let verbatim = in_fence
+            || trimmed.is_empty()
+            || trimmed.starts_with('|')
+            || trimmed.starts_with('#')
+            || (lead >= 4 && para.is_none())
+            || content.ends_with("  ")
+            || content.ends_with('\\');
+        if verbatim {
+            flush(&mut para, &mut out);
+            out.push(raw.to_string());
+            continue;
+        }
+
+        if let Some(marker) = list_marker(trimmed) {
+            flush(&mut para, &mut out);
+            let first = format!("{}{}", &content[..lead], marker);
+            let rest = " ".repeat(first.len());
+            let words = split_words(&trimmed[marker.len()..]);
+            para = Some((first, rest, words));
+            continue;
+        }
+
+        match &mut para {
+            Some((_, _, words)) => words.extend(split_words(trimmed)),
+            None => {
+                let indent = content[..lead].to_string();
+                para = Some((indent.clone(), indent, split_words(trimmed)));
+            }
+        }
+    }
+    flush(&mut para, &mut out);
+    out.join("\n")
+}
+
+/// Returns the list marker (including its trailing space) that `line` starts with, e.g. `- ` or `12. `.
+fn list_marker(line: &str) -> Option<&str> {
+    if line.starts_with("- ") || line.starts_with("* ") || line.starts_with("+ ") {
+        return Some(&line[..2]);
+    }
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && (line[digits..].starts_with(". ") || line[digits..].starts_with(") ")) {
+        return Some(&line[..digits + 2]);
+    }
+    None
+}
+
+/// Splits prose into words, keeping backtick code spans (which may contain spaces) together.
+fn split_words(text: &str) -> Vec<String> {
+    let mut words: Vec<String> = Vec::new();
+    let mut open = false;
+    for w in text.split_whitespace() {
+        if open {
+            let last = words.last_mut().expect("an open span has a first word");
+            last.push(' ');
+            last.push_str(w);
+        } else {
+            words.push(w.to_string());
+        }
+        if w.matches('`').count() % 2 == 1 {
+            open = !open;
+        }
+    }
+    words
+}
+
+/// Greedily fills `words` into `///` lines of at most `width` text columns.
+fn fill_words(words: &[String], first: &str, rest: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = first.to_string();
+    let mut has_word = false;
+    for w in words {
+        if has_word && line.chars().count() + 1 + w.chars().count() > width {
+            lines.push(format!("/// {}", line.trim_end()));
+            line = rest.to_string();
+            has_word = false;
+        }
+        if has_word {
+            line.push(' ');
+        }
+        line.push_str(w);
+        has_word = true;
+    }
+    lines.push(format!("/// {}", line.trim_end()));
+    lines
+}
+
 /// Removes XML-like tags from a string by matching and replacing occurrences of the specified tag,
 /// including self-closing or nested content within `<tag>...</tag>` boundaries. The pattern
 /// uses case-insensitive matching and handles whitespace and attribute variations.
@@ -306,6 +481,7 @@ fn coerce_to_rustdoc(raw: &str) -> String {
             "Parameters:" => *l = "## Parameters".into(),
             "Returns:" => *l = "## Returns".into(),
             "Errors:" => *l = "## Errors".into(),
+            "Panics:" => *l = "## Panics".into(),
             "Safety:" => *l = "## Safety".into(),
             "Notes:" => *l = "## Notes".into(),
             "Examples:" => *l = "## Examples".into(),
@@ -649,4 +825,91 @@ mod tests {
             got
         );
     }
+
+    // ---------- parse_critique_verdict ----------
+
+    #[test]
+    fn test_parse_critique_verdict_recognizes_approval_variants() {
+        for raw in [
+            "APPROVED",
+            "approved.",
+            "**Approved**",
+            "<think>looks fine</think>\n`APPROVED`",
+        ] {
+            assert_eq!(
+                parse_critique_verdict(raw),
+                CritiqueVerdict::Approved,
+                "Expected approval for {raw:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_critique_verdict_treats_empty_replies_as_no_verdict() {
+        for raw in ["", "  \n\t", "<think>hmm</think>\n", "```\n```"] {
+            assert_eq!(
+                parse_critique_verdict(raw),
+                CritiqueVerdict::Empty,
+                "Expected no verdict for {raw:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_critique_verdict_returns_sanitized_revision() {
+        let raw = "ANSWER: ```rust\n/// Adds one to `x`.\n///\n/// # Returns\n/// The incremented value.\n```";
+        let CritiqueVerdict::Revised(got) = parse_critique_verdict(raw) else {
+            panic!("revision expected");
+        };
+        assert!(got.starts_with("/// Adds one to `x`."), "FULL:\n{got}");
+        assert!(got.contains("/// # Returns"), "FULL:\n{got}");
+    }
+
+    // ---------- reflow_doc ----------
+
+    #[test]
+    fn test_reflow_doc_wraps_prose_and_list_items() {
+        let doc = "/// This summary line is much too long and should be wrapped to the configured width.\n///\n/// - `x`: A list item whose description runs long enough to need a continuation line.\n/// 2. Numbered.";
+        let got = reflow_doc(doc, 40);
+        assert_eq!(
+            got,
+            "/// This summary line is much too long\n/// and should be wrapped to the\n/// configured width.\n///\n/// - `x`: A list item whose description\n///   runs long enough to need a\n///   continuation line.\n/// 2. Numbered.",
+            "FULL:\n{}",
+            numbered(&got)
+        );
+        assert!(got.lines().all(|l| l.chars().count() <= 40));
+    }
+
+    #[test]
+    fn test_reflow_doc_keeps_fences_tables_headings_and_long_links() {
+        let doc = "/// # Examples\n/// ```rust\n/// let value_with_a_really_long_name = compute_something_expensive(1, 2, 3);\n/// ```\n/// | a | b |\n/// |---|---|\n/// See https://example.com/a/really/long/link/that/must/not/be/split/anywhere for details.";
+        let got = reflow_doc(doc, 40);
+        let kept: Vec<&str> = doc.lines().take(6).collect();
+        assert_eq!(
+            got.lines().take(6).collect::<Vec<_>>(),
+            kept,
+            "FULL:\n{got}"
+        );
+        assert!(
+            got.contains(
+                "/// https://example.com/a/really/long/link/that/must/not/be/split/anywhere"
+            ),
+            "long link must stay on one line:\n{}",
+            numbered(&got)
+        );
+    }
+
+    #[test]
+    fn test_reflow_doc_joins_short_lines_and_keeps_code_spans_whole() {
+        let doc = "/// Calls\n/// `Foo::bar(a, b)` when\n/// ready.";
+        assert_eq!(
+            reflow_doc(doc, 100),
+            "/// Calls `Foo::bar(a, b)` when ready."
+        );
+        let got = reflow_doc(doc, 24);
+        assert!(
+            got.lines().any(|l| l.contains("`Foo::bar(a, b)`")),
+            "code span must not be split:\n{got}"
+        );
+    }
 }
@@ -0,0 +1,197 @@
+use crate::model::Row;
+use crate::style::strip_doc_prefix;
+use crate::util::fqpath_to_filename;
+
+use std::path::{Component, Path, PathBuf};
+
+/// The markdown file the doc of `fqpath` is kept in under `dir`: `<dir>/<fqpath_to_filename>.md`.
+pub fn sidecar_path(dir: &Path, fqpath: &str) -> PathBuf {
+    dir.join(format!("{}.md", fqpath_to_filename(fqpath)))
+}
+
+/// Makes `path` absolute against the current directory and resolves `.` and `..` without touching the disk
+/// (the sidecar may not exist yet).
+fn normalize(path: &Path) -> PathBuf {
+    let abs = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+    let mut out = PathBuf::new();
+    for c in abs.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+/// The path of `to` relative to the directory `from`, joined with `/` so the same attribute builds on every
+/// platform.
+///
+/// Parameters:
+/// - `from`: The directory the path is relative to.
+/// - `to`: The target path.
+///
+/// Returns:
+/// - e.g. `../../docs/crate.a.f.md` for `from = src/a` and `to = docs/crate.a.f.md`.
+pub fn relative_path(from: &Path, to: &Path) -> String {
+    let (from, to) = (normalize(from), normalize(to));
+    let common = from
+        .components()
+        .zip(to.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let ups = from.components().count() - common;
+    std::iter::repeat_n("..".to_string(), ups)
+        .chain(
+            to.components()
+                .skip(common)
+                .map(|c| c.as_os_str().to_string_lossy().into_owned()),
+        )
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// The attribute that makes `sidecar` the doc of an item in `source_file`.
+///
+/// `include_str!` resolves its path against the directory of the file it appears in, so the path is relative
+/// to that: `#[doc = include_str!("../docs/crate.f.md")]` for an item of `src/lib.rs`.
+pub fn include_attr(source_file: &Path, sidecar: &Path) -> String {
+    let dir = source_file.parent().unwrap_or(Path::new(""));
+    format!("#[doc = include_str!({:?})]", relative_path(dir, sidecar))
+}
+
+/// The markdown a `///` doc block becomes in its sidecar: the comment prefix is stripped from every line.
+///
+/// Returns:
+/// - The text, ending in exactly one newline.
+pub fn markdown(doc: &str) -> String {
+    let mut md = doc
+        .lines()
+        .map(strip_doc_prefix)
+        .collect::<Vec<_>>()
+        .join("\n");
+    md.truncate(md.trim_end().len());
+    md.push('\n');
+    md
+}
+
+/// The path of a `#[doc = include_str!("...")]` line, as written.
+fn include_target(line: &str) -> Option<&str> {
+    let rest = line.trim().strip_prefix("#[doc")?.trim_start();
+    let rest = rest.strip_prefix('=')?.trim_start();
+    let rest = rest.strip_prefix("include_str!")?.trim_start();
+    let rest = rest.strip_prefix('(')?.trim_start().strip_prefix('"')?;
+    let (path, tail) = rest.split_once('"')?;
+    (tail.trim_start().strip_prefix(')')?.trim() == "]").then_some(path)
+}
+
+/// Replaces every `#[doc = include_str!("...")]` line of a doc with the file it includes, as `///` lines, so
+/// sidecar docs are fingerprinted, audited and merged by their text rather than by the include.
+///
+/// Parameters:
+/// - `doc`: The doc as harvested.
+/// - `source_file`: The file the doc is in; include paths are relative to its directory.
+///
+/// Returns:
+/// - The doc with includes resolved; an include whose file cannot be read is kept as is.
+pub fn resolve_includes(doc: &str, source_file: &Path) -> String {
+    let dir = source_file.parent().unwrap_or(Path::new(""));
+    doc.lines()
+        .map(|line| {
+            let Some(text) =
+                include_target(line).and_then(|p| std::fs::read_to_string(dir.join(p)).ok())
+            else {
+                return line.to_string();
+            };
+            text.trim_end()
+                .lines()
+                .map(|l| {
+                    if l.is_empty() {
+                        "///".to_string()
+                    } else {
+                        format!("/// {l}")
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A harvested item's doc with its sidecar includes resolved (see [`resolve_includes`]); empty when it has none.
+pub fn row_doc(row: &Row) -> String {
+    resolve_includes(row.doc.as_deref().unwrap_or(""), Path::new(&row.file))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---------- relative_path / include_attr ----------
+
+    #[test]
+    fn test_include_attr_is_relative_to_the_source_files_directory() {
+        let md = sidecar_path(Path::new("docs"), "crate::a::b::Thing::new");
+        assert_eq!(md, Path::new("docs/crate.a.b.Thing.new.md"));
+        assert_eq!(
+            include_attr(Path::new("src/a/b.rs"), &md),
+            r#"#[doc = include_str!("../../docs/crate.a.b.Thing.new.md")]"#,
+            "one `..` per directory between the source file and the common root"
+        );
+        assert_eq!(
+            include_attr(Path::new("./src/lib.rs"), Path::new("src/../docs/x.md")),
+            r#"#[doc = include_str!("../docs/x.md")]"#,
+            "`.` and `..` are resolved before comparing"
+        );
+        assert_eq!(
+            relative_path(Path::new("/w/src"), Path::new("/w/src/docs/x.md")),
+            "docs/x.md"
+        );
+    }
+
+    // ---------- resolve_includes ----------
+
+    #[test]
+    fn test_resolve_includes_reads_the_sidecar_next_to_the_source() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        std::fs::create_dir_all(dir.path().join("docs")).unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("docs/f.md"),
+            "Does f.\n\n# Errors\nNever.\n",
+        )
+        .unwrap();
+        let src = dir.path().join("src").join("lib.rs");
+
+        assert_eq!(
+            resolve_includes("    #[doc = include_str!(\"../docs/f.md\")]", &src),
+            "/// Does f.\n///\n/// # Errors\n/// Never."
+        );
+        let missing = "#[doc = include_str!(\"../docs/gone.md\")]";
+        assert_eq!(
+            resolve_includes(missing, &src),
+            missing,
+            "unreadable includes are kept"
+        );
+        assert_eq!(resolve_includes("/// Plain.", &src), "/// Plain.");
+    }
+
+    // ---------- markdown ----------
+
+    #[test]
+    fn test_markdown_strips_doc_prefixes_and_keeps_code_indentation() {
+        let doc =
+            "/// Does a thing.\n///\n/// ```\n/// if x {\n///     y();\n/// }\n/// ```\n///\n";
+        assert_eq!(
+            markdown(doc),
+            "Does a thing.\n\n```\nif x {\n    y();\n}\n```\n"
+        );
+    }
+}
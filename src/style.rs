@@ -0,0 +1,661 @@
+use crate::error::{Error, Result};
+
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+
+/// Canonical section names in the order they are listed when no profile says otherwise.
+pub const CANONICAL_SECTIONS: &[&str] = &[
+    "Parameters",
+    "Returns",
+    "Errors",
+    "Panics",
+    "Safety",
+    "Notes",
+    "Examples",
+];
+
+/// One titled section of a rustdoc block (`# Errors` and the lines under it).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    /// The canonical name (see [`CANONICAL_SECTIONS`]) or, for unrecognized headings, the heading text verbatim.
+    pub title: String,
+    /// The section's lines without the `///` prefix or the heading itself.
+    pub lines: Vec<String>,
+}
+
+/// A rustdoc block split into its summary and titled sections.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedDoc {
+    /// Lines before the first heading, without the `///` prefix.
+    pub summary: Vec<String>,
+    /// The sections in source order.
+    pub sections: Vec<Section>,
+}
+
+/// Maps a heading text to its canonical section name (`Arguments` → `Parameters`, `Example` → `Examples`, …).
+///
+/// # Returns
+/// The canonical name, or `None` when the heading is not one of the standard sections.
+pub fn canonical_section(title: &str) -> Option<&'static str> {
+    let t = title
+        .trim()
+        .trim_end_matches(':')
+        .trim()
+        .to_ascii_lowercase();
+    Some(match t.as_str() {
+        "parameters" | "params" | "arguments" | "args" => "Parameters",
+        "returns" | "return" | "return value" => "Returns",
+        "errors" | "error" => "Errors",
+        "panics" | "panic" => "Panics",
+        "safety" => "Safety",
+        "notes" | "note" => "Notes",
+        "examples" | "example" => "Examples",
+        _ => return None,
+    })
+}
+
+/// Removes the `///` marker and the single space that conventionally follows it.
+pub fn strip_doc_prefix(line: &str) -> &str {
+    let t = line.trim_start();
+    let t = t.strip_prefix("///").unwrap_or(t);
+    t.strip_prefix(' ').unwrap_or(t)
+}
+
+/// Recognizes a section heading line (already stripped of `///`) and returns its canonical title.
+///
+/// Accepted forms are markdown headings (`# Errors`, `## Parameters:`), bold headings (`**Returns:**`), and
+/// bare `Title:` lines when `Title` is a standard section name.
+fn heading_title(line: &str) -> Option<String> {
+    let title = heading_text(line)?;
+    Some(
+        canonical_section(&title)
+            .map(str::to_string)
+            .unwrap_or(title),
+    )
+}
+
+/// The text of a section heading line as written, without its markup (see [`heading_title`]).
+fn heading_text(line: &str) -> Option<String> {
+    let t = line.trim();
+    let title = if let Some(rest) = t.strip_prefix('#') {
+        let rest = rest.trim_start_matches('#');
+        if !rest.starts_with(' ') {
+            return None;
+        }
+        rest.trim().trim_end_matches(':').trim().to_string()
+    } else if t.starts_with("**") && t.trim_end_matches(':').ends_with("**") && t.len() > 4 {
+        t.trim_end_matches(':')
+            .trim_matches('*')
+            .trim()
+            .trim_end_matches(':')
+            .to_string()
+    } else if t.ends_with(':') && canonical_section(t).is_some() {
+        t.trim_end_matches(':').to_string()
+    } else {
+        return None;
+    };
+    (!title.is_empty()).then_some(title)
+}
+
+/// Splits a `///` block into its summary and sections.
+///
+/// Headings inside fenced code blocks are ignored, so hidden doc-test lines (`# use foo;`) are never
+/// mistaken for sections.
+///
+/// # Parameters
+/// - `doc`: A rustdoc block whose lines start with `///`.
+///
+/// # Returns
+/// The parsed doc; a block without headings has everything in `summary`.
+pub fn parse_doc(doc: &str) -> ParsedDoc {
+    let mut parsed = ParsedDoc::default();
+    let mut in_fence = false;
+    for raw in doc.lines() {
+        let line = strip_doc_prefix(raw);
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            if let Some(title) = heading_title(line) {
+                parsed.sections.push(Section {
+                    title,
+                    lines: vec![],
+                });
+                continue;
+            }
+        }
+        match parsed.sections.last_mut() {
+            Some(s) => s.lines.push(line.to_string()),
+            None => parsed.summary.push(line.to_string()),
+        }
+    }
+    parsed
+}
+
+/// Named rules for how the sections of a doc are titled and ordered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleProfile {
+    /// Profile name as selected on the command line or in the project file.
+    pub name: String,
+    /// Number of `#` in section headings (`1` renders `# Errors`, `2` renders `## Errors`).
+    pub heading_level: usize,
+    /// Display names for canonical sections; sections not listed keep their canonical name.
+    pub heading_names: BTreeMap<String, String>,
+    /// Canonical section order. Unknown sections follow the listed ones in their original order.
+    pub order: Vec<String>,
+    /// Whether a `Parameters` section may appear at all; when `false` it is dropped.
+    pub allow_parameters: bool,
+}
+
+impl StyleProfile {
+    /// The standard library convention: `# Errors` / `# Panics` / `# Safety` / `# Examples` as h1 headings,
+    /// and no Parameters section (parameters are described in prose).
+    pub fn rustdoc_std() -> Self {
+        Self {
+            name: "rustdoc-std".into(),
+            heading_level: 1,
+            heading_names: BTreeMap::new(),
+            order: ["Returns", "Errors", "Panics", "Safety", "Notes", "Examples"]
+                .map(String::from)
+                .to_vec(),
+            allow_parameters: false,
+        }
+    }
+
+    /// The format produced by `sanitize::coerce_to_rustdoc`: `## Parameters`, `## Returns`, … as h2 headings.
+    pub fn legacy() -> Self {
+        Self {
+            name: "legacy".into(),
+            heading_level: 2,
+            heading_names: BTreeMap::new(),
+            order: CANONICAL_SECTIONS.iter().map(|s| s.to_string()).collect(),
+            allow_parameters: true,
+        }
+    }
+
+    /// Looks up a built-in profile by name (`rustdoc-std` or `legacy`).
+    ///
+    /// # Errors
+    /// - `Error::External` if no built-in profile has that name.
+    pub fn builtin(name: &str) -> Result<Self> {
+        match name {
+            "rustdoc-std" | "std" => Ok(Self::rustdoc_std()),
+            "legacy" => Ok(Self::legacy()),
+            other => Err(Error::External {
+                context: "unknown style profile",
+                message: format!("'{other}' (expected 'rustdoc-std' or 'legacy')"),
+            }),
+        }
+    }
+
+    /// Rewrites a sanitized doc block so its sections follow this profile.
+    ///
+    /// The summary is kept as-is. Sections are retitled (canonical name mapped through `heading_names`,
+    /// rendered at `heading_level`), reordered according to `order`, separated by a single blank `///`,
+    /// and `Parameters` is dropped when the profile does not allow it. Blocks without sections are returned
+    /// unchanged.
+    ///
+    /// # Parameters
+    /// - `doc`: A sanitized `///` block.
+    ///
+    /// # Returns
+    /// The restyled `///` block.
+    pub fn apply(&self, doc: &str) -> String {
+        let parsed = parse_doc(doc);
+        if parsed.sections.is_empty() {
+            return doc.to_string();
+        }
+
+        let rank = |title: &str| {
+            self.order
+                .iter()
+                .position(|o| o == title)
+                .unwrap_or(self.order.len())
+        };
+        let mut sections: Vec<&Section> = parsed
+            .sections
+            .iter()
+            .filter(|s| self.allow_parameters || s.title != "Parameters")
+            .collect();
+        sections.sort_by_key(|s| rank(&s.title));
+
+        let headed: Vec<(String, &[String])> = sections
+            .into_iter()
+            .map(|s| {
+                let name = self.heading_names.get(&s.title).unwrap_or(&s.title);
+                (
+                    format!("{} {}", "#".repeat(self.heading_level.max(1)), name),
+                    s.lines.as_slice(),
+                )
+            })
+            .collect();
+        render_doc(&parsed.summary, &headed)
+    }
+}
+
+/// Renders a summary and `(heading line, body)` sections as a `///` block, one blank `///` between blocks.
+fn render_doc(summary: &[String], sections: &[(String, &[String])]) -> String {
+    let mut blocks: Vec<Vec<String>> = Vec::new();
+    let summary = trim_blank(summary);
+    if !summary.is_empty() {
+        blocks.push(summary.to_vec());
+    }
+    for (heading, lines) in sections {
+        let mut block = vec![heading.clone()];
+        block.extend(trim_blank(lines).iter().cloned());
+        blocks.push(block);
+    }
+
+    blocks
+        .iter()
+        .map(|b| {
+            b.iter()
+                .map(|l| {
+                    if l.is_empty() {
+                        "///".to_string()
+                    } else {
+                        format!("/// {l}")
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n///\n")
+}
+
+/// Returns the number of `#` used by the first markdown heading outside code fences, if any.
+fn heading_level_of(doc: &str) -> Option<usize> {
+    let mut in_fence = false;
+    for raw in doc.lines() {
+        let line = strip_doc_prefix(raw).trim_start();
+        if line.starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence && heading_title(line).is_some() && line.starts_with('#') {
+            return Some(line.chars().take_while(|c| *c == '#').count());
+        }
+    }
+    None
+}
+
+/// A doc block cut for merging: the summary and each standard section, as the original `///` lines.
+#[derive(Debug, Default)]
+struct RawDoc {
+    /// `///` lines before the first section.
+    summary: Vec<String>,
+    /// Canonical name and lines (heading first) of each section, in source order.
+    sections: Vec<(&'static str, Vec<String>)>,
+}
+
+/// The canonical section a line opens, when it is a heading of a standard section.
+fn canonical_heading(line: &str) -> Option<&'static str> {
+    heading_title(strip_doc_prefix(line)).and_then(|t| canonical_section(&t))
+}
+
+/// The number of `#` of a markdown heading line, or `None` for other lines.
+fn markdown_level(line: &str) -> Option<usize> {
+    let t = strip_doc_prefix(line).trim_start();
+    t.starts_with('#')
+        .then(|| t.chars().take_while(|c| *c == '#').count())
+}
+
+/// Splits a doc into its summary and standard sections without rewriting a byte of it.
+///
+/// Only headings of standard sections (see [`canonical_section`]) outside code fences start a section, and of
+/// markdown headings only those no deeper than the first such heading; other headings (`## Basic` under
+/// `# Examples`) stay in the body of the section they appear in. Lines lacking a `///` marker get one.
+fn split_raw(doc: &str) -> RawDoc {
+    let lines: Vec<String> = doc
+        .lines()
+        .map(|l| match l.trim_start() {
+            t if t.starts_with("///") => t.to_string(),
+            _ if l.trim().is_empty() => "///".to_string(),
+            _ => format!("/// {l}"),
+        })
+        .collect();
+
+    let mut top: Option<usize> = None;
+    let mut raw = RawDoc::default();
+    let mut in_fence = false;
+    for line in lines {
+        if strip_doc_prefix(&line).trim_start().starts_with("```") {
+            in_fence = !in_fence;
+        } else if !in_fence {
+            if let Some(name) = canonical_heading(&line) {
+                let level = markdown_level(&line);
+                let nested = matches!((level, top), (Some(l), Some(t)) if l > t);
+                if !nested {
+                    top = top.or(level);
+                    raw.sections.push((name, vec![line]));
+                    continue;
+                }
+            }
+        }
+        match raw.sections.last_mut() {
+            Some((_, body)) => body.push(line),
+            None => raw.summary.push(line),
+        }
+    }
+    raw
+}
+
+/// Merges a generated doc into a hand-written one without touching what a human wrote.
+///
+/// Equivalent to [`merge_docs_with`] with no post-processing of the generated parts.
+pub fn merge_docs(existing: &str, generated: &str) -> String {
+    merge_docs_with(existing, generated, str::to_string)
+}
+
+/// Merges a generated doc into a hand-written one, post-processing only the generated parts.
+///
+/// The existing summary and sections are kept byte for byte, headings and sub-headings included, in their
+/// original order. Generated sections are added only when the existing doc has no section of the same canonical
+/// name, each before the first existing section that comes after it in [`CANONICAL_SECTIONS`] order; the
+/// generated summary is used only when the existing doc has none.
+///
+/// Parameters:
+/// - `existing`: The hand-written doc, with or without `///` prefixes.
+/// - `generated`: The sanitized model answer, or an earlier merge result (its hand-written parts are skipped).
+/// - `finish`: Applied to the generated summary and sections that are kept, as one `///` block (house style,
+///   reflow); never to hand-written text.
+///
+/// Returns:
+/// - The merged `///` block. Added headings are markdown headings at the level of the existing doc's first
+///   heading, or of the generated doc's, or `##` as produced by the sanitizer.
+pub fn merge_docs_with(
+    existing: &str,
+    generated: &str,
+    finish: impl FnOnce(&str) -> String,
+) -> String {
+    let old = split_raw(existing);
+    let new = split_raw(generated);
+    let has_summary = !trim_blank_doc(&old.summary).is_empty();
+
+    // the generated parts the existing doc lacks, finished as a block of their own
+    let mut added: Vec<Vec<String>> = Vec::new();
+    if !has_summary {
+        added.push(new.summary);
+    }
+    added.extend(
+        new.sections
+            .into_iter()
+            .filter(|(name, _)| !old.sections.iter().any(|(o, _)| o == name))
+            .map(|(_, lines)| lines),
+    );
+    let added = split_raw(&finish(&join_blocks(added)));
+
+    let level = heading_level_of(existing)
+        .or_else(|| heading_level_of(generated))
+        .unwrap_or(2);
+    let rank = |name: &str| {
+        CANONICAL_SECTIONS
+            .iter()
+            .position(|c| *c == name)
+            .unwrap_or(CANONICAL_SECTIONS.len())
+    };
+    let mut sections: Vec<Vec<String>> = Vec::new();
+    let mut placed = 0;
+    let mut generated_sections = added.sections;
+    generated_sections.sort_by_key(|(name, _)| rank(name));
+    for (name, mut lines) in generated_sections {
+        if let Some(text) = heading_text(strip_doc_prefix(&lines[0])) {
+            lines[0] = format!("/// {} {text}", "#".repeat(level));
+        }
+        while placed < old.sections.len() && rank(old.sections[placed].0) <= rank(name) {
+            sections.push(old.sections[placed].1.clone());
+            placed += 1;
+        }
+        sections.push(lines);
+    }
+    sections.extend(old.sections[placed..].iter().map(|(_, l)| l.clone()));
+
+    let summary = if has_summary {
+        old.summary
+    } else {
+        added.summary
+    };
+    let mut blocks = vec![summary];
+    blocks.extend(sections);
+    join_blocks(blocks)
+}
+
+/// Joins `///` line blocks, each without its surrounding blank lines, with one blank `///` between them.
+fn join_blocks(blocks: Vec<Vec<String>>) -> String {
+    blocks
+        .iter()
+        .map(|b| trim_blank_doc(b))
+        .filter(|b| !b.is_empty())
+        .map(|b| b.join("\n"))
+        .collect::<Vec<_>>()
+        .join("\n///\n")
+}
+
+/// Returns `///` lines without leading and trailing lines that are blank after the marker.
+fn trim_blank_doc(lines: &[String]) -> &[String] {
+    let blank = |l: &String| strip_doc_prefix(l).trim().is_empty();
+    let start = lines.iter().position(|l| !blank(l)).unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|l| !blank(l))
+        .map_or(start, |i| i + 1);
+    &lines[start..end]
+}
+
+/// Returns `lines` without leading and trailing blank lines.
+fn trim_blank(lines: &[String]) -> &[String] {
+    let start = lines
+        .iter()
+        .position(|l| !l.trim().is_empty())
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|l| !l.trim().is_empty())
+        .map_or(start, |i| i + 1);
+    &lines[start..end]
+}
+
+/// A style selection as written in the project file: either a built-in name or a customized profile.
+///
+/// ```yaml
+/// style: rustdoc-std
+/// # or
+/// style:
+///   base: legacy
+///   heading_level: 1
+///   headings: { Parameters: Arguments }
+///   order: [Parameters, Returns, Errors, Examples]
+///   allow_parameters: true
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum StyleSetting {
+    /// A built-in profile name.
+    Named(String),
+    /// A built-in profile with overrides.
+    Custom {
+        /// Built-in profile the overrides apply to.
+        base: String,
+        /// Overrides [`StyleProfile::heading_level`].
+        heading_level: Option<usize>,
+        /// Extends [`StyleProfile::heading_names`].
+        #[serde(default)]
+        headings: BTreeMap<String, String>,
+        /// Overrides [`StyleProfile::order`].
+        order: Option<Vec<String>>,
+        /// Overrides [`StyleProfile::allow_parameters`].
+        allow_parameters: Option<bool>,
+    },
+}
+
+impl StyleSetting {
+    /// Resolves the setting into a concrete profile.
+    ///
+    /// # Errors
+    /// - `Error::External` if the (base) profile name is not a built-in profile.
+    pub fn resolve(&self) -> Result<StyleProfile> {
+        match self {
+            StyleSetting::Named(name) => StyleProfile::builtin(name),
+            StyleSetting::Custom {
+                base,
+                heading_level,
+                headings,
+                order,
+                allow_parameters,
+            } => {
+                let mut p = StyleProfile::builtin(base)?;
+                p.name = format!("{base} (customized)");
+                if let Some(level) = heading_level {
+                    p.heading_level = *level;
+                }
+                for (k, v) in headings {
+                    let key = canonical_section(k)
+                        .map(str::to_string)
+                        .unwrap_or(k.clone());
+                    p.heading_names.insert(key, v.clone());
+                }
+                if let Some(order) = order {
+                    p.order = order
+                        .iter()
+                        .map(|o| {
+                            canonical_section(o)
+                                .map(str::to_string)
+                                .unwrap_or(o.clone())
+                        })
+                        .collect();
+                }
+                if let Some(allow) = allow_parameters {
+                    p.allow_parameters = *allow;
+                }
+                Ok(p)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ---------- parse_doc ----------
+
+    #[test]
+    fn test_parse_doc_recognizes_heading_forms_and_skips_fences() {
+        let doc = "/// Summary line.\n///\n/// Parameters:\n/// - `x`: input\n/// **Returns:**\n/// The value.\n/// # Examples\n/// ```rust\n/// # use foo;\n/// ```\n/// ## Arguments\n/// - more";
+        let p = parse_doc(doc);
+        assert_eq!(p.summary, vec!["Summary line.", ""]);
+        let titles: Vec<&str> = p.sections.iter().map(|s| s.title.as_str()).collect();
+        assert_eq!(
+            titles,
+            vec!["Parameters", "Returns", "Examples", "Parameters"],
+            "FULL DOC:\n{doc}"
+        );
+        assert_eq!(
+            p.sections[2].lines,
+            vec!["```rust", "# use foo;", "```"],
+            "hidden doc-test lines must stay inside the example"
+        );
+    }
+
+    // ---------- StyleProfile::apply ----------
+
+    #[test]
+    fn test_rustdoc_std_retitles_reorders_and_drops_parameters() {
+        let doc = "/// Does the thing.\n///\n/// ## Examples\n/// ```rust\n/// do_it();\n/// ```\n///\n/// ## Parameters\n/// - `x`: input\n///\n/// ## Errors\n/// - When it fails.";
+        let got = StyleProfile::rustdoc_std().apply(doc);
+        assert_eq!(
+            got,
+            "/// Does the thing.\n///\n/// # Errors\n/// - When it fails.\n///\n/// # Examples\n/// ```rust\n/// do_it();\n/// ```",
+            "FULL OUTPUT:\n{got}"
+        );
+    }
+
+    #[test]
+    fn test_legacy_profile_uses_h2_and_keeps_parameters() {
+        let doc = "/// Summary.\n/// # Returns\n/// A value.\n/// # Parameters\n/// - `x`: input";
+        let got = StyleProfile::legacy().apply(doc);
+        assert_eq!(
+            got,
+            "/// Summary.\n///\n/// ## Parameters\n/// - `x`: input\n///\n/// ## Returns\n/// A value.",
+            "FULL OUTPUT:\n{got}"
+        );
+    }
+
+    #[test]
+    fn test_apply_leaves_docs_without_sections_untouched() {
+        let doc = "/// A simple counter.";
+        assert_eq!(StyleProfile::rustdoc_std().apply(doc), doc);
+    }
+
+    // ---------- StyleSetting ----------
+
+    #[test]
+    fn test_style_setting_custom_overrides_base_profile() {
+        let yaml = "base: rustdoc-std\nheading_level: 2\nheadings: { Parameters: Arguments }\nallow_parameters: true\norder: [Parameters, Errors]";
+        let setting: StyleSetting = serde_yaml::from_str(yaml).expect("valid yaml");
+        let p = setting.resolve().expect("known base");
+        assert_eq!(p.heading_level, 2);
+        assert!(p.allow_parameters);
+
+        let got = p.apply("/// S.\n/// # Errors\n/// E.\n/// # Parameters\n/// P.");
+        assert_eq!(
+            got,
+            "/// S.\n///\n/// ## Arguments\n/// P.\n///\n/// ## Errors\n/// E."
+        );
+    }
+
+    #[test]
+    fn test_style_setting_rejects_unknown_profile() {
+        let setting = StyleSetting::Named("gnu".into());
+        assert!(setting.resolve().is_err());
+    }
+
+    // ---------- merge_docs ----------
+
+    #[test]
+    fn test_merge_docs_keeps_hand_written_parts_and_adds_missing_sections() {
+        let existing = "/// Loads the config.\n///   (hand-wrapped line kept as is)\n///\n/// # Examples\n/// ```\n/// # use demo::load;\n/// load();\n/// ```";
+        let generated = "/// Reads the configuration file.\n///\n/// ## Examples\n/// Bogus.\n///\n/// ## Errors\n/// - `Error::Io` if the file is missing.";
+        let got = merge_docs(existing, generated);
+        assert_eq!(
+            got,
+            "/// Loads the config.\n///   (hand-wrapped line kept as is)\n///\n/// # Errors\n/// - `Error::Io` if the file is missing.\n///\n/// # Examples\n/// ```\n/// # use demo::load;\n/// load();\n/// ```",
+            "FULL OUTPUT:\n{got}"
+        );
+    }
+
+    #[test]
+    fn test_merge_docs_keeps_hand_written_headings_and_sub_headings_verbatim() {
+        let existing = "/// Applies `f`.\n///\n/// # Arguments\n/// * `f` -  the function,   kept as typed\n///\n/// # Examples\n///\n/// ## Basic\n/// ```\n/// apply(|x| x);\n/// ```\n///\n/// ## Chained\n/// Call it twice.\n///\n/// Safety:\n/// None needed.";
+        let generated = "/// Calls the function.\n///\n/// ## Parameters\n/// - `f`: Bogus.\n///\n/// ## Panics\n/// Panics if `f` panics, which this long line says at length.";
+
+        let got = merge_docs_with(existing, generated, |g| {
+            StyleProfile::rustdoc_std().apply(&g.replace("at length", "AT LENGTH"))
+        });
+        assert_eq!(
+            got,
+            "/// Applies `f`.\n///\n/// # Arguments\n/// * `f` -  the function,   kept as typed\n///\n/// # Panics\n/// Panics if `f` panics, which this long line says AT LENGTH.\n///\n/// # Examples\n///\n/// ## Basic\n/// ```\n/// apply(|x| x);\n/// ```\n///\n/// ## Chained\n/// Call it twice.\n///\n/// Safety:\n/// None needed.",
+            "only the added Panics section is finished; `# Arguments`, the Examples sub-headings and `Safety:` are \
+             kept byte for byte.\nFULL OUTPUT:\n{got}"
+        );
+        assert_eq!(
+            merge_docs_with(existing, &got, |g| g.to_uppercase()),
+            got.replace(
+                "/// # Panics\n/// Panics if `f` panics, which this long line says AT LENGTH.",
+                "/// # PANICS\n/// PANICS IF `F` PANICS, WHICH THIS LONG LINE SAYS AT LENGTH."
+            ),
+            "re-merging a merged doc finishes only what was generated"
+        );
+    }
+
+    #[test]
+    fn test_merge_docs_uses_generated_summary_when_existing_has_none() {
+        let got = merge_docs(
+            "/// ## Safety\n/// Caller holds the lock.",
+            "/// Frees the slot.",
+        );
+        assert_eq!(
+            got,
+            "/// Frees the slot.\n///\n/// ## Safety\n/// Caller holds the lock."
+        );
+    }
+}
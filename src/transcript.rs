@@ -0,0 +1,337 @@
+use crate::error::{Error, Result};
+use crate::llm::{LlmClient, LlmRequest};
+use crate::model::LlmDocResult;
+use crate::util::{fqpath_to_filename, stable_hash};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info};
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+/// One request/response round trip with the model.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Exchange {
+    /// Name of the template the prompt was sent with (e.g. `rustdoc_fn`, `rustdoc_critique`).
+    pub template: String,
+    /// The fully rendered prompt.
+    pub prompt: String,
+    /// The raw answer, before sanitization.
+    pub response: String,
+    /// Wall-clock time of the request in milliseconds.
+    pub elapsed_ms: u64,
+}
+
+/// A sanitized doc produced for an item (the item itself or, for structs, one of its fields).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecordedDoc {
+    /// The kind of the documented item (`fn`, `struct` or `field`).
+    pub kind: String,
+    /// Fully-qualified path of the documented item.
+    pub fqpath: String,
+    /// The sanitized rustdoc block that ended up in `docs.json`.
+    pub llm_doc: String,
+}
+
+/// Everything that was sent to and received from the model for one harvested item.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transcript {
+    /// Fully-qualified path of the item.
+    pub fqpath: String,
+    /// The exchanges in the order they were made (draft, re-prompt, critique…).
+    pub exchanges: Vec<Exchange>,
+    /// The sanitized results; empty when the item was rejected.
+    pub results: Vec<RecordedDoc>,
+}
+
+/// Collects exchanges during a run and writes one transcript file per item (`--record <dir>`).
+pub struct Recorder {
+    /// Directory the transcripts are written to.
+    pub dir: PathBuf,
+    /// Exchanges recorded so far, keyed by item fqpath.
+    exchanges: RefCell<BTreeMap<String, Vec<Exchange>>>,
+}
+
+impl Recorder {
+    /// Creates a recorder writing into `dir`; nothing touches the disk until [`Recorder::write`].
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            exchanges: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    /// Remembers an exchange made on behalf of the item `fqpath`.
+    pub fn record(&self, fqpath: &str, exchange: Exchange) {
+        self.exchanges
+            .borrow_mut()
+            .entry(fqpath.to_string())
+            .or_default()
+            .push(exchange);
+    }
+
+    /// Writes `<dir>/<fqpath>.json` for every item with at least one exchange.
+    ///
+    /// Parameters:
+    /// - `results`: The generation results; an item's own result and, for structs, its field results are
+    ///   attached to its transcript.
+    ///
+    /// Returns:
+    /// - The number of transcript files written.
+    ///
+    /// Errors:
+    /// - `Error::Io` if the directory cannot be created or a file cannot be written.
+    /// - `Error::Json` if a transcript cannot be serialized.
+    pub fn write(&self, results: &[LlmDocResult]) -> Result<usize> {
+        let exchanges = self.exchanges.borrow();
+        if exchanges.is_empty() {
+            return Ok(0);
+        }
+        std::fs::create_dir_all(&self.dir).map_err(|e| Error::Io {
+            path: Some(self.dir.clone()),
+            source: e,
+        })?;
+
+        for (fqpath, ex) in exchanges.iter() {
+            let field_prefix = format!("{fqpath}::");
+            let transcript = Transcript {
+                fqpath: fqpath.clone(),
+                exchanges: ex.clone(),
+                results: results
+                    .iter()
+                    .filter(|r| {
+                        &r.fqpath == fqpath
+                            || (r.kind == "field" && r.fqpath.starts_with(&field_prefix))
+                    })
+                    .map(|r| RecordedDoc {
+                        kind: r.kind.clone(),
+                        fqpath: r.fqpath.clone(),
+                        llm_doc: r.llm_doc.clone(),
+                    })
+                    .collect(),
+            };
+            let path = self
+                .dir
+                .join(format!("{}.json", fqpath_to_filename(fqpath)));
+            let bytes = serde_json::to_vec_pretty(&transcript).map_err(|e| Error::Json {
+                context: "serialize transcript",
+                source: e,
+            })?;
+            std::fs::write(&path, bytes).map_err(|e| Error::Io {
+                path: Some(path.clone()),
+                source: e,
+            })?;
+            debug!(file = %path.display(), exchanges = ex.len(), "wrote transcript");
+        }
+        info!(dir = %self.dir.display(), items = exchanges.len(), "wrote transcripts");
+        Ok(exchanges.len())
+    }
+}
+
+/// Reads every `*.json` transcript in `dir`.
+///
+/// Errors:
+/// - `Error::Io` if the directory or a file cannot be read.
+/// - `Error::Json` if a file is not a valid transcript.
+pub fn load_transcripts(dir: &Path) -> Result<Vec<Transcript>> {
+    let entries = std::fs::read_dir(dir).map_err(|e| Error::Io {
+        path: Some(dir.to_path_buf()),
+        source: e,
+    })?;
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+
+    let mut out = Vec::with_capacity(paths.len());
+    for path in paths {
+        let text = std::fs::read_to_string(&path).map_err(|e| Error::Io {
+            path: Some(path.clone()),
+            source: e,
+        })?;
+        out.push(serde_json::from_str(&text).map_err(|e| Error::Json {
+            context: "parse transcript",
+            source: e,
+        })?);
+    }
+    Ok(out)
+}
+
+/// An offline backend that answers from recorded transcripts (`--replay <dir>`).
+///
+/// A request is matched on item, template and exact prompt first. If the prompt changed since recording
+/// (e.g. after editing the prompt builders), the recorded answers for the same item and template are
+/// handed out in their original order instead.
+pub struct ReplayClient {
+    /// Answers keyed by (fqpath, template, prompt hash).
+    exact: BTreeMap<(String, String, String), String>,
+    /// Answers keyed by (fqpath, template), in recording order.
+    ordered: RefCell<BTreeMap<(String, String), VecDeque<String>>>,
+}
+
+impl ReplayClient {
+    /// Loads all transcripts from `dir`.
+    ///
+    /// Errors:
+    /// - See [`load_transcripts`].
+    pub fn from_dir(dir: &Path) -> Result<Self> {
+        let transcripts = load_transcripts(dir)?;
+        info!(dir = %dir.display(), items = transcripts.len(), "loaded transcripts for replay");
+        Ok(Self::from_transcripts(transcripts))
+    }
+
+    /// Indexes already-loaded transcripts.
+    pub fn from_transcripts(transcripts: Vec<Transcript>) -> Self {
+        let mut exact = BTreeMap::new();
+        let mut ordered: BTreeMap<(String, String), VecDeque<String>> = BTreeMap::new();
+        for t in transcripts {
+            for ex in t.exchanges {
+                exact.insert(
+                    (
+                        t.fqpath.clone(),
+                        ex.template.clone(),
+                        stable_hash(&ex.prompt),
+                    ),
+                    ex.response.clone(),
+                );
+                ordered
+                    .entry((t.fqpath.clone(), ex.template))
+                    .or_default()
+                    .push_back(ex.response);
+            }
+        }
+        Self {
+            exact,
+            ordered: RefCell::new(ordered),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl LlmClient for ReplayClient {
+    /// Returns the recorded answer for the request.
+    ///
+    /// Errors:
+    /// - `Error::External` if nothing was recorded for the item and template.
+    async fn ask(&self, req: &LlmRequest<'_>) -> Result<String> {
+        let key = (
+            req.fqpath.to_string(),
+            req.template_name.to_string(),
+            stable_hash(&req.question),
+        );
+        if let Some(answer) = self.exact.get(&key) {
+            return Ok(answer.clone());
+        }
+        debug!(fqpath = %req.fqpath, "prompt changed since recording; replaying in order");
+        self.ordered
+            .borrow_mut()
+            .get_mut(&(key.0, key.1))
+            .and_then(|q| q.pop_front())
+            .ok_or_else(|| Error::External {
+                context: "no recorded answer for LLM request",
+                message: format!("{} ({})", req.fqpath, req.template_name),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use awful_aj::template::ChatTemplate;
+
+    // ---------- helpers ----------
+
+    fn mk_template() -> ChatTemplate {
+        ChatTemplate {
+            system_prompt: String::new(),
+            messages: vec![],
+            response_format: None,
+            pre_user_message_content: None,
+            post_user_message_content: None,
+        }
+    }
+
+    fn mk_exchange(template: &str, prompt: &str, response: &str) -> Exchange {
+        Exchange {
+            template: template.into(),
+            prompt: prompt.into(),
+            response: response.into(),
+            elapsed_ms: 5,
+        }
+    }
+
+    fn mk_result(kind: &str, fqpath: &str, doc: &str) -> LlmDocResult {
+        LlmDocResult {
+            kind: kind.into(),
+            fqpath: fqpath.into(),
+            file: "src/lib.rs".into(),
+            start_line: Some(1),
+            llm_doc: doc.into(),
+            ..Default::default()
+        }
+    }
+
+    // ---------- Recorder ----------
+
+    #[test]
+    fn test_recorder_writes_one_transcript_per_item_with_field_results() {
+        let dir = tempfile::tempdir().expect("tempdir");
+        let rec = Recorder::new(dir.path().join("rec"));
+        rec.record("crate::W", mk_exchange("rustdoc_struct", "p", "{}"));
+        rec.record("crate::f", mk_exchange("rustdoc_fn", "p1", "raw1"));
+        rec.record(
+            "crate::f",
+            mk_exchange("rustdoc_critique", "p2", "APPROVED"),
+        );
+
+        let results = vec![
+            mk_result("fn", "crate::f", "/// F."),
+            mk_result("struct", "crate::W", "/// W."),
+            mk_result("field", "crate::W::x", "/// X."),
+            mk_result("fn", "crate::W2", "/// Unrelated."),
+        ];
+        assert_eq!(rec.write(&results).unwrap(), 2);
+
+        let loaded = load_transcripts(&dir.path().join("rec")).unwrap();
+        let f = loaded.iter().find(|t| t.fqpath == "crate::f").unwrap();
+        assert_eq!(f.exchanges.len(), 2, "{f:#?}");
+        assert_eq!(f.exchanges[1].template, "rustdoc_critique");
+        assert_eq!(f.results.len(), 1, "{f:#?}");
+
+        let w = loaded.iter().find(|t| t.fqpath == "crate::W").unwrap();
+        let docs: Vec<&str> = w.results.iter().map(|r| r.fqpath.as_str()).collect();
+        assert_eq!(docs, vec!["crate::W", "crate::W::x"]);
+    }
+
+    // ---------- ReplayClient ----------
+
+    #[tokio::test]
+    async fn test_replay_client_matches_exact_prompt_then_falls_back_to_order() {
+        let client = ReplayClient::from_transcripts(vec![Transcript {
+            fqpath: "crate::f".into(),
+            exchanges: vec![
+                mk_exchange("rustdoc_fn", "first", "one"),
+                mk_exchange("rustdoc_fn", "second", "two"),
+            ],
+            results: vec![],
+        }]);
+        let tpl = mk_template();
+        let req = |q: &str| LlmRequest {
+            fqpath: "crate::f",
+            template_name: "rustdoc_fn",
+            template: &tpl,
+            question: q.into(),
+        };
+
+        assert_eq!(client.ask(&req("second")).await.unwrap(), "two");
+        assert_eq!(client.ask(&req("edited prompt")).await.unwrap(), "one");
+        assert_eq!(client.ask(&req("edited again")).await.unwrap(), "two");
+        assert!(
+            client.ask(&req("one too many")).await.is_err(),
+            "Expected error once the recorded answers are exhausted"
+        );
+    }
+}
@@ -168,6 +168,53 @@ pub fn extract_struct_fields_in_file(
     out
 }
 
+/// Turns a fully-qualified path into a file-name stem that is safe on every platform.
+///
+/// `::` separators become `.` and any character other than ASCII alphanumerics, `_` and `-` becomes `_`,
+/// so `crate::pipeline::Pipeline::<T>::wanted` maps to `crate.pipeline.Pipeline._T_.wanted`.
+///
+/// # Parameters
+/// - `fqpath`: The fully-qualified path of an item.
+///
+/// # Returns
+/// - The file-name stem (without extension).
+pub fn fqpath_to_filename(fqpath: &str) -> String {
+    fqpath
+        .split("::")
+        .map(|seg| {
+            seg.chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Computes a stable 64-bit FNV-1a hash of `text`, rendered as 16 lowercase hex digits.
+///
+/// Unlike `std::hash::DefaultHasher` the value never changes between Rust releases or runs, so it can be
+/// used to name files on disk (e.g. fixtures keyed by prompt).
+///
+/// # Parameters
+/// - `text`: The text to hash.
+///
+/// # Returns
+/// - The hash as a hex string.
+pub fn stable_hash(text: &str) -> String {
+    let mut h: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in text.as_bytes() {
+        h ^= u64::from(*b);
+        h = h.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("{h:016x}")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -440,4 +487,26 @@ mod tests {
             with_line_numbers(&src)
         );
     }
+
+    // ---------- fqpath_to_filename / stable_hash ----------
+
+    #[test]
+    fn test_fqpath_to_filename_replaces_separators_and_generics() {
+        assert_eq!(
+            fqpath_to_filename("crate::pipeline::Pipeline::wanted"),
+            "crate.pipeline.Pipeline.wanted"
+        );
+        assert_eq!(
+            fqpath_to_filename("crate::m::Wrapper<T>::get"),
+            "crate.m.Wrapper_T_.get"
+        );
+    }
+
+    #[test]
+    fn test_stable_hash_is_fixed_and_distinguishes_inputs() {
+        // Reference values for 64-bit FNV-1a.
+        assert_eq!(stable_hash(""), "cbf29ce484222325");
+        assert_eq!(stable_hash("a"), "af63dc4c8601ec8c");
+        assert_ne!(stable_hash("prompt one"), stable_hash("prompt two"));
+    }
 }
@@ -0,0 +1,362 @@
+use crate::grep::Hazard;
+use crate::regexes::{re_code_span, re_ident_path, re_word};
+use crate::style::parse_doc;
+
+use std::collections::BTreeSet;
+
+/// Names that are always considered valid inside generated docs even though they never appear in the harvest:
+/// primitives, prelude items, common `std` types and keywords models like to put in backticks.
+#[rustfmt::skip]
+const BUILTIN_NAMES: &[&str] = &[
+    // primitives
+    "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64",
+    "i128", "isize", "f32", "f64",
+    // prelude / std
+    "Option", "Some", "None", "Result", "Ok", "Err", "String", "Vec", "Box", "Rc", "Arc", "Cow",
+    "HashMap", "HashSet", "BTreeMap", "BTreeSet", "VecDeque", "Path", "PathBuf", "Default",
+    "Clone", "Copy", "Debug", "Display", "Iterator", "IntoIterator", "From", "Into", "AsRef",
+    "ToString", "PartialEq", "Eq", "PartialOrd", "Ord", "Hash", "Send", "Sync", "Sized", "Fn",
+    "FnMut", "FnOnce", "Drop", "Future", "Error",
+    // roots and keywords
+    "std", "core", "alloc", "crate", "self", "Self", "super", "true", "false", "mut", "ref",
+    "async", "await", "unsafe", "fn", "pub", "struct", "enum", "impl", "trait", "where", "dyn",
+    "let", "const", "static", "move", "return", "match", "if", "else", "loop", "while", "for",
+];
+
+/// Extracts the inline code spans of a rustdoc block that look like item references.
+///
+/// The doc is scanned line by line with the leading `///` removed. Fenced code blocks are skipped entirely,
+/// and only spans that are a bare identifier or a `::` path (optionally followed by `()`) are returned;
+/// things like `` `Vec<String>` ``, `` `&str` `` or `` `x + 1` `` are ignored.
+///
+/// # Parameters
+/// - `doc`: The sanitized rustdoc block (lines starting with `///`).
+///
+/// # Returns
+/// A deduplicated, sorted list of the referenced names with any trailing `()` removed.
+///
+/// # Examples
+/// ```rust
+/// let refs = doc_code_refs("/// Builds a `Pipeline` via `Pipeline::from_harvest()`.");
+/// assert_eq!(refs, vec!["Pipeline", "Pipeline::from_harvest"]);
+/// ```
+pub fn doc_code_refs(doc: &str) -> Vec<String> {
+    let mut out = BTreeSet::new();
+    let mut in_fence = false;
+    for raw in doc.lines() {
+        let line = raw.trim_start().trim_start_matches('/').trim_start();
+        if line.starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        for cap in re_code_span().captures_iter(line) {
+            let text = cap[1].trim();
+            if let Some(m) = re_ident_path().captures(text) {
+                out.insert(m[1].to_string());
+            }
+        }
+    }
+    out.into_iter().collect()
+}
+
+/// Collects every identifier that appears in an item's signature and body.
+///
+/// This covers the function's parameters, its local bindings, and any fields, methods or external items the
+/// body touches, so that a doc mentioning them is never flagged as hallucinated.
+///
+/// # Parameters
+/// - `signature`: The item signature as harvested.
+/// - `body`: The item body text (may be empty).
+///
+/// # Returns
+/// A set of identifier strings.
+pub fn code_identifiers(signature: &str, body: &str) -> BTreeSet<String> {
+    re_word()
+        .find_iter(signature)
+        .chain(re_word().find_iter(body))
+        .map(|m| m.as_str().to_string())
+        .collect()
+}
+
+/// Returns `true` when `name` is a primitive, prelude item, common `std` type or keyword.
+fn is_builtin(name: &str) -> bool {
+    BUILTIN_NAMES.contains(&name)
+}
+
+/// Decides whether a referenced name or path resolves against the known identifier set.
+///
+/// # Parameters
+/// - `reference`: A name as returned by [`doc_code_refs`] (e.g. `Row`, `crate::model::Row`, `Pipeline::wanted`).
+/// - `known`: Harvested symbol names, fully-qualified paths and code identifiers.
+///
+/// # Returns
+/// `true` if the reference is known.
+///
+/// # Notes
+/// - Leading `crate::`, `self::` and `super::` segments are ignored.
+/// - A path resolves when it is known in full or is the tail of a known fully-qualified path, so `model::Row` and
+///   `Pipeline::wanted` resolve but a made-up `Pipeline::frobnicate` does not.
+/// - Paths into a crate item are not resolved by their segments, except enum variants (`Kind::Fn`), which are
+///   not harvested: a capitalised last segment the item's code uses is accepted.
+/// - Paths rooted in `std`, a prelude name or anything that is not a crate item (an external crate) resolve when
+///   their first or last segment is known.
+fn resolves(reference: &str, known: &BTreeSet<String>) -> bool {
+    if known.contains(reference) {
+        return true;
+    }
+    let mut segs: Vec<&str> = reference.split("::").collect();
+    while segs.len() > 1 && matches!(segs[0], "crate" | "self" | "super") {
+        segs.remove(0);
+    }
+    let (first, last) = (segs[0], segs[segs.len() - 1]);
+    if segs.len() == 1 {
+        return is_builtin(first) || known.contains(first);
+    }
+    let tail_of_known = |path: &str| {
+        let suffix = format!("::{path}");
+        known.iter().any(|k| k.ends_with(&suffix))
+    };
+    let joined = segs.join("::");
+    if known.contains(&joined) || tail_of_known(&joined) || is_builtin(first) {
+        return true;
+    }
+    if tail_of_known(first) {
+        return last.starts_with(|c: char| c.is_ascii_uppercase()) && known.contains(last);
+    }
+    known.contains(first) || known.contains(last)
+}
+
+/// Lists the code references in a generated doc that do not resolve to anything in the crate.
+///
+/// # Parameters
+/// - `doc`: The sanitized rustdoc block.
+/// - `known`: Harvested symbol names and fully-qualified paths, plus the item's own code identifiers
+///   (see [`code_identifiers`]).
+///
+/// # Returns
+/// The unresolved references in sorted order; empty when every reference is known.
+///
+/// # Examples
+/// ```rust
+/// let known: BTreeSet<String> = ["Row".to_string()].into_iter().collect();
+/// let doc = "/// Converts a `Row` into a `Widget`.";
+///
+/// assert_eq!(unknown_refs(doc, &known), vec!["Widget"]);
+/// ```
+pub fn unknown_refs(doc: &str, known: &BTreeSet<String>) -> Vec<String> {
+    doc_code_refs(doc)
+        .into_iter()
+        .filter(|r| !resolves(r, known))
+        .collect()
+}
+
+/// Formats unresolved references as the warning strings recorded in `docs.json`.
+pub fn ref_warnings(unknown: &[String]) -> Vec<String> {
+    unknown
+        .iter()
+        .map(|r| format!("unknown reference `{r}`"))
+        .collect()
+}
+
+/// Returns `true` if the signature declares an `unsafe fn` (including `pub unsafe extern "C" fn` and the like).
+///
+/// Only the qualifiers before the parameter list count, after skipping a restricted visibility such as
+/// `pub(crate)`, so an `unsafe fn()` parameter type does not make the function unsafe.
+pub fn is_unsafe_fn(signature: &str) -> bool {
+    let mut rest = signature.trim_start();
+    if let Some(vis) = rest.strip_prefix("pub") {
+        let vis = vis.trim_start();
+        if vis.starts_with('(') {
+            rest = vis.find(')').map_or(vis, |close| &vis[close + 1..]);
+        }
+    }
+    let head = rest.split('(').next().unwrap_or("");
+    let words: Vec<&str> = head.split_whitespace().collect();
+    match (
+        words.iter().position(|w| *w == "unsafe"),
+        words.iter().position(|w| *w == "fn"),
+    ) {
+        (Some(u), Some(f)) => u < f,
+        _ => false,
+    }
+}
+
+/// Lists the sections a function doc must contain, with the reason for each.
+///
+/// A `Panics` section is required when the body contains an operation that can panic, and a `Safety`
+/// section when the function itself is `unsafe`. `unsafe` blocks inside a safe function do not require one.
+///
+/// # Parameters
+/// - `signature`: The function signature.
+/// - `hazards`: The operations found in the body (see [`crate::grep::hazards_in_span`]).
+///
+/// # Returns
+/// `(section, reason)` pairs, e.g. ``("Panics", "`v[0]` (panics if the index is out of bounds)")``.
+pub fn required_sections(signature: &str, hazards: &[Hazard]) -> Vec<(&'static str, String)> {
+    let mut out = Vec::new();
+    if let Some(h) = hazards.iter().find(|h| h.panics()) {
+        out.push(("Panics", h.describe()));
+    }
+    if is_unsafe_fn(signature) {
+        out.push(("Safety", "the function is `unsafe`".to_string()));
+    }
+    out
+}
+
+/// Returns the required sections that `doc` lacks, keeping their reasons.
+///
+/// Sections are matched by canonical name, so `# Panics`, `## Panics` and `Panics:` all count.
+pub fn missing_sections(
+    doc: &str,
+    required: &[(&'static str, String)],
+) -> Vec<(&'static str, String)> {
+    let parsed = parse_doc(doc);
+    required
+        .iter()
+        .filter(|(name, _)| !parsed.sections.iter().any(|s| s.title == *name))
+        .cloned()
+        .collect()
+}
+
+/// Formats missing sections as the warning strings recorded in `docs.json`.
+pub fn section_warnings(missing: &[(&'static str, String)]) -> Vec<String> {
+    missing
+        .iter()
+        .map(|(name, why)| format!("missing `# {name}` section: {why}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|s| s.to_string()).collect()
+    }
+
+    // ---------- doc_code_refs ----------
+
+    #[test]
+    fn test_doc_code_refs_picks_identifiers_and_paths_only() {
+        let doc = "/// Uses `Row`, `crate::model::Span` and `Pipeline::wanted()`.\n/// Not `Vec<String>` or `a + b`.";
+        let got = doc_code_refs(doc);
+        assert_eq!(
+            got,
+            vec!["Pipeline::wanted", "Row", "crate::model::Span"],
+            "FULL DOC:\n{doc}"
+        );
+    }
+
+    #[test]
+    fn test_doc_code_refs_skips_fenced_code() {
+        let doc =
+            "/// Summary with `Foo`.\n/// ```rust\n/// let x = `Bar`;\n/// ```\n/// After `Baz`.";
+        let got = doc_code_refs(doc);
+        assert_eq!(got, vec!["Baz", "Foo"], "FULL DOC:\n{doc}");
+    }
+
+    // ---------- unknown_refs ----------
+
+    #[test]
+    fn test_unknown_refs_reports_names_missing_from_known_set() {
+        let k = known(&["Row", "crate::model::Row"]);
+        let doc = "/// Turns a `Row` into a `Widget` via `frobnicate`.";
+        let got = unknown_refs(doc, &k);
+        assert_eq!(got, vec!["Widget", "frobnicate"], "FULL DOC:\n{doc}");
+    }
+
+    #[test]
+    fn test_unknown_refs_accepts_builtins_and_prefixed_paths() {
+        let k = known(&["crate::model::Row", "Row", "Error"]);
+        let doc = "/// Returns `Option<Row>` as `Some(row)` or `None`.\n/// See `crate::model::Row`, `model::Row`, `Error::Io` and `std::fs::read`.";
+        let got = unknown_refs(doc, &k);
+        assert!(got.is_empty(), "Expected all refs to resolve; got {got:?}");
+    }
+
+    #[test]
+    fn test_unknown_refs_reports_made_up_members_of_real_types() {
+        let mut k = known(&[
+            "Pipeline",
+            "crate::pipeline::Pipeline",
+            "crate::pipeline::Pipeline::wanted",
+            "Mode",
+            "crate::Mode",
+        ]);
+        k.extend(code_identifiers(
+            "fn f(m: Mode)",
+            "m == Mode::Strict; serde_json::to_string(&m)",
+        ));
+        let doc = "/// Calls `Pipeline::wanted`, not `Pipeline::frobnicate`; `Mode::Strict`, not `Mode::Lenient`.\n/// Uses `serde_json::to_string`.";
+        let got = unknown_refs(doc, &k);
+        assert_eq!(
+            got,
+            vec!["Mode::Lenient", "Pipeline::frobnicate"],
+            "a known type must not vouch for members it does not have"
+        );
+    }
+
+    #[test]
+    fn test_unknown_refs_uses_code_identifiers_for_params_and_locals() {
+        let mut k = known(&["Row"]);
+        k.extend(code_identifiers(
+            "fn run(rows: Vec<Row>, limit: usize)",
+            "let processed = 0; rows.len()",
+        ));
+        let doc = "/// Walks `rows` up to `limit`, counting in `processed`; see `missing`.";
+        let got = unknown_refs(doc, &k);
+        assert_eq!(got, vec!["missing"], "FULL DOC:\n{doc}");
+    }
+
+    #[test]
+    fn test_ref_warnings_format() {
+        let got = ref_warnings(&["Widget".to_string()]);
+        assert_eq!(got, vec!["unknown reference `Widget`"]);
+    }
+
+    // ---------- required_sections / missing_sections ----------
+
+    #[test]
+    fn test_is_unsafe_fn_checks_qualifiers_before_fn() {
+        assert!(is_unsafe_fn("pub unsafe fn read(p: *const u8) -> u8"));
+        assert!(is_unsafe_fn("pub unsafe extern \"C\" fn cb()"));
+        assert!(is_unsafe_fn("pub(crate) unsafe fn raw()"));
+        assert!(is_unsafe_fn("pub(in crate::a) const unsafe fn raw()"));
+        assert!(!is_unsafe_fn(
+            "pub(crate) fn call(f: unsafe fn(u8), g: unsafe extern \"C\" fn())"
+        ));
+        assert!(!is_unsafe_fn("pub fn run(unsafe_mode: bool)"));
+    }
+
+    #[test]
+    fn test_missing_sections_reports_panics_and_safety() {
+        let hazards = vec![
+            Hazard {
+                kind: "unsafe".into(),
+                text: "unsafe {…".into(),
+            },
+            Hazard {
+                kind: "unwrap".into(),
+                text: "opt.unwrap()".into(),
+            },
+        ];
+        let required = required_sections("pub unsafe fn f()", &hazards);
+        assert_eq!(
+            required.iter().map(|(n, _)| *n).collect::<Vec<_>>(),
+            vec!["Panics", "Safety"]
+        );
+
+        let doc = "/// Does it.\n///\n/// ## Panics\n/// - If `opt` is `None`.";
+        let missing = missing_sections(doc, &required);
+        assert_eq!(
+            section_warnings(&missing),
+            vec!["missing `# Safety` section: the function is `unsafe`"]
+        );
+        assert!(
+            required_sections("pub fn g()", &hazards[..1]).is_empty(),
+            "unsafe blocks in a safe fn do not require a section"
+        );
+    }
+}